@@ -0,0 +1,44 @@
+// check that LIR programs (.lir or .json) are valid and well-typed, as per lir-description.md
+
+use cs260::lir;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("Usage: cargo run --bin lir-check <lir_or_json_file>...");
+        std::process::exit(1);
+    }
+
+    let mut num_invalid = 0;
+    for fname in &args[1..] {
+        let prog = if fname.ends_with(".json") {
+            lir::Program::parse_json(fname)
+        } else {
+            lir::Program::parse_lir(fname)
+        };
+        let prog = match prog {
+            Ok(prog) => prog,
+            Err(err) => {
+                eprintln!("{}", err);
+                num_invalid += 1;
+                continue;
+            }
+        };
+        let diags = prog.validate();
+        for diag in &diags {
+            eprintln!("{}: {}", fname, diag);
+        }
+        let type_errors = lir::typeck::check(&prog);
+        for err in &type_errors {
+            eprintln!("{}: {}", fname, err);
+        }
+        if !diags.is_empty() || !type_errors.is_empty() {
+            num_invalid += 1;
+        }
+    }
+
+    if num_invalid > 0 {
+        eprintln!("{} of {} programs are invalid", num_invalid, args.len() - 1);
+        std::process::exit(1);
+    }
+}
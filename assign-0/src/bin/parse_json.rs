@@ -10,7 +10,10 @@ fn main() {
     }
     let json_fname = &args[1];
 
-    let prog = lir::Program::parse_json(json_fname);
+    let prog = lir::Program::parse_json(json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
 
     println!("{:#?}", prog.get_stats());
 }
@@ -6,7 +6,17 @@ use std::io::{BufReader, BufWriter};
 
 use crate::stats::Stats;
 
-#[derive(Serialize, Deserialize, Debug)]
+mod error;
+mod parser;
+mod printer;
+mod validate;
+
+pub mod typeck;
+
+pub use error::LirError;
+pub use validate::Diagnostic;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Program {
     structs: HashMap<String, Vec<Field>>,
     globals: Vec<Variable>,
@@ -14,19 +24,19 @@ pub struct Program {
     externs: HashMap<String, Type>,       // external function declarations
 }
 
-// #[derive(Serialize, Deserialize, Debug)]
+// #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 // pub struct Struct {
 //     // name: String,
 //     fields: Vec<Field>,
 // }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Field {
     name: String,
     typ: Type,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Type {
     Int,                         // "Int"
     Struct(String),              // {"Struct": "xxx"}
@@ -34,7 +44,7 @@ pub enum Type {
     Pointer(Box<Type>),          // {"Pointer": "xxx"}
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Variable {
     // it could be as parameter, local variable, or global variable
     name: String,
@@ -42,7 +52,7 @@ pub struct Variable {
     scope: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Function {
     id: String,
     ret_ty: Option<Type>,
@@ -51,13 +61,13 @@ pub struct Function {
     body: HashMap<String, Block>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct FunctionType {
     ret_ty: Option<Type>,
     param_ty: Vec<Type>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Block {
     /*
      {
@@ -83,7 +93,7 @@ pub struct Block {
     term: Terminal,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     // 10 kinds of instructions
     AddrOf {
@@ -146,7 +156,7 @@ pub enum Instruction {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ArithOp {
     // arithmetic operators
     Add,
@@ -155,7 +165,7 @@ pub enum ArithOp {
     Divide,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum RelaOp {
     // relational operators
     Neq,
@@ -166,7 +176,7 @@ pub enum RelaOp {
     GreaterEq,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Terminal {
     // a terminal signals the end of a basic block and is one of
     Jump(String), // {"Jump": "bb1"}
@@ -193,7 +203,7 @@ pub enum Terminal {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Operand {
     // an operand is either a variable or a constant
     Var(Variable),
@@ -210,10 +220,22 @@ impl Program {
         }
     }
 
-    pub fn parse_json(fname: &str) -> Program {
-        let file = File::open(fname).expect("Failed to open file");
+    pub fn parse_json(fname: &str) -> Result<Program, LirError> {
+        let file = File::open(fname).map_err(|e| LirError::from_io(fname, &e))?;
         let reader = BufReader::new(file);
-        json::from_reader(reader).unwrap()
+        json::from_reader(reader).map_err(|e| LirError::from_json(fname, &e))
+    }
+
+    pub fn parse_lir(fname: &str) -> Result<Program, LirError> {
+        let src = std::fs::read_to_string(fname).map_err(|e| LirError::from_io(fname, &e))?;
+        Program::parse_lir_str(&src).map_err(|e| LirError {
+            file: fname.to_string(),
+            ..e
+        })
+    }
+
+    pub fn parse_lir_str(src: &str) -> Result<Program, LirError> {
+        parser::parse(src)
     }
 
     pub fn write_json(&self, fname: &str) {
@@ -222,6 +244,10 @@ impl Program {
         json::to_writer(writer, self).unwrap();
     }
 
+    pub fn write_lir(&self, fname: &str) {
+        std::fs::write(fname, self.to_string()).expect("Failed to create file");
+    }
+
     pub fn get_stats(&self) -> Stats {
         let mut stats = Stats::new();
 
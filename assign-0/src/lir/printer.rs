@@ -0,0 +1,244 @@
+/*
+Pretty-printer emitting the concrete syntax of lir-description.md.
+
+Structs, globals, externs, functions, locals and basic blocks are printed sorted by name, so that
+the output is canonical and matches the reference lir2json/lir tools byte for byte.
+*/
+use super::*;
+use std::fmt;
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn sorted_decls(vars: &[Variable]) -> Vec<&Variable> {
+    let mut vars: Vec<&Variable> = vars.iter().collect();
+    vars.sort_by(|a, b| a.name.cmp(&b.name));
+    vars
+}
+
+fn fmt_ret_ty(ret_ty: &Option<Type>) -> String {
+    match ret_ty {
+        Some(typ) => typ.to_string(),
+        None => "_".to_string(),
+    }
+}
+
+fn fmt_decl(var: &Variable) -> String {
+    format!("{}:{}", var.name, var.typ)
+}
+
+fn fmt_lhs(lhs: &Option<Variable>) -> String {
+    match lhs {
+        Some(var) => format!("{} = ", var),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for name in sorted_keys(&self.structs) {
+            writeln!(f, "struct {} {{", name)?;
+            for field in &self.structs[name] {
+                writeln!(f, "  {}:{}", field.name, field.typ)?;
+            }
+            writeln!(f, "}}\n")?;
+        }
+
+        for global in sorted_decls(&self.globals) {
+            writeln!(f, "{}", fmt_decl(global))?;
+        }
+        if !self.globals.is_empty() {
+            writeln!(f)?;
+        }
+
+        for name in sorted_keys(&self.externs) {
+            writeln!(f, "extern {}:{}", name, self.externs[name])?;
+        }
+        if !self.externs.is_empty() {
+            writeln!(f)?;
+        }
+
+        for name in sorted_keys(&self.functions) {
+            writeln!(f, "{}", self.functions[name])?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self.params.iter().map(fmt_decl).collect();
+        writeln!(
+            f,
+            "fn {}({}) -> {} {{",
+            self.id,
+            params.join(", "),
+            fmt_ret_ty(&self.ret_ty)
+        )?;
+        if !self.locals.is_empty() {
+            let locals: Vec<String> = sorted_decls(&self.locals)
+                .into_iter()
+                .map(fmt_decl)
+                .collect();
+            writeln!(f, "let {}", locals.join(", "))?;
+        }
+        for (i, label) in sorted_keys(&self.body).into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", self.body[label])?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}:", self.id)?;
+        for inst in &self.insts {
+            writeln!(f, "  {}", inst)?;
+        }
+        writeln!(f, "  {}", self.term)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::AddrOf { lhs, rhs } => write!(f, "{} = $addrof {}", lhs, rhs),
+            Instruction::Alloc { lhs, num, id } => write!(f, "{} = $alloc {} [{}]", lhs, num, id),
+            Instruction::Copy { lhs, op } => write!(f, "{} = $copy {}", lhs, op),
+            Instruction::Gep { lhs, src, idx } => write!(f, "{} = $gep {} {}", lhs, src, idx),
+            Instruction::Arith { lhs, aop, op1, op2 } => {
+                write!(f, "{} = $arith {} {} {}", lhs, aop, op1, op2)
+            }
+            Instruction::Load { lhs, src } => write!(f, "{} = $load {}", lhs, src),
+            Instruction::Store { dst, op } => write!(f, "$store {} {}", dst, op),
+            Instruction::Gfp { lhs, src, field } => write!(f, "{} = $gfp {} {}", lhs, src, field),
+            Instruction::Cmp { lhs, rop, op1, op2 } => {
+                write!(f, "{} = $cmp {} {} {}", lhs, rop, op1, op2)
+            }
+            Instruction::CallExt {
+                lhs,
+                ext_callee,
+                args,
+            } => write!(
+                f,
+                "{}$call_ext {}({})",
+                fmt_lhs(lhs),
+                ext_callee,
+                join(args)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Terminal::Jump(label) => write!(f, "$jump {}", label),
+            Terminal::Branch { cond, tt, ff } => write!(f, "$branch {} {} {}", cond, tt, ff),
+            // the reference printer leaves a blank after a bare `$ret`
+            Terminal::Ret(None) => write!(f, "$ret "),
+            Terminal::Ret(Some(op)) => write!(f, "$ret {}", op),
+            Terminal::CallDirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => write!(
+                f,
+                "{}$call_dir {}({}) then {}",
+                fmt_lhs(lhs),
+                callee,
+                join(args),
+                next_bb
+            ),
+            Terminal::CallIndirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => write!(
+                f,
+                "{}$call_idr {}({}) then {}",
+                fmt_lhs(lhs),
+                callee,
+                join(args),
+                next_bb
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArithOp::Add => write!(f, "add"),
+            ArithOp::Subtract => write!(f, "sub"),
+            ArithOp::Multiply => write!(f, "mul"),
+            ArithOp::Divide => write!(f, "div"),
+        }
+    }
+}
+
+impl fmt::Display for RelaOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RelaOp::Neq => write!(f, "neq"),
+            RelaOp::Eq => write!(f, "eq"),
+            RelaOp::Less => write!(f, "lt"),
+            RelaOp::LessEq => write!(f, "lte"),
+            RelaOp::Greater => write!(f, "gt"),
+            RelaOp::GreaterEq => write!(f, "gte"),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::Function(func_ty) => write!(f, "{}", func_ty),
+            Type::Pointer(pointee) => write!(f, "&{}", pointee),
+        }
+    }
+}
+
+impl fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}) -> {}",
+            join(&self.param_ty),
+            fmt_ret_ty(&self.ret_ty)
+        )
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Var(var) => write!(f, "{}", var),
+            Operand::CInt(n) => write!(f, "{}", n),
+        }
+    }
+}
@@ -17,8 +17,24 @@ fn main() {
             println!("Testing parsing {} ...", fname);
 
             let stats = stats::Stats::read_stats(&stats_fname);
-            let program = lir::Program::parse_json(&json_fname);
+            let program = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
             assert_eq!(stats, program.get_stats());
+            let parsed = lir::Program::parse_lir(path_str).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+            assert_eq!(program, parsed);
+
+            // printing must give back the original text, and parsing it the same program
+            let text = fs::read_to_string(path_str).unwrap();
+            assert_eq!(text, program.to_string());
+            assert_eq!(
+                program,
+                lir::Program::parse_lir_str(&program.to_string()).unwrap()
+            );
         }
     }
 }
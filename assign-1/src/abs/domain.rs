@@ -183,20 +183,6 @@ impl Interval {
             Self::Range(_, u) => Some(u.clone()),
         }
     }
-    pub fn widen(&self, other: &Self) -> Self {
-        match (self, other) {
-            (Self::Bottom, _) => other.clone(),
-            (_, Self::Bottom) => self.clone(),
-            (Self::Top, Self::Top) => Self::Top,
-            (Self::Top, Self::Range(_, _)) => Self::Top,
-            (Self::Range(_, _), Self::Top) => Self::Top,
-            (Self::Range(l1, u1), Self::Range(l2, u2)) => {
-                let l = if l1 <= l2 { *l1 } else { Number::NInfinity };
-                let u = if u1 >= u2 { *u1 } else { Number::Infinity };
-                Self::Range(l, u)
-            }
-        }
-    }
     pub fn has_overlap(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Bottom, _) => false,
@@ -253,6 +239,13 @@ impl AbstractSemantics for Constant {
             _ => false,
         }
     }
+    fn contains(&self, val: i32) -> bool {
+        match self {
+            Self::Bottom => false,
+            Self::Top => true,
+            Self::CInt(c) => *c == val,
+        }
+    }
     fn join(&self, other: &Self) -> Self {
         match (self, other) {
             (Self::Bottom, _) => other.clone(),
@@ -267,6 +260,10 @@ impl AbstractSemantics for Constant {
             _ => Self::Top,
         }
     }
+    fn widen(&self, other: &Self) -> Self {
+        // the lattice has a finite height, joining is enough
+        self.join(other)
+    }
     fn arith(&self, other: &Self, op: &lir::ArithOp) -> Self {
         match (self, other) {
             (Self::Bottom, _) => Self::Bottom,
@@ -359,6 +356,13 @@ impl AbstractSemantics for Interval {
             _ => false,
         }
     }
+    fn contains(&self, val: i32) -> bool {
+        match self {
+            Self::Bottom => false,
+            Self::Top => true,
+            Self::Range(l, u) => *l <= Number::Integer(val) && Number::Integer(val) <= *u,
+        }
+    }
     fn join(&self, other: &Self) -> Self {
         match (self, other) {
             (Self::Bottom, _) => other.clone(),
@@ -373,6 +377,20 @@ impl AbstractSemantics for Interval {
             }
         }
     }
+    fn widen(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Bottom, _) => other.clone(),
+            (_, Self::Bottom) => self.clone(),
+            (Self::Top, Self::Top) => Self::Top,
+            (Self::Top, Self::Range(_, _)) => Self::Top,
+            (Self::Range(_, _), Self::Top) => Self::Top,
+            (Self::Range(l1, u1), Self::Range(l2, u2)) => {
+                let l = if l1 <= l2 { *l1 } else { Number::NInfinity };
+                let u = if u1 >= u2 { *u1 } else { Number::Infinity };
+                Self::Range(l, u)
+            }
+        }
+    }
 
     fn arith(&self, other: &Self, op: &lir::ArithOp) -> Self {
         match (self, other) {
@@ -6,17 +6,26 @@ use crate::lir;
 use crate::store;
 use crate::utils;
 use log;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    #[default]
+    Worklist, // FIFO worklist over the blocks
+    Recursive, // Bourdoncle's recursive strategy over the WTO, widening only at component heads
+}
 
 #[derive(Debug, Clone)]
 pub struct Analyzer<T> {
     pub prog: lir::Program,
     pub bb2store: HashMap<String, store::Store<T>>,
+    pub entry_store: store::Store<T>, // the store before entry
     pub reachable_successors: HashMap<String, Vec<String>>,
     pub cfg: cfg::ControlFlowGraph,
     pub worklist: VecDeque<lir::Block>,
     pub global_ints: Vec<lir::Variable>,
     pub addrof_ints: Vec<lir::Variable>,
+    pub strategy: Strategy,
     pub executed: bool,
 }
 
@@ -29,7 +38,7 @@ impl ConstantAnalyzer {
         let cfg = cfg::ControlFlowGraph::from_function(&prog, func_name);
         #[cfg(debug_assertions)]
         {
-            println!("CFG edges: {:?}", cfg.edges);
+            println!("CFG edges: {:?}", cfg.edges());
         }
         let reachable_successors: HashMap<String, Vec<String>> = HashMap::new();
         let mut worklist: VecDeque<lir::Block> = VecDeque::new();
@@ -58,16 +67,18 @@ impl ConstantAnalyzer {
         for bb_label in &cfg.get_all_block_labels() {
             bb2store.insert(bb_label.clone(), store::ConstantStore::new());
         }
-        bb2store.insert("entry".to_string(), entry_store);
+        bb2store.insert("entry".to_string(), entry_store.clone());
 
         Self {
             prog,
             bb2store,
+            entry_store,
             reachable_successors,
             cfg,
             worklist,
             global_ints,
             addrof_ints,
+            strategy: Strategy::Worklist,
             executed: false,
         }
     }
@@ -100,27 +111,139 @@ impl IntervalAnalyzer {
         for bb_label in &cfg.get_all_block_labels() {
             bb2store.insert(bb_label.clone(), store::IntervalStore::new());
         }
-        bb2store.insert("entry".to_string(), entry_store);
+        bb2store.insert("entry".to_string(), entry_store.clone());
 
         Self {
             prog,
             bb2store,
+            entry_store,
             reachable_successors,
             cfg,
             worklist,
             global_ints,
             addrof_ints,
+            strategy: Strategy::Worklist,
             executed: false,
         }
     }
 }
 
+impl<T> Analyzer<T>
+where
+    T: Clone + PartialEq + AbstractSemantics,
+    Analyzer<T>: AbstractExecution,
+{
+    fn mfp_recursive(&mut self) {
+        // stabilize the components of the WTO in order, where bb2store keeps the stores after
+        // each block; a block is executed once one of its predecessors reaches it
+        let entry_store = self.entry_store.clone();
+        let wto = self.cfg.weak_topological_order();
+        let mut head_stores = HashMap::new();
+        self.stabilize(&wto.components, &entry_store, &mut head_stores);
+    }
+
+    pub fn stores_before_instrs(&self) -> HashMap<String, Vec<store::Store<T>>> {
+        // after mfp, the stores before each instruction and before the terminal of each block
+        // reached, by executing it again from the join of the stores of its predecessors
+        let mut analyzer = self.clone();
+        let mut stores = HashMap::new();
+        for label in self.cfg.get_all_block_labels() {
+            let Some(store_in) = self.store_in(&label, &self.entry_store) else {
+                continue;
+            };
+            let block = self.cfg.get_block(&label).unwrap();
+            analyzer.bb2store.insert(label.clone(), store_in);
+            let mut block_stores = Vec::new();
+            for instr in &block.insts {
+                block_stores.push(analyzer.bb2store[&label].clone());
+                analyzer.exe_instr(instr, &label);
+            }
+            block_stores.push(analyzer.bb2store[&label].clone());
+            stores.insert(label, block_stores);
+        }
+        stores
+    }
+
+    fn stabilize(
+        &mut self,
+        components: &[cfg::WtoComponent],
+        entry_store: &store::Store<T>,
+        head_stores: &mut HashMap<String, store::Store<T>>,
+    ) {
+        for component in components {
+            match component {
+                cfg::WtoComponent::Vertex(label) => {
+                    if let Some(store_in) = self.store_in(label, entry_store) {
+                        self.exe_block_from(label, store_in);
+                    }
+                }
+                cfg::WtoComponent::Component { head, body } => loop {
+                    // iterate the component until the store before its head is stable
+                    let store_in = match (head_stores.get(head), self.store_in(head, entry_store)) {
+                        (_, None) => None,
+                        (None, Some(store_in)) => Some(store_in),
+                        (Some(old), Some(store_in)) => {
+                            if &old.join(&store_in) == old {
+                                break;
+                            }
+                            Some(old.widen(&store_in))
+                        }
+                    };
+                    let reached = store_in.is_some();
+                    if let Some(store_in) = store_in {
+                        head_stores.insert(head.clone(), store_in.clone());
+                        self.exe_block_from(head, store_in);
+                    }
+                    self.stabilize(body, entry_store, head_stores);
+                    if !reached && self.store_in(head, entry_store).is_none() {
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    fn store_in(&self, label: &str, entry_store: &store::Store<T>) -> Option<store::Store<T>> {
+        // the join of the stores of the predecessors reaching the block, None if there are none
+        let mut store_in = if label == "entry" {
+            Some(entry_store.clone())
+        } else {
+            None
+        };
+        for pred in self.cfg.get_predecessor_labels(label) {
+            let reaching = self
+                .reachable_successors
+                .get(&pred)
+                .is_some_and(|succs| succs.iter().any(|succ| succ == label));
+            if reaching {
+                let pred_store = self.bb2store.get(&pred).unwrap();
+                store_in = Some(match store_in {
+                    Some(store_in) => store_in.join(pred_store),
+                    None => pred_store.clone(),
+                });
+            }
+        }
+        store_in
+    }
+
+    fn exe_block_from(&mut self, label: &str, store_in: store::Store<T>) {
+        self.bb2store.insert(label.to_string(), store_in);
+        let block = self.cfg.get_block(label).unwrap().clone();
+        self.exe_block(&block);
+    }
+}
+
 impl AbstractExecution for ConstantAnalyzer {
     fn mfp(&mut self) {
         if self.executed {
             log::warn!("Already executed");
             return;
         }
+        if self.strategy == Strategy::Recursive {
+            self.executed = true;
+            self.mfp_recursive();
+            return;
+        }
         let mut visited: HashMap<String, u32> = HashMap::new(); // <bb_label, count>
         for bb_label in self.cfg.get_all_block_labels() {
             visited.insert(bb_label.clone(), 0);
@@ -182,8 +305,10 @@ impl AbstractExecution for ConstantAnalyzer {
         */
 
         // following is another more direct implementation of the MFP worklist algorithm
+        let mut queued: HashSet<String> = self.worklist.iter().map(|b| b.id.clone()).collect();
         while !self.worklist.is_empty() {
             let block = self.worklist.pop_front().unwrap();
+            queued.remove(&block.id);
             let store_before = self.bb2store.get(&block.id).unwrap().clone();
             if &block != self.cfg.get_entry().unwrap() {
                 let mut store_joined = store::ConstantStore::new();
@@ -204,9 +329,9 @@ impl AbstractExecution for ConstantAnalyzer {
             let store_after = self.bb2store.get(&block.id).unwrap().clone();
             if store_before != store_after || visited.get(&block.id).unwrap() == &0 {
                 for succ_label in self.reachable_successors.get(&block.id).unwrap() {
-                    let succ = self.cfg.get_block(&succ_label).unwrap().clone();
-                    if !self.worklist.contains(&succ) {
-                        self.worklist.push_back(succ.clone());
+                    if queued.insert(succ_label.clone()) {
+                        let succ = self.cfg.get_block(&succ_label).unwrap().clone();
+                        self.worklist.push_back(succ);
                     }
                 }
             }
@@ -600,6 +725,11 @@ impl AbstractExecution for IntervalAnalyzer {
             log::warn!("Already executed");
             return;
         }
+        if self.strategy == Strategy::Recursive {
+            self.executed = true;
+            self.mfp_recursive();
+            return;
+        }
         let loop_headers = self.cfg.get_loop_headers();
         // println!("loop headers: {:?}", loop_headers);
         let mut visited: HashMap<String, u32> = HashMap::new(); // <bb_label, count>
@@ -607,8 +737,10 @@ impl AbstractExecution for IntervalAnalyzer {
             visited.insert(bb_label.clone(), 0);
         }
         self.executed = true;
+        let mut queued: HashSet<String> = self.worklist.iter().map(|b| b.id.clone()).collect();
         while !self.worklist.is_empty() {
             let block = self.worklist.pop_front().unwrap();
+            queued.remove(&block.id);
             self.exe_block(&block);
             visited.insert(block.id.clone(), visited.get(&block.id).unwrap() + 1);
 
@@ -678,7 +810,7 @@ impl AbstractExecution for IntervalAnalyzer {
                         self.bb2store.insert(succ_label.clone(), store_joined);
                     }
 
-                    if !self.worklist.contains(&succ) {
+                    if queued.insert(succ_label.clone()) {
                         // avoiding multiple consecutive executions on the same block
                         self.worklist.push_back(succ.clone());
                     }
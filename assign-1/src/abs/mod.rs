@@ -1,3 +1,5 @@
 pub mod domain;
+pub mod execution;
+pub mod oracle;
+pub mod sccp;
 pub mod semantics;
-pub mod execution;
\ No newline at end of file
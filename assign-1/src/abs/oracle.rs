@@ -0,0 +1,137 @@
+/*
+Soundness oracle: checks the result of an abstract execution against concrete executions.
+
+Every value a variable takes at the entry of a basic block (as recorded by the interpreter's trace)
+must be abstracted by the value of that variable in the store at the entry of the block. Note that
+bb2store holds the stores at the exit of the blocks, so the entry stores are rebuilt by joining the
+exit stores of the predecessors that the analysis found reachable, as in the MFP algorithm.
+*/
+use super::execution::{AbstractExecution, Analyzer};
+use super::semantics::AbstractSemantics;
+use crate::lir;
+use crate::lir::interp::{TraceEntry, Value};
+use crate::store::Store;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// a concrete value at the entry of `block` that is not abstracted by the computed store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsound<T> {
+    pub block: String,
+    pub var: lir::Variable,
+    pub value: i32,
+    pub abs: Option<T>, // None if the store of the block has no value for the variable
+    pub unreachable: bool, // whether the analysis found the block unreachable
+}
+
+impl<T: fmt::Display> fmt::Display for Unsound<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.abs {
+            Some(abs) => write!(
+                f,
+                "block `{}`: `{}` is {}, which is not in {}",
+                self.block, self.var.name, self.value, abs
+            ),
+            None if self.unreachable => write!(
+                f,
+                "block `{}`: `{}` is {}, but the block is unreachable in the analysis",
+                self.block, self.var.name, self.value
+            ),
+            None => write!(
+                f,
+                "block `{}`: `{}` is {}, but the analysis has no value for it",
+                self.block, self.var.name, self.value
+            ),
+        }
+    }
+}
+
+// the store at the entry of each basic block, given the store at the entry of the function
+pub fn entry_stores<T>(analyzer: &Analyzer<T>, init: &Store<T>) -> HashMap<String, Store<T>>
+where
+    T: Clone + AbstractSemantics,
+{
+    let mut stores = HashMap::new();
+    for label in analyzer.cfg.get_all_block_labels() {
+        if label == "entry" {
+            stores.insert(label, init.clone());
+            continue;
+        }
+        let block = analyzer.cfg.get_block(&label).unwrap();
+        let mut store = Store::new();
+        for pred in analyzer.cfg.get_predecessors(block) {
+            let reachable = analyzer
+                .reachable_successors
+                .get(&pred.id)
+                .is_some_and(|succs| succs.contains(&label));
+            if reachable {
+                store = store.join(analyzer.bb2store.get(&pred.id).unwrap());
+            }
+        }
+        stores.insert(label, store);
+    }
+    stores
+}
+
+/*
+Run the (not yet executed) analyzer of `func_name` and check it against the trace of a concrete
+execution. Only the trace entries of the analyzed function are considered, so the trace may come
+from any caller. At most one counterexample is reported per block/variable pair, ordered by block
+and then by variable.
+*/
+pub fn check<T>(mut analyzer: Analyzer<T>, func_name: &str, trace: &[TraceEntry]) -> Vec<Unsound<T>>
+where
+    T: Clone + AbstractSemantics,
+    Analyzer<T>: AbstractExecution,
+{
+    assert!(!analyzer.executed, "the analyzer has already been executed");
+    let init = analyzer.bb2store.get("entry").unwrap().clone();
+    analyzer.mfp();
+    let stores = entry_stores(&analyzer, &init);
+    let func = &analyzer.prog.functions[func_name];
+
+    let mut reported = HashSet::new();
+    let mut unsound = Vec::new();
+    for entry in trace {
+        // only the first point of each visit of a block of the analyzed function
+        // a frame that has branched on a value computed from an undefined local is beyond the
+        // analyses, which take such values as bottom (and the trace leaves them out)
+        let point = &entry.point;
+        if point.func != func_name || entry.diverged {
+            continue;
+        }
+        let at_entry = match point.index {
+            Some(idx) => idx == 0,
+            None => func.body[&point.block].insts.is_empty(),
+        };
+        if !at_entry {
+            continue;
+        }
+
+        let store = &stores[&point.block];
+        let unreachable = store.len() == 0;
+        for (var, value) in &entry.values {
+            let value = match (&var.typ, value) {
+                (lir::Type::Int, Value::Int(n)) => *n,
+                _ => continue,
+            };
+            if reported.contains(&(&point.block, var)) {
+                continue;
+            }
+            let abs = match store.get(var) {
+                Some(abs) if abs.contains(value) => continue,
+                abs => abs.cloned(),
+            };
+            reported.insert((&point.block, var));
+            unsound.push(Unsound {
+                block: point.block.clone(),
+                var: var.clone(),
+                value,
+                abs,
+                unreachable,
+            });
+        }
+    }
+    unsound.sort_by(|a, b| (&a.block, &a.var.name).cmp(&(&b.block, &b.var.name)));
+    unsound
+}
@@ -0,0 +1,524 @@
+/*
+Sparse conditional constant propagation (Wegman and Zadeck) over the SSA form of a function.
+
+Values are kept per SSA version rather than per block, and a block is only evaluated once an edge
+into it is known to be executable. The transfer functions are those of `ConstantAnalyzer`: an int
+parameter starts as Top and a local read before any definition as Bottom.
+
+The int variables without versions (globals and address-taken variables, see `ssa`, and those never
+defined) are memory, which stores and calls may write as well. Their values are kept in a store at
+the start and the end of each block as in `ConstantAnalyzer`, and an instruction reading or writing
+them is evaluated again with the rest of its block.
+*/
+use super::domain;
+use super::semantics::AbstractSemantics;
+use crate::lir;
+use crate::ssa::{self, SsaFunction};
+use crate::store;
+use crate::transform::successors;
+use crate::utils;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Site {
+    Phi(usize),  // the phi at this index at the start of the block
+    Inst(usize), // the instruction at this index
+    Term,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sccp {
+    pub ssa: SsaFunction,
+    pub values: HashMap<lir::Variable, domain::Constant>, // of each int version defined
+    pub executable_blocks: HashSet<String>,
+    pub executable_edges: HashSet<(String, String)>,
+    renamed: HashSet<lir::Variable>, // the variables with versions, read as version 0 if undefined
+    uses: HashMap<lir::Variable, Vec<(String, Site)>>,
+    global_ints: Vec<lir::Variable>,
+    addrof_ints: Vec<lir::Variable>,
+    mem_in: HashMap<String, store::ConstantStore>, // joined over the executable edges
+    mem_out: HashMap<String, store::ConstantStore>,
+}
+
+impl Sccp {
+    pub fn new(prog: &lir::Program, func_name: &str) -> Self {
+        let ssa = SsaFunction::from_function(prog, func_name);
+        let mut uses: HashMap<lir::Variable, Vec<(String, Site)>> = HashMap::new();
+        for (label, block) in &ssa.function.body {
+            for (i, phi) in ssa.phis.get(label).into_iter().flatten().enumerate() {
+                for (_, arg) in &phi.args {
+                    if let lir::Operand::Var(var) = arg {
+                        uses.entry(var.clone())
+                            .or_default()
+                            .push((label.clone(), Site::Phi(i)));
+                    }
+                }
+            }
+            for (i, inst) in block.insts.iter().enumerate() {
+                for var in ssa::inst_uses(inst) {
+                    uses.entry(var.clone())
+                        .or_default()
+                        .push((label.clone(), Site::Inst(i)));
+                }
+            }
+            for var in ssa::term_uses(&block.term) {
+                uses.entry(var.clone())
+                    .or_default()
+                    .push((label.clone(), Site::Term));
+            }
+        }
+        let renamed: HashSet<lir::Variable> = ssa.origins.values().cloned().collect();
+
+        // the memory at entry: globals and parameters are Top, locals undefined
+        let global_ints = prog.get_int_globals();
+        let mut entry_mem = store::ConstantStore::new();
+        for local in prog.get_int_locals(func_name) {
+            if !renamed.contains(&local) {
+                entry_mem.set(local, domain::Constant::Bottom);
+            }
+        }
+        for var in global_ints
+            .iter()
+            .chain(&prog.get_int_parameters(func_name))
+        {
+            if !renamed.contains(var) {
+                entry_mem.set(var.clone(), domain::Constant::Top);
+            }
+        }
+
+        let mut sccp = Self {
+            ssa,
+            values: HashMap::new(),
+            executable_blocks: HashSet::new(),
+            executable_edges: HashSet::new(),
+            renamed,
+            uses,
+            global_ints,
+            addrof_ints: prog.get_addrof_ints(func_name),
+            mem_in: HashMap::new(),
+            mem_out: HashMap::new(),
+        };
+        if sccp.ssa.function.body.contains_key("entry") {
+            sccp.mem_in.insert("entry".to_string(), entry_mem);
+            sccp.run();
+        }
+        sccp
+    }
+
+    fn run(&mut self) {
+        // the flow worklist holds the blocks reached by a new executable edge, or whose memory at
+        // the start has changed (true), and the SSA worklist the sites using a version whose value
+        // has changed
+        let mut flow_worklist: VecDeque<(String, bool)> = VecDeque::new();
+        let mut ssa_worklist: VecDeque<(String, Site)> = VecDeque::new();
+        flow_worklist.push_back(("entry".to_string(), true));
+        while !flow_worklist.is_empty() || !ssa_worklist.is_empty() {
+            while let Some((label, mem_changed)) = flow_worklist.pop_front() {
+                let phi_count = self.ssa.phis.get(&label).map_or(0, Vec::len);
+                for i in 0..phi_count {
+                    self.visit_phi(&label, i, &mut ssa_worklist);
+                }
+                if self.executable_blocks.insert(label.clone()) || mem_changed {
+                    self.visit_block(&label, &mut flow_worklist, &mut ssa_worklist);
+                }
+            }
+            let Some((label, site)) = ssa_worklist.pop_front() else {
+                continue;
+            };
+            if !self.executable_blocks.contains(&label) {
+                continue;
+            }
+            match site {
+                Site::Phi(i) => self.visit_phi(&label, i, &mut ssa_worklist),
+                Site::Inst(i) => {
+                    let inst = &self.ssa.function.body[&label].insts[i];
+                    if self.touches_memory(inst) {
+                        self.visit_block(&label, &mut flow_worklist, &mut ssa_worklist);
+                    } else {
+                        let mut mem = store::ConstantStore::new();
+                        self.visit_inst(&label, i, &mut mem, &mut ssa_worklist);
+                    }
+                }
+                Site::Term => {
+                    // the terminal reads the memory at the end of the block, which it does not
+                    // change again
+                    let mem = self.mem_out[&label].clone();
+                    self.visit_term(&label, mem, &mut flow_worklist, &mut ssa_worklist);
+                }
+            }
+        }
+    }
+
+    fn visit_block(
+        &mut self,
+        label: &str,
+        flow_worklist: &mut VecDeque<(String, bool)>,
+        ssa_worklist: &mut VecDeque<(String, Site)>,
+    ) {
+        let mut mem = self.mem_in[label].clone();
+        for i in 0..self.ssa.function.body[label].insts.len() {
+            self.visit_inst(label, i, &mut mem, ssa_worklist);
+        }
+        self.visit_term(label, mem, flow_worklist, ssa_worklist);
+    }
+
+    fn visit_phi(&mut self, label: &str, i: usize, ssa_worklist: &mut VecDeque<(String, Site)>) {
+        let phi = &self.ssa.phis[label][i];
+        let mut val = domain::Constant::Bottom;
+        for (pred, arg) in &phi.args {
+            if self
+                .executable_edges
+                .contains(&(pred.clone(), label.to_string()))
+            {
+                val = val.join(&self.value_of(arg, &self.mem_out[pred]));
+            }
+        }
+        let lhs = phi.lhs.clone();
+        let mut mem = store::ConstantStore::new();
+        self.assign(&lhs, val, &mut mem, ssa_worklist);
+    }
+
+    fn visit_inst(
+        &mut self,
+        label: &str,
+        i: usize,
+        mem: &mut store::ConstantStore,
+        ssa_worklist: &mut VecDeque<(String, Site)>,
+    ) {
+        let inst = self.ssa.function.body[label].insts[i].clone();
+        if let Some(lhs) = ssa::inst_def(&inst) {
+            let val = self.exe_instr(&inst, mem);
+            self.assign(lhs, val, mem, ssa_worklist);
+        }
+        match inst {
+            lir::Instruction::Store { dst: _, op } => {
+                // the stored int may be in any int whose address is taken
+                let is_int = match op {
+                    lir::Operand::CInt(_) => true,
+                    lir::Operand::Var(ref var) => var.typ == lir::Type::Int,
+                };
+                if is_int {
+                    let val = self.value_of(&op, mem);
+                    for var in &self.addrof_ints {
+                        let joined = match mem.get(var) {
+                            Some(old) => old.join(&val),
+                            None => val.clone(),
+                        };
+                        mem.set(var.clone(), joined);
+                    }
+                }
+            }
+            lir::Instruction::CallExt { args, .. } => self.exe_call(&args, mem),
+            _ => {}
+        }
+    }
+
+    fn visit_term(
+        &mut self,
+        label: &str,
+        mut mem: store::ConstantStore,
+        flow_worklist: &mut VecDeque<(String, bool)>,
+        ssa_worklist: &mut VecDeque<(String, Site)>,
+    ) {
+        let term = self.ssa.function.body[label].term.clone();
+        let succs: Vec<&String> = match term {
+            lir::Terminal::Branch {
+                ref cond,
+                ref tt,
+                ref ff,
+            } => match self.value_of(cond, &mem) {
+                domain::Constant::Bottom => vec![],
+                domain::Constant::Top => vec![tt, ff],
+                domain::Constant::CInt(0) => vec![ff],
+                domain::Constant::CInt(_) => vec![tt],
+            },
+            _ => successors(&term),
+        };
+        if let lir::Terminal::CallDirect { ref args, .. }
+        | lir::Terminal::CallIndirect { ref args, .. } = term
+        {
+            self.exe_call(args, &mut mem);
+        }
+        if let Some(lhs) = ssa::term_def(&term) {
+            self.assign(lhs, domain::Constant::Top, &mut mem, ssa_worklist);
+        }
+
+        self.mem_out.insert(label.to_string(), mem);
+        for succ in succs {
+            // a new edge is followed, and new memory is joined at the start of the successor
+            let is_new = self
+                .executable_edges
+                .insert((label.to_string(), succ.clone()));
+            let mem_out = &self.mem_out[label];
+            let mem_in = match self.mem_in.get(succ) {
+                Some(mem_in) => mem_in.join(mem_out),
+                None => mem_out.clone(),
+            };
+            let mem_changed = self.mem_in.get(succ) != Some(&mem_in);
+            if mem_changed {
+                self.mem_in.insert(succ.clone(), mem_in);
+            }
+            if is_new || mem_changed {
+                flow_worklist.push_back((succ.clone(), mem_changed));
+            }
+        }
+    }
+
+    fn exe_instr(&self, instr: &lir::Instruction, mem: &store::ConstantStore) -> domain::Constant {
+        // the value of the int an instruction defines
+        match instr {
+            lir::Instruction::Copy { op, .. } => self.value_of(op, mem),
+            lir::Instruction::Arith { aop, op1, op2, .. } => {
+                self.value_of(op1, mem).arith(&self.value_of(op2, mem), aop)
+            }
+            lir::Instruction::Cmp { rop, op1, op2, .. } => {
+                self.value_of(op1, mem).cmp(&self.value_of(op2, mem), rop)
+            }
+            _ => domain::Constant::Top,
+        }
+    }
+
+    fn exe_call(&self, args: &[lir::Operand], mem: &mut store::ConstantStore) {
+        // the callee may change every global, and every int whose address is taken if it is
+        // passed a pointer to ints
+        for var in &self.global_ints {
+            mem.set(var.clone(), domain::Constant::Top);
+        }
+        let passes_int_ptr = args.iter().any(|arg| match arg {
+            lir::Operand::Var(lir::Variable {
+                typ: lir::Type::Pointer(to),
+                ..
+            }) => utils::able_to_reach_int(to),
+            _ => false,
+        });
+        if passes_int_ptr {
+            for var in &self.addrof_ints {
+                mem.set(var.clone(), domain::Constant::Top);
+            }
+        }
+    }
+
+    fn assign(
+        &mut self,
+        lhs: &lir::Variable,
+        val: domain::Constant,
+        mem: &mut store::ConstantStore,
+        ssa_worklist: &mut VecDeque<(String, Site)>,
+    ) {
+        if lhs.typ != lir::Type::Int {
+            return;
+        }
+        if self.is_memory(lhs) {
+            mem.set(lhs.clone(), val);
+            return;
+        }
+        // values only go up, so the sites using lhs are revisited at most twice
+        if self.ssa.origins.contains_key(lhs) && self.value(lhs) != val {
+            if let Some(uses) = self.uses.get(lhs) {
+                ssa_worklist.extend(uses.iter().cloned());
+            }
+            self.values.insert(lhs.clone(), val);
+        }
+    }
+
+    fn is_memory(&self, var: &lir::Variable) -> bool {
+        var.typ == lir::Type::Int
+            && !self.ssa.origins.contains_key(var)
+            && !self.renamed.contains(var)
+    }
+
+    fn touches_memory(&self, instr: &lir::Instruction) -> bool {
+        matches!(
+            instr,
+            lir::Instruction::Store { .. } | lir::Instruction::CallExt { .. }
+        ) || ssa::inst_def(instr).is_some_and(|var| self.is_memory(var))
+            || ssa::inst_uses(instr)
+                .into_iter()
+                .any(|var| self.is_memory(var))
+    }
+
+    fn value_of(&self, op: &lir::Operand, mem: &store::ConstantStore) -> domain::Constant {
+        match op {
+            lir::Operand::CInt(c) => domain::Constant::CInt(*c),
+            lir::Operand::Var(var) if self.is_memory(var) => {
+                mem.get(var).cloned().unwrap_or(domain::Constant::Top)
+            }
+            lir::Operand::Var(var) => self.value(var),
+        }
+    }
+
+    pub fn value(&self, var: &lir::Variable) -> domain::Constant {
+        // the value of a version, Bottom if it is not evaluated yet; a renamed local is read as
+        // version 0 where no definition reaches, i.e., while it is undefined
+        if var.typ != lir::Type::Int {
+            return domain::Constant::Top;
+        }
+        if let Some(val) = self.values.get(var) {
+            return val.clone();
+        }
+        let is_undefined = self.renamed.contains(var) && !self.ssa.function.params.contains(var);
+        if self.ssa.origins.contains_key(var) || is_undefined {
+            domain::Constant::Bottom
+        } else {
+            domain::Constant::Top
+        }
+    }
+
+    pub fn unreachable_blocks(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self
+            .ssa
+            .function
+            .body
+            .keys()
+            .filter(|label| !self.executable_blocks.contains(*label))
+            .cloned()
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    pub fn unreachable_edges(&self) -> Vec<(String, String)> {
+        // the edges of the CFG never taken, including those between unreachable blocks
+        let mut edges: Vec<(String, String)> = Vec::new();
+        for (label, block) in &self.ssa.function.body {
+            for succ in successors(&block.term) {
+                let edge = (label.clone(), succ.clone());
+                if !self.executable_edges.contains(&edge) && !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges.sort();
+        edges
+    }
+
+    pub fn bb2store(&self) -> HashMap<String, store::ConstantStore> {
+        // for each executable block, the memory at its end and the last int version of each
+        // variable it defines
+        let mut bb2store = HashMap::new();
+        for label in &self.executable_blocks {
+            let block = &self.ssa.function.body[label];
+            let mut store = self.mem_out[label].clone();
+            let defs = self
+                .ssa
+                .phis
+                .get(label)
+                .into_iter()
+                .flatten()
+                .map(|phi| &phi.lhs)
+                .chain(block.insts.iter().filter_map(ssa::inst_def))
+                .chain(ssa::term_def(&block.term));
+            let mut last_defs = HashMap::new();
+            for var in defs {
+                if var.typ == lir::Type::Int && self.ssa.origins.contains_key(var) {
+                    last_defs.insert(self.ssa.origin(var), var);
+                }
+            }
+            for var in last_defs.into_values() {
+                store.set(var.clone(), self.value(var));
+            }
+            bb2store.insert(label.clone(), store);
+        }
+        bb2store
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::abs::execution::{AbstractExecution, ConstantAnalyzer};
+
+    #[test]
+    fn test_conditional_constants() {
+        // x is 1 on the only path taken, though another definition of it is in the loop
+        let src = concat!(
+            "fn main() -> int {\nentry:\n  $ret 0\n}\n\n",
+            "fn test(n:int) -> int {\n",
+            "let c:int, i:int, x:int\n",
+            "entry:\n  x = $copy 1\n  i = $copy 0\n  $jump loop\n\n",
+            "loop:\n  c = $cmp neq x 1\n  $branch c dead body\n\n",
+            "dead:\n  x = $copy 2\n  $jump loop\n\n",
+            "body:\n  i = $arith add i x\n  c = $cmp lt i n\n  $branch c loop exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let sccp = Sccp::new(&prog, "test");
+        assert_eq!(sccp.unreachable_blocks(), vec!["dead"]);
+        assert_eq!(
+            sccp.unreachable_edges(),
+            vec![
+                ("dead".to_string(), "loop".to_string()),
+                ("loop".to_string(), "dead".to_string())
+            ]
+        );
+        let value_of = |name: &str| {
+            let var = sccp.ssa.function.locals.iter().find(|v| v.name == name);
+            sccp.value(var.unwrap())
+        };
+        // x.2 is the phi of x in loop, and x.3 the definition in dead
+        assert_eq!(value_of("x.2"), domain::Constant::CInt(1));
+        assert_eq!(value_of("c.1"), domain::Constant::CInt(0));
+        assert_eq!(value_of("i.2"), domain::Constant::Top);
+        assert_eq!(value_of("x.3"), domain::Constant::Bottom);
+
+        // the dense analysis agrees on what reaches exit
+        let mut analyzer = ConstantAnalyzer::new(prog, "test");
+        analyzer.mfp();
+        let x = sccp.ssa.origin(
+            sccp.ssa
+                .function
+                .locals
+                .iter()
+                .find(|v| v.name == "x.2")
+                .unwrap(),
+        );
+        assert_eq!(
+            analyzer.bb2store["exit"].get(x),
+            Some(&domain::Constant::CInt(1))
+        );
+    }
+
+    #[test]
+    fn test_as_precise_as_constant_analyzer() {
+        // at the end of each block, every int is at least as precise as with ConstantAnalyzer,
+        // and every block and edge taken is taken by ConstantAnalyzer as well
+        for entry in std::fs::read_dir("./demos/json").unwrap() {
+            let path = entry.unwrap().path();
+            let prog = lir::Program::parse_json(path.to_str().unwrap()).unwrap();
+            for func_name in prog.functions.keys() {
+                let sccp = Sccp::new(&prog, func_name);
+                let mut analyzer = ConstantAnalyzer::new(prog.clone(), func_name);
+                analyzer.mfp();
+                for (src, dst) in &sccp.executable_edges {
+                    assert!(
+                        analyzer.reachable_successors[src].contains(dst),
+                        "{:?} {}: {} -> {}",
+                        path,
+                        func_name,
+                        src,
+                        dst
+                    );
+                }
+                for (label, store) in sccp.bb2store() {
+                    for var in store.get_variables() {
+                        let val = store.get(&var).unwrap();
+                        let Some(expected) = analyzer.bb2store[&label].get(sccp.ssa.origin(&var))
+                        else {
+                            continue;
+                        };
+                        assert!(
+                            &val.join(expected) == expected,
+                            "{:?} {}: {} at {} is {}, not {}",
+                            path,
+                            func_name,
+                            var.name,
+                            label,
+                            val,
+                            expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -3,7 +3,9 @@ use crate::lir;
 pub trait AbstractSemantics {
     fn is_bottom(&self) -> bool;
     fn is_top(&self) -> bool;
+    fn contains(&self, val: i32) -> bool; // whether the concrete value is abstracted by self
     fn join(&self, other: &Self) -> Self;
+    fn widen(&self, other: &Self) -> Self; // an upper bound of both that makes ascending chains finite
     fn arith(&self, other: &Self, op: &lir::ArithOp) -> Self;
     fn cmp(&self, other: &Self, op: &lir::RelaOp) -> Self;
 }
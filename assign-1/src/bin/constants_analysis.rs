@@ -4,10 +4,14 @@ use cs260::lir;
 use cs260::utils;
 
 fn main() {
-    // accept command line arguments (./constants_analysis <json_file> <func_name>)
+    // accept command line arguments (./constants_analysis <json_file> <func_name> [--wto|--sccp])
+    // with `--wto`, the fixpoint is computed by the recursive strategy over the WTO of the CFG
+    // with `--sccp`, the constants of the SSA versions are computed by SCCP instead
     let args: Vec<String> = std::env::args().collect();
-    if args.len() != 3 {
-        println!("Usage: cargo run --bin constants_analysis <json_file> <func_name>");
+    if args.len() != 3 && !(args.len() == 4 && (args[3] == "--wto" || args[3] == "--sccp")) {
+        println!(
+            "Usage: cargo run --bin constants_analysis <json_file> <func_name> [--wto|--sccp]"
+        );
         std::process::exit(1);
     }
     let json_fname = &args[1];
@@ -19,14 +23,41 @@ fn main() {
         println!("func_name: {}", func_name);
     }
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if args.len() == 4 && args[3] == "--sccp" {
+        // the SSA function, then the values of the versions defined in each reachable block
+        let sccp = abs::sccp::Sccp::new(&prog, func_name);
+        #[cfg(debug_assertions)]
+        {
+            println!("{}", sccp.ssa);
+        }
+        utils::display_bb2store(&sccp.bb2store());
+        let edges: Vec<String> = sccp
+            .unreachable_edges()
+            .iter()
+            .map(|(src, dst)| format!("{} -> {}", src, dst))
+            .collect();
+        println!(
+            "unreachable blocks: {}",
+            sccp.unreachable_blocks().join(", ")
+        );
+        println!("unreachable edges: {}", edges.join(", "));
+        return;
+    }
     let mut analyzer = abs::execution::ConstantAnalyzer::new(prog, &func_name);
+    if args.len() == 4 {
+        analyzer.strategy = abs::execution::Strategy::Recursive;
+    }
+    analyzer.mfp();
     #[cfg(debug_assertions)]
     {
+        // the CFG with the store after each block, see visualize_cfg.sh
         let _ = analyzer
             .cfg
-            .to_dot_file(format!("{}.dot", func_name).as_str());
+            .to_detailed_dot_file_with(format!("{}.dot", func_name).as_str(), &analyzer.bb2store);
     }
-    analyzer.mfp();
     utils::display_bb2store(&analyzer.bb2store);
 }
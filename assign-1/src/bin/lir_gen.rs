@@ -0,0 +1,100 @@
+/*
+Generate random LIR programs, or fuzz the analyses with them.
+
+`lir_gen <seed> [--json]` prints the program generated from the seed, and
+`lir_gen --fuzz <seed> <count>` checks the programs of `count` consecutive seeds: every function is
+analyzed with both analyses, which must neither panic nor disagree with a concrete run of `main`.
+*/
+use cs260::abs::execution::{ConstantAnalyzer, IntervalAnalyzer};
+use cs260::abs::oracle;
+use cs260::lir;
+use cs260::lir::gen;
+use cs260::lir::interp::{Interpreter, TraceEntry};
+use std::panic;
+
+fn usage() -> ! {
+    println!("Usage: cargo run --bin lir_gen <seed> [--json]");
+    println!("       cargo run --bin lir_gen --fuzz <seed> <count>");
+    std::process::exit(1);
+}
+
+fn parse_num(arg: &str) -> u64 {
+    arg.parse().unwrap_or_else(|_| usage())
+}
+
+fn panic_msg(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// the problems found by both analyses of `func_name`
+fn check(prog: &lir::Program, func_name: &str, trace: &[TraceEntry]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let constants = panic::catch_unwind(|| {
+        let analyzer = ConstantAnalyzer::new(prog.clone(), func_name);
+        oracle::check(analyzer, func_name, trace)
+    });
+    match constants {
+        Ok(unsound) => problems.extend(unsound.iter().map(|u| format!("(constant) {}", u))),
+        Err(payload) => problems.push(format!("(constant) panicked: {}", panic_msg(payload))),
+    }
+    let intervals = panic::catch_unwind(|| {
+        let analyzer = IntervalAnalyzer::new(prog.clone(), func_name);
+        oracle::check(analyzer, func_name, trace)
+    });
+    match intervals {
+        Ok(unsound) => problems.extend(unsound.iter().map(|u| format!("(interval) {}", u))),
+        Err(payload) => problems.push(format!("(interval) panicked: {}", panic_msg(payload))),
+    }
+    problems
+}
+
+fn fuzz(first_seed: u64, count: u64) -> bool {
+    // panics are reported along with the seed instead
+    panic::set_hook(Box::new(|_| {}));
+    let mut ok = true;
+    for seed in first_seed..first_seed + count {
+        let prog = gen::generate(&gen::Config::new(seed));
+        let mut interp = Interpreter::new(&prog);
+        interp.trace = true;
+        // a trap only happens on a division by zero, the functions are still analyzed
+        let trace = match interp.run() {
+            Ok(exe) => exe.trace,
+            Err(_) => Vec::new(),
+        };
+        let mut func_names: Vec<&String> = prog.functions.keys().collect();
+        func_names.sort();
+        for func_name in func_names {
+            for problem in check(&prog, func_name, &trace) {
+                println!("seed {}, function `{}` {}", seed, func_name, problem);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.len() {
+        4 if args[1] == "--fuzz" => {
+            if !fuzz(parse_num(&args[2]), parse_num(&args[3])) {
+                std::process::exit(1);
+            }
+        }
+        2 | 3 => {
+            let prog = gen::generate(&gen::Config::new(parse_num(&args[1])));
+            match args.get(2).map(|s| s.as_str()) {
+                None => print!("{}", prog),
+                Some("--json") => println!("{}", serde_json::to_string(&prog).unwrap()),
+                Some(_) => usage(),
+            }
+        }
+        _ => usage(),
+    }
+}
@@ -1,5 +1,6 @@
 /*
-Get the loop headers of CFG generated by the given function name and program.
+Get the loop headers of CFG generated by the given function name and program, along with its
+natural loops and irreducible regions.
 */
 use cs260::cfg;
 use cs260::lir;
@@ -19,8 +20,21 @@ fn main() {
         println!("func_name: {}", func_name);
     }
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     let cfg = cfg::ControlFlowGraph::from_function(&prog, func_name);
     let loop_headers = cfg.get_loop_headers();
     println!("Loop headers: {:?}", loop_headers);
+    let forest = cfg.loop_forest();
+    for l in &forest.loops {
+        println!(
+            "Loop {} (depth {}): latches {:?}, body {:?}, exits {:?}, preheader {:?}",
+            l.header, l.depth, l.latches, l.body, l.exits, l.preheader
+        );
+    }
+    for region in &forest.irreducible {
+        println!("Irreducible region: {:?}", region);
+    }
 }
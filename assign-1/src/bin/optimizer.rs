@@ -0,0 +1,92 @@
+use cs260::abs;
+use cs260::abs::domain;
+use cs260::abs::execution::AbstractExecution;
+use cs260::lir;
+use cs260::transform;
+
+fn main() {
+    // accept command line arguments (./optimizer <json_file> <func_name> [--intervals] [--inline])
+    // the function is optimized with the results of the constant analysis, or of the interval
+    // analysis with `--intervals`, then the program is printed with a summary in comments
+    // with `--inline`, the direct calls of the function are inlined first (see InlineOptions)
+    let args: Vec<String> = std::env::args().collect();
+    let flags = &args[args.len().min(3)..];
+    if args.len() < 3
+        || flags
+            .iter()
+            .any(|flag| flag != "--intervals" && flag != "--inline")
+    {
+        println!(
+            "Usage: cargo run --bin optimizer <json_file> <func_name> [--intervals] [--inline]"
+        );
+        std::process::exit(1);
+    }
+    let intervals = flags.iter().any(|flag| flag == "--intervals");
+    let inline = flags.iter().any(|flag| flag == "--inline");
+    let json_fname = &args[1];
+    let func_name = &args[2];
+
+    let mut prog = lir::Program::parse_json(json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if !prog.functions.contains_key(func_name) {
+        eprintln!("no function `{}`", func_name);
+        std::process::exit(1);
+    }
+    let inlined = if inline {
+        let (function, inlined) =
+            transform::inline_calls(&prog, func_name, &transform::InlineOptions::default());
+        prog.functions.insert(func_name.to_string(), function);
+        Some(inlined)
+    } else {
+        None
+    };
+    let function = &prog.functions[func_name];
+    let inst_count: usize = function.body.values().map(|block| block.insts.len()).sum();
+    let block_count = function.body.len();
+
+    let (optimized, replaced, decided, kept) = if intervals {
+        let mut analyzer = abs::execution::IntervalAnalyzer::new(prog.clone(), func_name);
+        analyzer.mfp();
+        transform::optimize(&analyzer, func_name, |val| match val {
+            domain::Interval::Range(domain::Number::Integer(l), domain::Number::Integer(u))
+                if l == u =>
+            {
+                Some(*l)
+            }
+            _ => None,
+        })
+    } else {
+        let mut analyzer = abs::execution::ConstantAnalyzer::new(prog.clone(), func_name);
+        analyzer.mfp();
+        transform::optimize(&analyzer, func_name, |val| match val {
+            domain::Constant::CInt(c) => Some(*c),
+            _ => None,
+        })
+    };
+
+    let new_inst_count: usize = optimized.body.values().map(|block| block.insts.len()).sum();
+    let new_block_count = optimized.body.len();
+    prog.functions.insert(func_name.to_string(), optimized);
+    print!("{}", prog);
+    if let Some(inlined) = inlined {
+        println!("// inlined calls: {}", inlined);
+    }
+    println!("// replaced uses: {}", replaced);
+    println!("// decided branches: {}", decided);
+    if kept > 0 {
+        // simplify_cfg does not fold a branch whose other target is the only way to the `$ret`
+        println!("// branches kept on a literal: {}", kept);
+    }
+    println!(
+        "// removed instructions: {} of {}",
+        inst_count - new_inst_count,
+        inst_count
+    );
+    println!(
+        "// removed blocks: {} of {}",
+        block_count - new_block_count,
+        block_count
+    );
+}
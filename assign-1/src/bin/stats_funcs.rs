@@ -13,7 +13,10 @@ fn main() {
         let path = path.unwrap().path();
         let path_str = path.to_str().unwrap();
         assert!(path_str.ends_with(".json"));
-        let prog = lir::Program::parse_json(&path_str);
+        let prog = lir::Program::parse_json(&path_str).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
         let func_names: Vec<String> = prog.functions.keys().cloned().collect();
         let prog_name = path_str.replace(".json", "");
         let prog_name: Vec<&str> = prog_name.split("/").collect();
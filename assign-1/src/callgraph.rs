@@ -0,0 +1,380 @@
+use crate::icfg::{CalleeResolver, TypeBasedResolver};
+use crate::lir;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CallKind {
+    Direct,   // $call_dir
+    Indirect, // $call_idr, one call per resolved callee
+    External, // $call_ext
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CallSite {
+    pub caller: String,
+    pub block: String, // the block calling, whose terminal or one of whose instructions is the call
+    pub callee: String,
+    pub kind: CallKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    // functions defined by the program and the external functions they call; call sites are sorted
+    pub functions: BTreeSet<String>,
+    pub externs: BTreeSet<String>,
+    pub calls: Vec<CallSite>,
+    callees: BTreeMap<String, BTreeSet<String>>,
+    callers: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CallGraph {
+    pub fn new(prog: &lir::Program) -> Self {
+        Self::with_resolver(prog, &TypeBasedResolver)
+    }
+
+    pub fn with_resolver(prog: &lir::Program, resolver: &dyn CalleeResolver) -> Self {
+        let mut calls = Vec::new();
+        for (func_name, function) in &prog.functions {
+            for (label, block) in &function.body {
+                for inst in &block.insts {
+                    if let lir::Instruction::CallExt { ext_callee, .. } = inst {
+                        calls.push(CallSite {
+                            caller: func_name.clone(),
+                            block: label.clone(),
+                            callee: ext_callee.clone(),
+                            kind: CallKind::External,
+                        });
+                    }
+                }
+                match block.term {
+                    lir::Terminal::CallDirect { ref callee, .. } => calls.push(CallSite {
+                        caller: func_name.clone(),
+                        block: label.clone(),
+                        callee: callee.clone(),
+                        kind: CallKind::Direct,
+                    }),
+                    lir::Terminal::CallIndirect { ref callee, .. } => {
+                        for target in resolver.resolve(prog, func_name, callee) {
+                            calls.push(CallSite {
+                                caller: func_name.clone(),
+                                block: label.clone(),
+                                callee: target,
+                                kind: CallKind::Indirect,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        calls.sort();
+        calls.dedup();
+
+        let mut callees: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut callers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for call in &calls {
+            callees
+                .entry(call.caller.clone())
+                .or_default()
+                .insert(call.callee.clone());
+            callers
+                .entry(call.callee.clone())
+                .or_default()
+                .insert(call.caller.clone());
+        }
+        let externs = calls
+            .iter()
+            .filter(|call| call.kind == CallKind::External)
+            .map(|call| call.callee.clone())
+            .collect();
+        Self {
+            functions: prog.functions.keys().cloned().collect(),
+            externs,
+            calls,
+            callees,
+            callers,
+        }
+    }
+
+    pub fn callees(&self, func: &str) -> Vec<&str> {
+        self.callees
+            .get(func)
+            .into_iter()
+            .flatten()
+            .map(|callee| callee.as_str())
+            .collect()
+    }
+
+    pub fn callers(&self, func: &str) -> Vec<&str> {
+        self.callers
+            .get(func)
+            .into_iter()
+            .flatten()
+            .map(|caller| caller.as_str())
+            .collect()
+    }
+
+    pub fn call_sites(&self, func: &str) -> Vec<&CallSite> {
+        // the calls to a function
+        self.calls
+            .iter()
+            .filter(|call| call.callee == func)
+            .collect()
+    }
+
+    pub fn sccs(&self) -> Vec<Vec<String>> {
+        // strongly connected components of the defined functions (Tarjan), each sorted; a
+        // component comes after every component it calls into
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            sccs: Vec::new(),
+        };
+        for func in &self.functions {
+            if !tarjan.index.contains_key(func.as_str()) {
+                tarjan.visit(func);
+            }
+        }
+        tarjan.sccs
+    }
+
+    pub fn is_recursive(&self, func: &str) -> bool {
+        // calls itself, directly or through other functions
+        self.callees(func).contains(&func)
+            || self
+                .sccs()
+                .iter()
+                .any(|scc| scc.len() > 1 && scc.iter().any(|f| f == func))
+    }
+
+    pub fn recursive_functions(&self) -> BTreeSet<String> {
+        let mut recursive = BTreeSet::new();
+        for scc in self.sccs() {
+            if scc.len() > 1 || self.callees(&scc[0]).contains(&scc[0].as_str()) {
+                recursive.extend(scc);
+            }
+        }
+        recursive
+    }
+
+    pub fn reachable_from(&self, root: &str) -> BTreeSet<String> {
+        // the functions (including externs) that may be called when calling root, and root itself
+        let mut reachable = BTreeSet::from([root.to_string()]);
+        let mut worklist = vec![root];
+        while let Some(func) = worklist.pop() {
+            for callee in self.callees(func) {
+                if reachable.insert(callee.to_string()) {
+                    worklist.push(callee);
+                }
+            }
+        }
+        reachable
+    }
+
+    pub fn reachable_from_main(&self) -> BTreeSet<String> {
+        if self.functions.contains("main") {
+            self.reachable_from("main")
+        } else {
+            BTreeSet::new()
+        }
+    }
+
+    pub fn bottom_up_order(&self) -> Vec<String> {
+        // defined functions with callees before callers (except within a recursive SCC), the
+        // order to compute function summaries in
+        self.sccs().into_iter().flatten().collect()
+    }
+
+    pub fn to_dot_file(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(b"digraph G {\n")?;
+        for func in &self.functions {
+            file.write_all(format!("  \"{}\";\n", func).as_bytes())?;
+        }
+        for ext in &self.externs {
+            file.write_all(format!("  \"{}\" [shape=box];\n", ext).as_bytes())?;
+        }
+        for (caller, callees) in &self.callees {
+            for callee in callees {
+                // dashed if only resolved indirect calls link the two functions
+                let indirect_only = self.calls.iter().all(|call| {
+                    call.caller != *caller
+                        || call.callee != *callee
+                        || call.kind == CallKind::Indirect
+                });
+                let style = if indirect_only { " [style=dashed]" } else { "" };
+                file.write_all(
+                    format!("  \"{}\" -> \"{}\"{};\n", caller, callee, style).as_bytes(),
+                )?;
+            }
+        }
+        file.write_all(b"}")?;
+        Ok(())
+    }
+
+    pub fn to_graphml_file(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")?;
+        file.write_all(b"<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n")?;
+        file.write_all(
+            b"<key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        )?;
+        file.write_all(b"<graph id=\"G\" edgedefault=\"directed\">\n")?;
+        for func in self.functions.iter().chain(&self.externs) {
+            file.write_all(format!("  <node id=\"{}\"/>\n", func).as_bytes())?;
+        }
+        for call in &self.calls {
+            file.write_all(
+                format!(
+                    "  <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{:?}</data></edge>\n",
+                    call.caller, call.callee, call.kind
+                )
+                .as_bytes(),
+            )?;
+        }
+        file.write_all(b"</graph>\n")?;
+        file.write_all(b"</graphml>\n")?;
+        Ok(())
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    index: HashMap<&'a str, usize>,
+    lowlink: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: BTreeSet<&'a str>,
+    sccs: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, func: &'a str) {
+        let index = self.index.len();
+        self.index.insert(func, index);
+        self.lowlink.insert(func, index);
+        self.stack.push(func);
+        self.on_stack.insert(func);
+        for callee in self.graph.callees(func) {
+            // externs have no body to call back into the program
+            if !self.graph.functions.contains(callee) {
+                continue;
+            }
+            if !self.index.contains_key(callee) {
+                self.visit(callee);
+                let low = self.lowlink[func].min(self.lowlink[callee]);
+                self.lowlink.insert(func, low);
+            } else if self.on_stack.contains(callee) {
+                let low = self.lowlink[func].min(self.index[callee]);
+                self.lowlink.insert(func, low);
+            }
+        }
+        if self.lowlink[func] == self.index[func] {
+            let mut scc = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                scc.push(member.to_string());
+                if member == func {
+                    break;
+                }
+            }
+            scc.sort();
+            self.sccs.push(scc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::icfg::PointsToResolver;
+
+    const SRC: &str = concat!(
+        "f:&(int) -> int\n",
+        "g:&(int) -> int\n",
+        "even:&(int) -> int\n",
+        "odd:&(int) -> int\n\n",
+        "extern print:(int) -> int\n\n",
+        "fn f(p:int) -> int {\n",
+        "let r:int\n",
+        "entry:\n  r = $call_ext print(p)\n  $ret r\n}\n\n",
+        "fn g(p:int) -> int {\n",
+        "let r:int\n",
+        "entry:\n  r = $call_dir g(p) then exit\n\n",
+        "exit:\n  $ret r\n}\n\n",
+        "fn even(n:int) -> int {\n",
+        "let r:int\n",
+        "entry:\n  r = $call_dir odd(n) then exit\n\n",
+        "exit:\n  $ret r\n}\n\n",
+        "fn odd(n:int) -> int {\n",
+        "let r:int\n",
+        "entry:\n  r = $call_dir even(n) then exit\n\n",
+        "exit:\n  $ret r\n}\n\n",
+        "fn unused() -> int {\n",
+        "entry:\n  $ret 0\n}\n\n",
+        "fn main() -> int {\n",
+        "let x:int, y:int, fp:&(int) -> int\n",
+        "entry:\n  x = $call_dir even(1) then bb1\n\n",
+        "bb1:\n  fp = $copy f\n  y = $call_idr fp(x) then bb2\n\n",
+        "bb2:\n  $ret y\n}\n",
+    );
+
+    #[test]
+    fn test_call_graph() {
+        let prog = lir::Program::parse_lir_str(SRC).unwrap();
+        let graph = CallGraph::new(&prog);
+        assert_eq!(graph.externs, BTreeSet::from(["print".to_string()]));
+        // the indirect call may call any function from int to int
+        assert_eq!(graph.callees("main"), vec!["even", "f", "g", "odd"]);
+        assert_eq!(graph.callers("print"), vec!["f"]);
+        // from odd, and both directly and indirectly from main
+        assert_eq!(graph.call_sites("even").len(), 3);
+
+        assert!(graph.is_recursive("g"));
+        assert!(graph.is_recursive("odd"));
+        assert!(!graph.is_recursive("f"));
+        assert_eq!(
+            graph.recursive_functions(),
+            BTreeSet::from(["even", "g", "odd"].map(String::from))
+        );
+        assert!(!graph.reachable_from_main().contains("unused"));
+        assert!(graph.reachable_from_main().contains("print"));
+
+        let sccs = graph.sccs();
+        assert_eq!(sccs.len(), 5);
+        assert!(sccs.contains(&vec!["even".to_string(), "odd".to_string()]));
+        let order = graph.bottom_up_order();
+        assert_eq!(order.len(), 6);
+        let position = |func: &str| order.iter().position(|f| f == func).unwrap();
+        for call in &graph.calls {
+            if graph.functions.contains(&call.callee) && !graph.is_recursive(&call.callee) {
+                assert!(position(&call.callee) < position(&call.caller));
+            }
+        }
+    }
+
+    #[test]
+    fn test_points_to_resolution() {
+        let prog = lir::Program::parse_lir_str(SRC).unwrap();
+        let fp = prog.functions["main"]
+            .locals
+            .iter()
+            .find(|var| var.name == "fp")
+            .unwrap()
+            .clone();
+        let resolver = PointsToResolver {
+            points_to: HashMap::from([(fp, BTreeSet::from(["f".to_string()]))]),
+        };
+        let graph = CallGraph::with_resolver(&prog, &resolver);
+        assert_eq!(graph.callees("main"), vec!["even", "f"]);
+        assert_eq!(
+            graph.reachable_from_main(),
+            BTreeSet::from(["even", "f", "main", "odd", "print"].map(String::from))
+        );
+    }
+}
@@ -1,16 +1,49 @@
+use crate::dominance::{DominanceFrontiers, DominatorTree};
 use crate::lir;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     io::Write,
 };
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CfgOptions {
+    // add a `dummy_entry` block (made unique if taken) that jumps to the entry block
+    pub synthetic_entry: bool,
+    // add a `dummy_exit` block (made unique if taken) that every `$ret` block flows into
+    pub synthetic_exit: bool,
+    // put a fresh block on every edge from a block with several successors to a block with
+    // several predecessors
+    pub split_critical_edges: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Jump,                                  // $jump, and the edges into or out of synthetic blocks
+    True,                                  // $branch to tt
+    False,                                 // $branch to ff
+    CallReturn { callee: Option<String> }, // call to next_bb; no callee name for $call_idr
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub src: String,
+    pub dst: String,
+    pub kind: EdgeKind,
+}
+
 #[derive(Debug, Clone)]
 pub struct ControlFlowGraph {
     // A DAG representing the control flow of a program
     // HashMap<String, lir::Block> is in the same type of lir::Function.body
     // Suppose the node label is the same as the block id
     pub nodes: HashMap<String, lir::Block>,
-    pub edges: Vec<(String, String)>,
+    edges: Vec<Edge>,
+    // indices into `edges` of the edges leaving / entering each block
+    succs: HashMap<String, Vec<usize>>,
+    preds: HashMap<String, Vec<usize>>,
+    // labels of the synthetic blocks, if they have been added
+    dummy_entry: Option<String>,
+    dummy_exit: Option<String>,
 }
 
 impl ControlFlowGraph {
@@ -18,86 +51,48 @@ impl ControlFlowGraph {
         Self {
             nodes: HashMap::new(),
             edges: Vec::new(),
+            succs: HashMap::new(),
+            preds: HashMap::new(),
+            dummy_entry: None,
+            dummy_exit: None,
         }
     }
 
-    /*
-    pub fn from_program(prog: &lir::Program) -> Self {
-        let mut cfg = Self::new();
-
-        // add dummy entry and exit blocks
-        let dummy_entry = lir::Block::new("dummy_entry", &lir::Terminal::Jump("XXX".to_string()));
-        cfg.nodes.insert("dummy_entry".to_string(), dummy_entry);
-        let dummy_exit = lir::Block::new("dummy_exit", &lir::Terminal::Ret(None));
-        cfg.nodes.insert("dummy_exit".to_string(), dummy_exit);
-
-        // insert all blocks in prog into cfg
-
-        // for block in prog.get_blocks() {
-        //     cfg.nodes.insert(block.get_id(), block.clone());
-        // }
-
-        // for block in prog.get_blocks() {
-        //     let block_id = block.get_id();
-        //     let next_block_id = block.get_next_block_id();
-        //     cfg.edges.push((block_id, next_block_id));
-        // }
-
-        // construct dummy "exit" block and insert it to cfg
-
-        // TODO: add edges from all blocks to exit block
-
-        cfg
-    }
-    */
-
     pub fn from_function(prog: &lir::Program, func_name: &str) -> Self {
         let mut cfg = Self::new();
         let function = prog.functions.get(func_name).unwrap();
-        // function.body.get("entry").unwrap();
-
-        // add dummy entry and exit blocks
-        // let dummy_entry = lir::Block::new("dummy_entry", &lir::Terminal::Jump("entry".to_string()));
-        // cfg.nodes.insert("dummy_entry".to_string(), dummy_entry);
-        // let dummy_exit = lir::Block::new("dummy_exit", &lir::Terminal::Ret(None));
-        // cfg.nodes.insert("dummy_exit".to_string(), dummy_exit);
 
         // insert all blocks in function.body into cfg
-        // let blocks: Vec<&lir::Block> = function.body.values().collect();
         for (label, block) in &function.body {
             cfg.nodes.insert(label.clone(), block.clone());
         }
 
-        // // add edge: <dummy_entry, entry block>
-        // cfg.edges
-        //     .push(("dummy_entry".to_string(), "entry".to_string()));
-
         // construct relationships between blocks in function.body
         for (label, block) in &function.body {
             match block.term {
                 lir::Terminal::Jump(ref next) => {
-                    cfg.edges.push((label.clone(), next.clone()));
+                    cfg.add_edge(label, next, EdgeKind::Jump);
                 }
                 lir::Terminal::Branch {
                     cond: _,
                     ref tt,
                     ref ff,
                 } => {
-                    cfg.edges.push((label.clone(), tt.clone()));
-                    cfg.edges.push((label.clone(), ff.clone()));
+                    cfg.add_edge(label, tt, EdgeKind::True);
+                    cfg.add_edge(label, ff, EdgeKind::False);
                 }
 
-                lir::Terminal::Ret(_) => {
-                    // add edge: <block with ret terminal, dummy_exit>
-                    // cfg.edges.push((label.clone(), "dummy_exit".to_string()));
-                }
+                lir::Terminal::Ret(_) => {}
                 lir::Terminal::CallDirect {
                     lhs: _,
-                    callee: _,
+                    ref callee,
                     args: _,
                     ref next_bb,
                 } => {
-                    cfg.edges.push((label.clone(), next_bb.clone()));
+                    let kind = EdgeKind::CallReturn {
+                        callee: Some(callee.clone()),
+                    };
+                    cfg.add_edge(label, next_bb, kind);
                 }
                 lir::Terminal::CallIndirect {
                     lhs: _,
@@ -105,19 +100,166 @@ impl ControlFlowGraph {
                     args: _,
                     ref next_bb,
                 } => {
-                    cfg.edges.push((label.clone(), next_bb.clone()));
+                    cfg.add_edge(label, next_bb, EdgeKind::CallReturn { callee: None });
                 }
             }
         }
         cfg
     }
 
+    pub fn from_function_with(prog: &lir::Program, func_name: &str, options: &CfgOptions) -> Self {
+        let mut cfg = Self::from_function(prog, func_name);
+        if options.synthetic_entry {
+            cfg.add_dummy_entry();
+        }
+        if options.synthetic_exit {
+            cfg.add_dummy_exit();
+        }
+        if options.split_critical_edges {
+            cfg.split_critical_edges();
+        }
+        cfg
+    }
+
+    pub fn add_edge(&mut self, src: &str, dst: &str, kind: EdgeKind) {
+        let index = self.edges.len();
+        self.succs.entry(src.to_string()).or_default().push(index);
+        self.preds.entry(dst.to_string()).or_default().push(index);
+        self.edges.push(Edge {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            kind,
+        });
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn out_edges<'a>(&'a self, label: &str) -> impl Iterator<Item = &'a Edge> {
+        let indices = self.succs.get(label).map(|v| v.as_slice()).unwrap_or(&[]);
+        indices.iter().map(|&i| &self.edges[i])
+    }
+
+    pub fn in_edges<'a>(&'a self, label: &str) -> impl Iterator<Item = &'a Edge> {
+        let indices = self.preds.get(label).map(|v| v.as_slice()).unwrap_or(&[]);
+        indices.iter().map(|&i| &self.edges[i])
+    }
+
+    pub fn add_dummy_entry(&mut self) {
+        // a `dummy_entry` block (made unique if taken) jumping to the current entry, which then has
+        // a predecessor
+        if self.get_dummy_entry().is_some() {
+            return;
+        }
+        let Some(entry) = self.get_entry_label().map(str::to_string) else {
+            return;
+        };
+        let label = self.fresh_label("dummy_entry");
+        let dummy_entry = lir::Block::new(&label, &lir::Terminal::Jump(entry.clone()));
+        self.nodes.insert(label.clone(), dummy_entry);
+        self.add_edge(&label, &entry, EdgeKind::Jump);
+        self.dummy_entry = Some(label);
+    }
+
+    pub fn add_dummy_exit(&mut self) {
+        // a `dummy_exit` block (made unique if taken) with an edge from every `$ret` block, so that
+        // there is a unique exit
+        if self.get_dummy_exit().is_some() {
+            return;
+        }
+        let label = self.fresh_label("dummy_exit");
+        let mut ret_labels: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|(_, block)| matches!(block.term, lir::Terminal::Ret(_)))
+            .map(|(ret_label, _)| ret_label.clone())
+            .collect();
+        ret_labels.sort();
+        for ret_label in ret_labels {
+            self.add_edge(&ret_label, &label, EdgeKind::Jump);
+        }
+        let dummy_exit = lir::Block::new(&label, &lir::Terminal::Ret(None));
+        self.nodes.insert(label.clone(), dummy_exit);
+        self.dummy_exit = Some(label);
+    }
+
+    pub(crate) fn fresh_label(&self, label: &str) -> String {
+        // `label`, or else the first of `label_1`, `label_2`, ... that is not taken
+        let mut fresh = label.to_string();
+        let mut suffix = 1;
+        while self.nodes.contains_key(&fresh) {
+            fresh = format!("{}_{}", label, suffix);
+            suffix += 1;
+        }
+        fresh
+    }
+
+    pub fn split_critical_edges(&mut self) {
+        // put a new block `split_<src>_<dst>` (made unique if taken) that just jumps to `dst` on
+        // every critical edge <src, dst>, and retarget the terminal of `src` to it
+        let critical_edges: BTreeSet<(String, String)> = self
+            .edges
+            .iter()
+            .filter(|edge| {
+                self.get_successor_labels(&edge.src).len() > 1
+                    && self.get_predecessor_labels(&edge.dst).len() > 1
+            })
+            .map(|edge| (edge.src.clone(), edge.dst.clone()))
+            .collect();
+        let mut edges = std::mem::take(&mut self.edges);
+        for (src, dst) in critical_edges {
+            let label = self.fresh_label(&format!("split_{}_{}", src, dst));
+
+            let block = self.nodes.get_mut(&src).unwrap();
+            let retarget = |target: &mut String| {
+                if *target == dst {
+                    *target = label.clone();
+                }
+            };
+            match &mut block.term {
+                lir::Terminal::Jump(next)
+                | lir::Terminal::CallDirect { next_bb: next, .. }
+                | lir::Terminal::CallIndirect { next_bb: next, .. } => retarget(next),
+                lir::Terminal::Branch { tt, ff, .. } => {
+                    retarget(tt);
+                    retarget(ff);
+                }
+                lir::Terminal::Ret(_) => {}
+            }
+
+            for edge in edges.iter_mut() {
+                if edge.src == src && edge.dst == dst {
+                    edge.dst = label.clone();
+                }
+            }
+            edges.push(Edge {
+                src: label.clone(),
+                dst: dst.clone(),
+                kind: EdgeKind::Jump,
+            });
+            let split = lir::Block::new(&label, &lir::Terminal::Jump(dst));
+            self.nodes.insert(label, split);
+        }
+        self.set_edges(edges);
+    }
+
+    fn set_edges(&mut self, edges: Vec<Edge>) {
+        // replace all edges, rebuilding the adjacency lists
+        self.edges.clear();
+        self.succs.clear();
+        self.preds.clear();
+        for edge in edges {
+            self.add_edge(&edge.src, &edge.dst, edge.kind);
+        }
+    }
+
     pub fn get_block(&self, label: &str) -> Option<&lir::Block> {
         self.nodes.get(label)
     }
 
     pub fn get_dummy_entry(&self) -> Option<&lir::Block> {
-        self.nodes.get("dummy_entry")
+        self.nodes.get(self.dummy_entry.as_ref()?)
     }
 
     pub fn get_entry(&self) -> Option<&lir::Block> {
@@ -125,7 +267,36 @@ impl ControlFlowGraph {
     }
 
     pub fn get_dummy_exit(&self) -> Option<&lir::Block> {
-        self.nodes.get("dummy_exit")
+        self.nodes.get(self.dummy_exit.as_ref()?)
+    }
+
+    pub fn get_exit(&self) -> Option<&lir::Block> {
+        // the synthetic exit if there is one, or else search the block whose terminal is Ret
+        if let Some(dummy_exit) = self.get_dummy_exit() {
+            return Some(dummy_exit);
+        }
+        for block in self.nodes.values() {
+            if let lir::Terminal::Ret(_) = block.term {
+                return Some(block); // TODO: what if there are more-than-one blocks with Ret terminal?
+            }
+        }
+        None
+    }
+
+    pub fn reverse(&self) -> Self {
+        // reverse the edges of this CFG (keeping their kinds) and return a new one
+        let mut reversed_cfg = self.clone();
+        let reversed_edges = self
+            .edges
+            .iter()
+            .map(|edge| Edge {
+                src: edge.dst.clone(),
+                dst: edge.src.clone(),
+                kind: edge.kind.clone(),
+            })
+            .collect();
+        reversed_cfg.set_edges(reversed_edges);
+        reversed_cfg
     }
 
     pub fn get_block_label(&self, block: &lir::Block) -> Option<String> {
@@ -143,24 +314,30 @@ impl ControlFlowGraph {
     }
 
     pub fn get_predecessor_labels(&self, label: &str) -> Vec<String> {
-        self.edges
-            .iter()
-            .filter(|(_, dst)| dst == label)
-            .map(|(src, _)| src.clone())
-            .collect()
+        // distinct, in the order of the edges
+        let mut labels: Vec<String> = Vec::new();
+        for edge in self.in_edges(label) {
+            if !labels.contains(&edge.src) {
+                labels.push(edge.src.clone());
+            }
+        }
+        labels
     }
 
     pub fn get_successor_labels(&self, label: &str) -> Vec<String> {
-        self.edges
-            .iter()
-            .filter(|(src, _)| src == label)
-            .map(|(_, dst)| dst.clone())
-            .collect()
+        // distinct, in the order of the edges
+        let mut labels: Vec<String> = Vec::new();
+        for edge in self.out_edges(label) {
+            if !labels.contains(&edge.dst) {
+                labels.push(edge.dst.clone());
+            }
+        }
+        labels
     }
 
     pub fn get_predecessors(&self, block: &lir::Block) -> Vec<&lir::Block> {
-        let label = self.get_block_label(block).unwrap();
-        let predecessor_labels = self.get_predecessor_labels(&label);
+        // looked up by the block's id, which is its label
+        let predecessor_labels = self.get_predecessor_labels(&block.id);
         let mut predecessors = Vec::new();
         for predecessor_label in predecessor_labels {
             predecessors.push(self.nodes.get(&predecessor_label).unwrap());
@@ -169,8 +346,8 @@ impl ControlFlowGraph {
     }
 
     pub fn get_successors(&self, block: &lir::Block) -> Vec<&lir::Block> {
-        let label = self.get_block_label(block).unwrap();
-        let successor_labels = self.get_successor_labels(&label);
+        // looked up by the block's id, which is its label
+        let successor_labels = self.get_successor_labels(&block.id);
         let mut successors = Vec::new();
         for successor_label in successor_labels {
             successors.push(self.nodes.get(&successor_label).unwrap());
@@ -223,7 +400,7 @@ impl ControlFlowGraph {
             &mut loop_headers,
         );
 
-        for (src, _) in &self.edges {
+        for Edge { src, .. } in &self.edges {
             if !visited.contains(src) {
                 self.dfs_loop_headers(
                     src,
@@ -303,12 +480,557 @@ impl ControlFlowGraph {
     pub fn to_dot_file(&self, filename: &str) -> std::io::Result<()> {
         let mut file = std::fs::File::create(filename)?;
         file.write_all(b"digraph G {\n")?;
-        for (src, dst) in &self.edges {
+        for Edge { src, dst, .. } in &self.edges {
             file.write_all(format!("  {} -> {};\n", src, dst).as_bytes())?;
         }
         file.write_all(b"}")?;
         Ok(())
     }
+
+    pub fn to_detailed_dot_file(&self, filename: &str) -> std::io::Result<()> {
+        self.to_detailed_dot_file_with(filename, &HashMap::<String, String>::new())
+    }
+
+    pub fn to_detailed_dot_file_with<F: std::fmt::Display>(
+        &self,
+        filename: &str,
+        facts: &HashMap<String, F>,
+    ) -> std::io::Result<()> {
+        // every block as a record of its instructions and terminal in LIR syntax, followed by its
+        // fact (if any) which is also its tooltip; true / false edges are green / red
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(b"digraph G {\n")?;
+        file.write_all(b"  node [shape=record, fontname=\"Courier\"];\n")?;
+        let mut labels: Vec<&String> = self.nodes.keys().collect();
+        labels.sort();
+        for label in labels {
+            let block = &self.nodes[label];
+            let mut fields = vec![escape_record(&format!("{}:", label))];
+            let lines: Vec<String> = block.insts.iter().map(|inst| inst.to_string()).collect();
+            if !lines.is_empty() {
+                fields.push(escape_record(&lines.join("\n")));
+            }
+            fields.push(escape_record(&block.term.to_string()));
+            let mut attrs = String::new();
+            if let Some(fact) = facts.get(label) {
+                let fact = fact.to_string();
+                fields.push(escape_record(fact.trim_end()));
+                attrs = format!(", tooltip=\"{}\"", escape_string(fact.trim_end()));
+            }
+            file.write_all(
+                format!(
+                    "  \"{}\" [label=\"{{{}}}\"{}];\n",
+                    label,
+                    fields.join("|"),
+                    attrs
+                )
+                .as_bytes(),
+            )?;
+        }
+        for edge in &self.edges {
+            let attrs = match &edge.kind {
+                EdgeKind::Jump => String::new(),
+                EdgeKind::True => {
+                    " [label=\"T\", color=darkgreen, fontcolor=darkgreen]".to_string()
+                }
+                EdgeKind::False => " [label=\"F\", color=red, fontcolor=red]".to_string(),
+                EdgeKind::CallReturn { callee } => format!(
+                    " [label=\"{}\", style=dashed]",
+                    escape_string(callee.as_deref().unwrap_or("*"))
+                ),
+            };
+            file.write_all(
+                format!("  \"{}\" -> \"{}\"{};\n", edge.src, edge.dst, attrs).as_bytes(),
+            )?;
+        }
+        file.write_all(b"}")?;
+        Ok(())
+    }
+
+    pub fn to_detailed_graphml_file(&self, filename: &str) -> std::io::Result<()> {
+        self.to_detailed_graphml_file_with(filename, &HashMap::<String, String>::new())
+    }
+
+    pub fn to_detailed_graphml_file_with<F: std::fmt::Display>(
+        &self,
+        filename: &str,
+        facts: &HashMap<String, F>,
+    ) -> std::io::Result<()> {
+        // the code of every block and its fact (if any) as node data, the edge kinds as edge data
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")?;
+        file.write_all(b"<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n")?;
+        file.write_all(
+            b"<key id=\"code\" for=\"node\" attr.name=\"code\" attr.type=\"string\"/>\n",
+        )?;
+        file.write_all(
+            b"<key id=\"fact\" for=\"node\" attr.name=\"fact\" attr.type=\"string\"/>\n",
+        )?;
+        file.write_all(
+            b"<key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        )?;
+        file.write_all(b"<graph id=\"G\" edgedefault=\"directed\">\n")?;
+        let mut labels: Vec<&String> = self.nodes.keys().collect();
+        labels.sort();
+        for label in labels {
+            let code = escape_xml(self.nodes[label].to_string().trim_end());
+            let mut data = format!("<data key=\"code\">{}</data>", code);
+            if let Some(fact) = facts.get(label) {
+                let fact = escape_xml(fact.to_string().trim_end());
+                data.push_str(&format!("<data key=\"fact\">{}</data>", fact));
+            }
+            file.write_all(format!("  <node id=\"{}\">{}</node>\n", label, data).as_bytes())?;
+        }
+        for edge in &self.edges {
+            let kind = match &edge.kind {
+                EdgeKind::Jump => "jump".to_string(),
+                EdgeKind::True => "true".to_string(),
+                EdgeKind::False => "false".to_string(),
+                EdgeKind::CallReturn { callee } => {
+                    format!("call {}", callee.as_deref().unwrap_or("*"))
+                }
+            };
+            file.write_all(
+                format!(
+                    "  <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data></edge>\n",
+                    edge.src,
+                    edge.dst,
+                    escape_xml(&kind)
+                )
+                .as_bytes(),
+            )?;
+        }
+        file.write_all(b"</graph>\n")?;
+        file.write_all(b"</graphml>\n")?;
+        Ok(())
+    }
+
+    pub fn dominance_frontiers(&self) -> DominanceFrontiers {
+        DominanceFrontiers::new(self, &DominatorTree::new(self))
+    }
+
+    pub fn iterated_dominance_frontier(&self, blocks: &[&str]) -> BTreeSet<String> {
+        self.dominance_frontiers().iterated(blocks)
+    }
+
+    pub fn get_entry_label(&self) -> Option<&str> {
+        // the synthetic entry or `entry`, or else the only block without predecessors (if there is
+        // one)
+        if let Some(dummy_entry) = self.get_dummy_entry() {
+            return Some(&dummy_entry.id);
+        }
+        if let Some((label, _)) = self.nodes.get_key_value("entry") {
+            return Some(label);
+        }
+        let mut sources = self
+            .nodes
+            .keys()
+            .filter(|label| self.in_edges(label).next().is_none());
+        match (sources.next(), sources.next()) {
+            (Some(source), None) => Some(source),
+            _ => None,
+        }
+    }
+
+    pub fn reverse_postorder(&self) -> Vec<String> {
+        // the blocks reachable from entry, each before its successors except along back edges
+        let mut succs: HashMap<&str, Vec<&str>> = HashMap::new();
+        for Edge { src, dst, .. } in &self.edges {
+            if self.nodes.contains_key(dst) {
+                succs.entry(src).or_default().push(dst);
+            }
+        }
+        let mut postorder = Vec::new();
+        if let Some(entry) = self.get_entry_label() {
+            let mut visited = HashSet::from([entry]);
+            let mut stack = vec![(entry, 0)];
+            while let Some((label, next)) = stack.pop() {
+                let label_succs = succs.get(label).map(|s| s.as_slice()).unwrap_or(&[]);
+                if next < label_succs.len() {
+                    stack.push((label, next + 1));
+                    if visited.insert(label_succs[next]) {
+                        stack.push((label_succs[next], 0));
+                    }
+                } else {
+                    postorder.push(label.to_string());
+                }
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    pub fn weak_topological_order(&self) -> Wto {
+        let mut builder = WtoBuilder {
+            succs: HashMap::new(),
+            dfn: self.nodes.keys().map(|label| (label.as_str(), 0)).collect(),
+            num: 0,
+            stack: Vec::new(),
+        };
+        for Edge { src, dst, .. } in &self.edges {
+            if self.nodes.contains_key(dst) {
+                builder.succs.entry(src).or_default().push(dst);
+            }
+        }
+        let mut components = Vec::new();
+        if let Some(entry) = self.get_entry_label() {
+            builder.visit(entry, &mut components);
+        }
+        components.reverse();
+        Wto { components }
+    }
+
+    pub fn loop_forest(&self) -> LoopForest {
+        LoopForest::new(self)
+    }
+}
+
+fn escape_string(text: &str) -> String {
+    // a DOT string, with one line per line of text
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_record(text: &str) -> String {
+    // a field of a DOT record label, with one left-justified line per line of text
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\l"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push_str("\\l");
+    escaped
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn neighbors<'a>(map: &HashMap<&'a str, Vec<&'a str>>, label: &str) -> Vec<&'a str> {
+    map.get(label).cloned().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    // a natural loop: the header and the blocks reaching one of its back edges without passing it
+    pub header: String,
+    pub latches: BTreeSet<String>, // sources of the back edges to the header
+    pub body: BTreeSet<String>,    // including the header and the blocks of nested loops
+    pub exits: Vec<(String, String)>, // edges leaving the body
+    pub preheader: Option<String>, // the only block entering the loop, if it only jumps there
+    pub parent: Option<usize>,     // index of the innermost enclosing loop
+    pub depth: usize,              // 1 for outermost loops
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopForest {
+    // natural loops of the blocks reachable from entry, enclosing loops come first; cyclic regions
+    // that can be entered at more than one block have no natural loop and are listed separately
+    pub loops: Vec<Loop>,
+    pub irreducible: Vec<BTreeSet<String>>,
+}
+
+impl LoopForest {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let dom_tree = DominatorTree::new(cfg);
+        let mut succs: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut preds: HashMap<&str, Vec<&str>> = HashMap::new();
+        for Edge { src, dst, .. } in cfg.edges() {
+            succs.entry(src).or_default().push(dst);
+            preds.entry(dst).or_default().push(src);
+        }
+
+        // retreating edges of a DFS are either back edges (to a dominator), which close natural
+        // loops, or edges into irreducible regions
+        let mut latches: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+        let mut irreducible_targets = Vec::new();
+        if dom_tree.is_reachable(dom_tree.root()) {
+            let root = dom_tree.root();
+            let mut visited = HashSet::from([root]);
+            let mut on_stack = HashSet::from([root]);
+            let mut stack = vec![(root, 0)];
+            while let Some((label, next)) = stack.pop() {
+                let label_succs = neighbors(&succs, label);
+                if next == label_succs.len() {
+                    on_stack.remove(label);
+                    continue;
+                }
+                stack.push((label, next + 1));
+                let succ = label_succs[next];
+                if on_stack.contains(succ) {
+                    if dom_tree.dominates(succ, label) {
+                        latches.entry(succ).or_default().insert(label.to_string());
+                    } else {
+                        irreducible_targets.push(succ);
+                    }
+                } else if dom_tree.is_reachable(succ) && visited.insert(succ) {
+                    on_stack.insert(succ);
+                    stack.push((succ, 0));
+                }
+            }
+        }
+
+        let mut loops = Vec::new();
+        for (header, header_latches) in latches {
+            let mut body = BTreeSet::from([header.to_string()]);
+            let mut worklist: Vec<&str> = header_latches.iter().map(|l| l.as_str()).collect();
+            while let Some(label) = worklist.pop() {
+                if body.insert(label.to_string()) {
+                    worklist.extend(
+                        neighbors(&preds, label)
+                            .into_iter()
+                            .filter(|pred| dom_tree.is_reachable(pred)),
+                    );
+                }
+            }
+            let mut exits = Vec::new();
+            for label in &body {
+                for succ in neighbors(&succs, label) {
+                    if !body.contains(succ) {
+                        exits.push((label.clone(), succ.to_string()));
+                    }
+                }
+            }
+            exits.sort();
+            exits.dedup();
+            let mut entering: Vec<&str> = neighbors(&preds, header)
+                .into_iter()
+                .filter(|pred| !body.contains(*pred))
+                .collect();
+            entering.dedup();
+            let preheader = match entering[..] {
+                [pred] if neighbors(&succs, pred).iter().all(|succ| *succ == header) => {
+                    Some(pred.to_string())
+                }
+                _ => None,
+            };
+            loops.push(Loop {
+                header: header.to_string(),
+                latches: header_latches,
+                body,
+                exits,
+                preheader,
+                parent: None,
+                depth: 1,
+            });
+        }
+
+        // bodies of natural loops are either nested or disjoint, so the innermost enclosing loop
+        // is the last one (in order of decreasing size) containing the header
+        loops.sort_by(|a, b| {
+            b.body
+                .len()
+                .cmp(&a.body.len())
+                .then_with(|| a.header.cmp(&b.header))
+        });
+        for idx in 0..loops.len() {
+            let parent = (0..idx)
+                .rev()
+                .find(|outer| loops[*outer].body.contains(&loops[idx].header));
+            if let Some(parent) = parent {
+                loops[idx].parent = Some(parent);
+                loops[idx].depth = loops[parent].depth + 1;
+            }
+        }
+
+        // an irreducible region is the strongly connected component of a retreating edge's target
+        let reach = |from: &str, map: &HashMap<&str, Vec<&str>>| -> HashSet<String> {
+            let mut reached = HashSet::from([from.to_string()]);
+            let mut worklist = vec![from];
+            while let Some(label) = worklist.pop() {
+                for next in neighbors(map, label) {
+                    if dom_tree.is_reachable(next) && reached.insert(next.to_string()) {
+                        worklist.push(next);
+                    }
+                }
+            }
+            reached
+        };
+        let mut irreducible: Vec<BTreeSet<String>> = Vec::new();
+        for target in irreducible_targets {
+            if irreducible.iter().any(|region| region.contains(target)) {
+                continue;
+            }
+            let forward = reach(target, &succs);
+            let backward = reach(target, &preds);
+            irreducible.push(forward.intersection(&backward).cloned().collect());
+        }
+        irreducible.sort();
+
+        Self { loops, irreducible }
+    }
+
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|idx| self.loops[*idx].parent.is_none())
+            .collect()
+    }
+
+    pub fn children(&self, idx: usize) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|child| self.loops[*child].parent == Some(idx))
+            .collect()
+    }
+
+    pub fn is_header(&self, label: &str) -> bool {
+        self.loops.iter().any(|l| l.header == label)
+    }
+
+    pub fn innermost(&self, label: &str) -> Option<usize> {
+        // the innermost loop containing the block
+        (0..self.loops.len())
+            .rev()
+            .find(|idx| self.loops[*idx].body.contains(label))
+    }
+
+    pub fn loop_depth(&self, label: &str) -> usize {
+        self.innermost(label)
+            .map(|idx| self.loops[idx].depth)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WtoComponent {
+    Vertex(String),
+    Component {
+        head: String,
+        body: Vec<WtoComponent>,
+    },
+}
+
+impl WtoComponent {
+    pub fn head(&self) -> &str {
+        match self {
+            WtoComponent::Vertex(label) => label,
+            WtoComponent::Component { head, .. } => head,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wto {
+    // Bourdoncle's weak topological order of the blocks reachable from entry: every cycle of the
+    // CFG is inside a component and goes through its head
+    pub components: Vec<WtoComponent>,
+}
+
+impl Wto {
+    pub fn heads(&self) -> HashSet<String> {
+        let mut heads = HashSet::new();
+        let mut stack: Vec<&WtoComponent> = self.components.iter().collect();
+        while let Some(component) = stack.pop() {
+            if let WtoComponent::Component { head, body } = component {
+                heads.insert(head.clone());
+                stack.extend(body);
+            }
+        }
+        heads
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        // the blocks in the order of the WTO
+        fn flatten(components: &[WtoComponent], labels: &mut Vec<String>) {
+            for component in components {
+                labels.push(component.head().to_string());
+                if let WtoComponent::Component { body, .. } = component {
+                    flatten(body, labels);
+                }
+            }
+        }
+        let mut labels = Vec::new();
+        flatten(&self.components, &mut labels);
+        labels
+    }
+}
+
+impl std::fmt::Display for WtoComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WtoComponent::Vertex(label) => write!(f, "{}", label),
+            WtoComponent::Component { head, body } => {
+                write!(f, "({}", head)?;
+                for component in body {
+                    write!(f, " {}", component)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Wto {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // e.g., `entry (bb1 bb2 (bb3 bb4)) bb5`
+        let components: Vec<String> = self.components.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", components.join(" "))
+    }
+}
+
+struct WtoBuilder<'a> {
+    succs: HashMap<&'a str, Vec<&'a str>>,
+    dfn: HashMap<&'a str, usize>, // 0 for unvisited, usize::MAX for done
+    num: usize,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> WtoBuilder<'a> {
+    fn visit(&mut self, label: &'a str, partition: &mut Vec<WtoComponent>) -> usize {
+        // the components are pushed in reverse order
+        self.stack.push(label);
+        self.num += 1;
+        self.dfn.insert(label, self.num);
+        let mut head = self.num;
+        let mut is_loop = false;
+        for succ in self.succs.get(label).cloned().unwrap_or_default() {
+            let min = match self.dfn[succ] {
+                0 => self.visit(succ, partition),
+                succ_dfn => succ_dfn,
+            };
+            if min <= head {
+                head = min;
+                is_loop = true;
+            }
+        }
+        if head == self.dfn[label] {
+            self.dfn.insert(label, usize::MAX);
+            let mut element = self.stack.pop().unwrap();
+            if is_loop {
+                while element != label {
+                    self.dfn.insert(element, 0);
+                    element = self.stack.pop().unwrap();
+                }
+                partition.push(self.component(label));
+            } else {
+                partition.push(WtoComponent::Vertex(label.to_string()));
+            }
+        }
+        head
+    }
+
+    fn component(&mut self, label: &'a str) -> WtoComponent {
+        let mut body = Vec::new();
+        for succ in self.succs.get(label).cloned().unwrap_or_default() {
+            if self.dfn[succ] == 0 {
+                self.visit(succ, &mut body);
+            }
+        }
+        body.reverse();
+        WtoComponent::Component {
+            head: label.to_string(),
+            body,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -364,7 +1086,7 @@ mod test {
 
     #[test]
     fn test_example_program() {
-        let prog = lir::Program::parse_json("./examples/json/tortoise_and_hare.json");
+        let prog = lir::Program::parse_json("./examples/json/tortoise_and_hare.json").unwrap();
         // let prog = lir::Program::parse_json("./examples/json/lambda.json");
 
         println!("number for basic blocks: {}", prog.get_num_basic_blocks());
@@ -378,24 +1100,25 @@ mod test {
 
     #[test]
     fn test_example_function() {
-        let prog = lir::Program::parse_json("./demos/json/test3.json");
+        let prog = lir::Program::parse_json("./demos/json/test3.json").unwrap();
         let cfg: ControlFlowGraph = ControlFlowGraph::from_function(&prog, "test");
 
         // println!("{:#?}", prog);
         println!("all block labels: {:?}", cfg.nodes.keys());
-        println!("all edges: {:?}", cfg.edges);
+        println!("all edges: {:?}", cfg.edges());
         println!("topo orders of blocks: {:?}", cfg.topological_sort());
     }
 
     fn constr_demo_cfg() -> ControlFlowGraph {
+        // the block ids are the node labels, as in a CFG built from a function
         let mut cfg = ControlFlowGraph::new();
 
-        let block0 = lir::Block::new("block0", &lir::Terminal::Jump("xxx".to_string()));
-        let block1 = lir::Block::new("block1", &lir::Terminal::Jump("xxx".to_string()));
-        let block2 = lir::Block::new("block2", &lir::Terminal::Jump("xxx".to_string()));
-        let block3 = lir::Block::new("block3", &lir::Terminal::Jump("xxx".to_string()));
-        let block4 = lir::Block::new("block4", &lir::Terminal::Jump("xxx".to_string()));
-        let block5 = lir::Block::new("block5", &lir::Terminal::Jump("xxx".to_string()));
+        let block0 = lir::Block::new("bb0", &lir::Terminal::Jump("xxx".to_string()));
+        let block1 = lir::Block::new("bb1", &lir::Terminal::Jump("xxx".to_string()));
+        let block2 = lir::Block::new("bb2", &lir::Terminal::Jump("xxx".to_string()));
+        let block3 = lir::Block::new("bb3", &lir::Terminal::Jump("xxx".to_string()));
+        let block4 = lir::Block::new("bb4", &lir::Terminal::Jump("xxx".to_string()));
+        let block5 = lir::Block::new("bb5", &lir::Terminal::Jump("xxx".to_string()));
 
         let label0 = "bb0".to_string();
         let label1 = "bb1".to_string();
@@ -411,13 +1134,13 @@ mod test {
         cfg.nodes.insert(label4.clone(), block4.clone());
         cfg.nodes.insert(label5.clone(), block5.clone());
 
-        cfg.edges.push((label0.clone(), label1.clone()));
-        cfg.edges.push((label0.clone(), label2.clone()));
-        cfg.edges.push((label1.clone(), label3.clone()));
-        cfg.edges.push((label2.clone(), label3.clone()));
-        cfg.edges.push((label2.clone(), label4.clone()));
-        cfg.edges.push((label3.clone(), label5.clone()));
-        cfg.edges.push((label4.clone(), label5.clone()));
+        cfg.add_edge(&label0, &label1, EdgeKind::Jump);
+        cfg.add_edge(&label0, &label2, EdgeKind::Jump);
+        cfg.add_edge(&label1, &label3, EdgeKind::Jump);
+        cfg.add_edge(&label2, &label3, EdgeKind::Jump);
+        cfg.add_edge(&label2, &label4, EdgeKind::Jump);
+        cfg.add_edge(&label3, &label5, EdgeKind::Jump);
+        cfg.add_edge(&label4, &label5, EdgeKind::Jump);
 
         cfg
     }
@@ -449,20 +1172,20 @@ mod test {
         cfg.nodes.insert(label5.clone(), block5.clone());
         cfg.nodes.insert(label6.clone(), block6.clone());
 
-        cfg.edges.push((label0.clone(), label1.clone()));
-        cfg.edges.push((label1.clone(), label2.clone()));
-        cfg.edges.push((label2.clone(), label1.clone()));
-        cfg.edges.push((label2.clone(), label3.clone()));
-        cfg.edges.push((label3.clone(), label4.clone()));
-        cfg.edges.push((label4.clone(), label5.clone()));
-        cfg.edges.push((label5.clone(), label4.clone()));
-        cfg.edges.push((label4.clone(), label6.clone()));
+        cfg.add_edge(&label0, &label1, EdgeKind::Jump);
+        cfg.add_edge(&label1, &label2, EdgeKind::Jump);
+        cfg.add_edge(&label2, &label1, EdgeKind::Jump);
+        cfg.add_edge(&label2, &label3, EdgeKind::Jump);
+        cfg.add_edge(&label3, &label4, EdgeKind::Jump);
+        cfg.add_edge(&label4, &label5, EdgeKind::Jump);
+        cfg.add_edge(&label5, &label4, EdgeKind::Jump);
+        cfg.add_edge(&label4, &label6, EdgeKind::Jump);
 
         let loop_headers = cfg.get_loop_headers();
 
         println!("Loop headers: {:?}", loop_headers);
 
-        for (src, dst) in &cfg.edges {
+        for Edge { src, dst, .. } in cfg.edges() {
             println!(
                 "{} -> {}, is_in_cycle: {}",
                 src,
@@ -476,4 +1199,314 @@ mod test {
             HashSet::from_iter(vec!["bb1".to_string(), "bb4".to_string()])
         );
     }
+
+    #[test]
+    fn test_loop_forest() {
+        // the graph of test_detect_loop_headers, rooted at bb0
+        let mut cfg = ControlFlowGraph::new();
+        for label in ["bb0", "bb1", "bb2", "bb3", "bb4", "bb5", "bb6"] {
+            let block = lir::Block::new(label, &lir::Terminal::Jump("xxx".to_string()));
+            cfg.nodes.insert(label.to_string(), block);
+        }
+        for (src, dst) in [
+            ("bb0", "bb1"),
+            ("bb1", "bb2"),
+            ("bb2", "bb1"),
+            ("bb2", "bb3"),
+            ("bb3", "bb4"),
+            ("bb4", "bb5"),
+            ("bb5", "bb4"),
+            ("bb4", "bb6"),
+        ] {
+            cfg.add_edge(src, dst, EdgeKind::Jump);
+        }
+        let forest = cfg.loop_forest();
+        assert_eq!(forest.loops.len(), 2);
+        assert_eq!(forest.roots(), vec![0, 1]);
+        assert!(forest.irreducible.is_empty());
+
+        let outer = &forest.loops[0];
+        assert_eq!(outer.header, "bb1");
+        assert_eq!(outer.latches, BTreeSet::from(["bb2".to_string()]));
+        assert_eq!(
+            outer.body,
+            BTreeSet::from(["bb1".to_string(), "bb2".to_string()])
+        );
+        assert_eq!(outer.exits, vec![("bb2".to_string(), "bb3".to_string())]);
+        assert_eq!(outer.preheader, Some("bb0".to_string()));
+        assert_eq!(forest.loops[1].header, "bb4");
+        assert_eq!(forest.loops[1].preheader, Some("bb3".to_string()));
+        assert!(forest.is_header("bb4"));
+        assert!(!forest.is_header("bb5"));
+    }
+
+    #[test]
+    fn test_loop_forest_nested() {
+        let src = concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $jump outer\n\n",
+            "outer:\n  $branch c inner exit\n\n",
+            "inner:\n  $branch c body latch\n\n",
+            "body:\n  $jump inner\n\n",
+            "latch:\n  $jump outer\n\n",
+            "exit:\n  $ret c\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let cfg = ControlFlowGraph::from_function(&prog, "test");
+        let forest = cfg.loop_forest();
+        assert_eq!(forest.loops.len(), 2);
+
+        let outer = &forest.loops[0];
+        assert_eq!(outer.header, "outer");
+        assert_eq!(outer.body.len(), 4);
+        assert_eq!(outer.exits, vec![("outer".to_string(), "exit".to_string())]);
+        assert_eq!(outer.preheader, Some("entry".to_string()));
+        assert_eq!((outer.parent, outer.depth), (None, 1));
+
+        let inner = &forest.loops[1];
+        assert_eq!(inner.header, "inner");
+        assert_eq!(inner.latches, BTreeSet::from(["body".to_string()]));
+        assert_eq!(
+            inner.exits,
+            vec![("inner".to_string(), "latch".to_string())]
+        );
+        assert_eq!(inner.preheader, None);
+        assert_eq!((inner.parent, inner.depth), (Some(0), 2));
+        assert_eq!(forest.children(0), vec![1]);
+
+        assert_eq!(forest.innermost("body"), Some(1));
+        assert_eq!(forest.loop_depth("body"), 2);
+        assert_eq!(forest.loop_depth("latch"), 1);
+        assert_eq!(forest.loop_depth("exit"), 0);
+    }
+
+    #[test]
+    fn test_loop_forest_irreducible() {
+        // the cycle between A and B can be entered at both of them; C loops on itself
+        let mut cfg = ControlFlowGraph::new();
+        for label in ["entry", "A", "B", "C", "exit"] {
+            let block = lir::Block::new(label, &lir::Terminal::Jump("xxx".to_string()));
+            cfg.nodes.insert(label.to_string(), block);
+        }
+        for (src, dst) in [
+            ("entry", "A"),
+            ("entry", "B"),
+            ("A", "B"),
+            ("B", "A"),
+            ("B", "C"),
+            ("C", "C"),
+            ("C", "exit"),
+        ] {
+            cfg.add_edge(src, dst, EdgeKind::Jump);
+        }
+        let forest = cfg.loop_forest();
+        assert_eq!(forest.loops.len(), 1);
+        assert_eq!(forest.loops[0].header, "C");
+        assert_eq!(forest.loops[0].preheader, None);
+        assert_eq!(
+            forest.irreducible,
+            vec![BTreeSet::from(["A".to_string(), "B".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_synthetic_labels_taken() {
+        // blocks that already have the labels of the synthetic ones are left alone
+        let src = concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $jump dummy_entry\n\n",
+            "dummy_entry:\n  $jump dummy_exit\n\n",
+            "dummy_exit:\n  $ret c\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let options = CfgOptions {
+            synthetic_entry: true,
+            synthetic_exit: true,
+            ..Default::default()
+        };
+        let cfg = ControlFlowGraph::from_function_with(&prog, "test", &options);
+        assert_eq!(cfg.nodes.len(), 5);
+        assert_eq!(cfg.get_entry_label(), Some("dummy_entry_1"));
+        assert_eq!(cfg.get_successor_labels("dummy_entry_1"), vec!["entry"]);
+        assert_eq!(
+            cfg.get_block_label(cfg.get_exit().unwrap()).unwrap(),
+            "dummy_exit_1"
+        );
+        assert_eq!(cfg.get_successor_labels("dummy_exit"), vec!["dummy_exit_1"]);
+    }
+
+    #[test]
+    fn test_cfg_options() {
+        let src = concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $branch c then exit\n\n",
+            "then:\n  $jump exit\n\n",
+            "exit:\n  $ret c\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let plain = ControlFlowGraph::from_function_with(&prog, "test", &CfgOptions::default());
+        assert_eq!(plain.nodes.len(), 3);
+        assert!(plain.get_dummy_entry().is_none() && plain.get_dummy_exit().is_none());
+
+        let options = CfgOptions {
+            synthetic_entry: true,
+            synthetic_exit: true,
+            split_critical_edges: true,
+        };
+        let cfg = ControlFlowGraph::from_function_with(&prog, "test", &options);
+        assert_eq!(cfg.nodes.len(), 6);
+        assert_eq!(cfg.get_entry_label(), Some("dummy_entry"));
+        assert_eq!(
+            cfg.get_block_label(cfg.get_exit().unwrap()).unwrap(),
+            "dummy_exit"
+        );
+        assert_eq!(cfg.get_successor_labels("dummy_entry"), vec!["entry"]);
+        assert_eq!(cfg.get_successor_labels("exit"), vec!["dummy_exit"]);
+
+        // entry -> exit was critical: entry branches and exit is also reached from then
+        assert_eq!(
+            cfg.get_successor_labels("entry"),
+            vec!["then", "split_entry_exit"]
+        );
+        assert_eq!(cfg.get_successor_labels("split_entry_exit"), vec!["exit"]);
+        assert_eq!(
+            cfg.get_predecessor_labels("exit"),
+            vec!["then", "split_entry_exit"]
+        );
+        match &cfg.get_block("entry").unwrap().term {
+            lir::Terminal::Branch { tt, ff, .. } => {
+                assert_eq!((tt.as_str(), ff.as_str()), ("then", "split_entry_exit"))
+            }
+            term => panic!("unexpected terminal {:?}", term),
+        }
+        for Edge { src, dst, .. } in cfg.edges() {
+            assert!(
+                cfg.get_successor_labels(src).len() <= 1
+                    || cfg.get_predecessor_labels(dst).len() <= 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_edge_kinds() {
+        let src = concat!(
+            "fn f() -> int {\n",
+            "entry:\n  $ret 0\n}\n\n",
+            "fn test(c:int) -> int {\n",
+            "let x:int\n",
+            "entry:\n  $branch c next next\n\n",
+            "next:\n  x = $call_dir f() then exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let cfg = ControlFlowGraph::from_function(&prog, "test");
+        let kinds: Vec<&EdgeKind> = cfg.out_edges("entry").map(|edge| &edge.kind).collect();
+        assert_eq!(kinds, vec![&EdgeKind::True, &EdgeKind::False]);
+        assert_eq!(cfg.get_successor_labels("entry"), vec!["next"]);
+        assert_eq!(cfg.get_predecessor_labels("next"), vec!["entry"]);
+        assert_eq!(cfg.in_edges("next").count(), 2);
+
+        let call_edge = cfg.out_edges("next").next().unwrap();
+        assert_eq!(call_edge.dst, "exit");
+        assert_eq!(
+            call_edge.kind,
+            EdgeKind::CallReturn {
+                callee: Some("f".to_string())
+            }
+        );
+        assert_eq!(cfg.out_edges("exit").count(), 0);
+
+        let reversed = cfg.reverse();
+        assert_eq!(reversed.get_successor_labels("exit"), vec!["next"]);
+        assert_eq!(reversed.get_predecessor_labels("entry"), vec!["next"]);
+    }
+
+    #[test]
+    fn test_detailed_dot_file() {
+        let src = concat!(
+            "fn test(c:int) -> int {\n",
+            "let x:int\n",
+            "entry:\n  x = $copy 1\n  $branch c then exit\n\n",
+            "then:\n  $jump exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let cfg = ControlFlowGraph::from_function(&prog, "test");
+        let facts = HashMap::from([("entry".to_string(), "x -> 1\n")]);
+        let filename = std::env::temp_dir().join("cs260_test_detailed.dot");
+        let filename = filename.to_str().unwrap();
+        cfg.to_detailed_dot_file_with(filename, &facts).unwrap();
+        let dot = std::fs::read_to_string(filename).unwrap();
+        assert!(dot.contains(concat!(
+            "\"entry\" [label=\"{entry:\\l|x = $copy 1\\l|$branch c then exit\\l|x -\\> 1\\l}\", ",
+            "tooltip=\"x -> 1\"];"
+        )));
+        assert!(dot.contains("\"then\" [label=\"{then:\\l|$jump exit\\l}\"];"));
+        assert!(dot.contains("\"entry\" -> \"then\" [label=\"T\", color=darkgreen"));
+        assert!(dot.contains("\"entry\" -> \"exit\" [label=\"F\", color=red"));
+        assert!(dot.contains("\"then\" -> \"exit\";"));
+    }
+
+    #[test]
+    fn test_reverse_postorder() {
+        let cfg = constr_demo_cfg();
+        let rpo = cfg.reverse_postorder();
+        assert_eq!(rpo.len(), 6);
+        assert_eq!(rpo[0], "bb0");
+        assert_eq!(rpo[5], "bb5");
+        let position = |label: &str| rpo.iter().position(|l| l == label).unwrap();
+        for Edge { src, dst, .. } in cfg.edges() {
+            assert!(position(src) < position(dst));
+        }
+    }
+
+    #[test]
+    fn test_weak_topological_order() {
+        let src = concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $jump outer\n\n",
+            "outer:\n  $branch c inner exit\n\n",
+            "inner:\n  $branch c body latch\n\n",
+            "body:\n  $jump inner\n\n",
+            "latch:\n  $jump outer\n\n",
+            "dead:\n  $jump exit\n\n",
+            "exit:\n  $ret c\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let cfg = ControlFlowGraph::from_function(&prog, "test");
+        let wto = cfg.weak_topological_order();
+        assert_eq!(wto.to_string(), "entry (outer (inner body) latch) exit");
+        assert_eq!(
+            wto.heads(),
+            HashSet::from(["outer".to_string(), "inner".to_string()])
+        );
+        assert_eq!(
+            wto.labels(),
+            vec!["entry", "outer", "inner", "body", "latch", "exit"]
+        );
+
+        // the graph of test_detect_loop_headers, rooted at bb0; bb6 loops on itself
+        let mut cfg = ControlFlowGraph::new();
+        for label in ["bb0", "bb1", "bb2", "bb3", "bb4", "bb5", "bb6"] {
+            let block = lir::Block::new(label, &lir::Terminal::Jump("xxx".to_string()));
+            cfg.nodes.insert(label.to_string(), block);
+        }
+        for (src, dst) in [
+            ("bb0", "bb1"),
+            ("bb1", "bb2"),
+            ("bb2", "bb1"),
+            ("bb2", "bb3"),
+            ("bb3", "bb4"),
+            ("bb4", "bb5"),
+            ("bb5", "bb4"),
+            ("bb4", "bb6"),
+            ("bb6", "bb6"),
+        ] {
+            cfg.add_edge(src, dst, EdgeKind::Jump);
+        }
+        assert_eq!(
+            cfg.weak_topological_order().to_string(),
+            "bb0 (bb1 bb2) bb3 (bb4 bb5) (bb6)"
+        );
+    }
 }
@@ -0,0 +1,531 @@
+/*
+Dominator and post-dominator trees of control flow graphs, and control dependence.
+
+The immediate dominators are computed by the iterative algorithm of Cooper, Harvey and Kennedy
+("A Simple, Fast Dominance Algorithm"), which runs over the blocks in reverse postorder and is
+near-linear on the CFGs of real programs. Blocks unreachable from the root are not in the tree:
+they have no immediate dominator and are dominated only by themselves.
+
+Post-dominators are the dominators of the reversed CFG, rooted at the block that returns, or at a
+synthetic `dummy_exit` block when a function has several of them (or none).
+*/
+use crate::cfg::{ControlFlowGraph, Edge, EdgeKind};
+use crate::lir;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    root: String,
+    idom: HashMap<String, String>,
+    children: HashMap<String, Vec<String>>, // sorted by label
+    depth: HashMap<String, usize>,
+    interval: HashMap<String, (usize, usize)>, // preorder numbers of a block and its last descendant
+}
+
+impl DominatorTree {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        // rooted at `entry`, or else at the only block without predecessors (if there is one)
+        let mut root = "entry";
+        if !cfg.nodes.contains_key(root) {
+            let mut sources = cfg
+                .nodes
+                .keys()
+                .filter(|label| cfg.in_edges(label).next().is_none());
+            if let (Some(source), None) = (sources.next(), sources.next()) {
+                root = source;
+            }
+        }
+        Self::with_root(cfg, root)
+    }
+
+    pub fn with_root(cfg: &ControlFlowGraph, root: &str) -> Self {
+        let mut succs: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut preds: HashMap<&str, Vec<&str>> = HashMap::new();
+        for Edge { src, dst, .. } in cfg.edges() {
+            succs.entry(src).or_default().push(dst);
+            preds.entry(dst).or_default().push(src);
+        }
+
+        // number the reachable blocks in postorder (iteratively, functions can be large)
+        let mut postorder: Vec<&str> = Vec::new();
+        let mut post_num: HashMap<&str, usize> = HashMap::new();
+        if cfg.nodes.contains_key(root) {
+            let mut visited = HashSet::from([root]);
+            let mut stack: Vec<(&str, usize)> = vec![(root, 0)];
+            while let Some((label, next)) = stack.pop() {
+                let label_succs = succs.get(label).map(|s| s.as_slice()).unwrap_or(&[]);
+                if next < label_succs.len() {
+                    stack.push((label, next + 1));
+                    let succ = label_succs[next];
+                    if cfg.nodes.contains_key(succ) && visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                } else {
+                    post_num.insert(label, postorder.len());
+                    postorder.push(label);
+                }
+            }
+        }
+
+        // idoms[b] is the postorder number of the immediate dominator of block b
+        let mut idoms: Vec<Option<usize>> = vec![None; postorder.len()];
+        if let Some(root_num) = idoms.len().checked_sub(1) {
+            idoms[root_num] = Some(root_num);
+        }
+        let intersect = |idoms: &[Option<usize>], mut b1: usize, mut b2: usize| {
+            while b1 != b2 {
+                while b1 < b2 {
+                    b1 = idoms[b1].unwrap();
+                }
+                while b2 < b1 {
+                    b2 = idoms[b2].unwrap();
+                }
+            }
+            b1
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..postorder.len().saturating_sub(1)).rev() {
+                let mut new_idom = None;
+                for pred in preds.get(postorder[b]).map(|p| p.as_slice()).unwrap_or(&[]) {
+                    let p = match post_num.get(pred) {
+                        Some(p) if idoms[*p].is_some() => *p,
+                        _ => continue, // unreachable, or not processed yet
+                    };
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(other) => intersect(&idoms, p, other),
+                    });
+                }
+                if new_idom.is_some() && idoms[b] != new_idom {
+                    idoms[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut tree = Self {
+            root: root.to_string(),
+            idom: HashMap::new(),
+            children: HashMap::new(),
+            depth: HashMap::new(),
+            interval: HashMap::new(),
+        };
+        for (b, idom) in idoms.iter().enumerate() {
+            let idom = idom.unwrap();
+            tree.children.entry(postorder[b].to_string()).or_default();
+            if idom != b {
+                tree.idom
+                    .insert(postorder[b].to_string(), postorder[idom].to_string());
+                tree.children
+                    .entry(postorder[idom].to_string())
+                    .or_default()
+                    .push(postorder[b].to_string());
+            }
+        }
+        for children in tree.children.values_mut() {
+            children.sort();
+        }
+
+        // number the tree in preorder for constant-time dominance queries
+        if !postorder.is_empty() {
+            let mut counter = 0;
+            let mut stack = vec![(tree.root.clone(), 0, false)];
+            while let Some((label, depth, done)) = stack.pop() {
+                if done {
+                    tree.interval.get_mut(&label).unwrap().1 = counter - 1;
+                    continue;
+                }
+                tree.depth.insert(label.clone(), depth);
+                tree.interval.insert(label.clone(), (counter, counter));
+                counter += 1;
+                stack.push((label.clone(), depth, true));
+                for child in tree.children[&label].iter().rev() {
+                    stack.push((child.clone(), depth + 1, false));
+                }
+            }
+        }
+        tree
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn is_reachable(&self, label: &str) -> bool {
+        self.depth.contains_key(label)
+    }
+
+    pub fn idom(&self, label: &str) -> Option<&str> {
+        self.idom.get(label).map(|idom| idom.as_str())
+    }
+
+    pub fn dominates(&self, a: &str, b: &str) -> bool {
+        // every block dominates itself, even an unreachable one
+        if a == b {
+            return true;
+        }
+        match (self.interval.get(a), self.interval.get(b)) {
+            (Some((a_first, a_last)), Some((b_first, _))) => {
+                a_first <= b_first && b_first <= a_last
+            }
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: &str, b: &str) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    pub fn children(&self, label: &str) -> impl Iterator<Item = &str> {
+        self.children
+            .get(label)
+            .into_iter()
+            .flatten()
+            .map(|child| child.as_str())
+    }
+
+    pub fn depth(&self, label: &str) -> Option<usize> {
+        self.depth.get(label).cloned()
+    }
+
+    pub fn dominators<'a>(&'a self, label: &'a str) -> Vec<&'a str> {
+        // the dominators of a block from itself up to the root
+        let mut doms = Vec::new();
+        let mut cur = Some(label);
+        while let Some(dom) = cur {
+            doms.push(dom);
+            cur = self.idom(dom);
+        }
+        doms
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PostDominatorTree {
+    tree: DominatorTree, // the dominator tree of the reversed CFG
+}
+
+impl PostDominatorTree {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let mut reversed = cfg.reverse();
+        // rooted at the only block that returns (and has no successors), or else at a `dummy_exit`
+        // (made unique if taken) after all the blocks that return
+        let mut exits = cfg.nodes.iter().filter(|(label, block)| {
+            matches!(block.term, lir::Terminal::Ret(_))
+                && cfg.get_successor_labels(label).is_empty()
+        });
+        let root = match (exits.next(), exits.next()) {
+            (Some((exit, _)), None) => exit.clone(),
+            _ => {
+                let exit_label = cfg.fresh_label("dummy_exit");
+                for (label, block) in &cfg.nodes {
+                    if let lir::Terminal::Ret(_) = block.term {
+                        reversed.add_edge(&exit_label, label, EdgeKind::Jump);
+                    }
+                }
+                let dummy_exit = lir::Block::new(&exit_label, &lir::Terminal::Ret(None));
+                reversed.nodes.insert(exit_label.clone(), dummy_exit);
+                exit_label
+            }
+        };
+        Self {
+            tree: DominatorTree::with_root(&reversed, &root),
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        self.tree.root()
+    }
+
+    pub fn reaches_exit(&self, label: &str) -> bool {
+        self.tree.is_reachable(label)
+    }
+
+    pub fn ipdom(&self, label: &str) -> Option<&str> {
+        self.tree.idom(label)
+    }
+
+    pub fn post_dominates(&self, a: &str, b: &str) -> bool {
+        self.tree.dominates(a, b)
+    }
+
+    pub fn strictly_post_dominates(&self, a: &str, b: &str) -> bool {
+        self.tree.strictly_dominates(a, b)
+    }
+
+    pub fn children(&self, label: &str) -> impl Iterator<Item = &str> {
+        self.tree.children(label)
+    }
+
+    pub fn depth(&self, label: &str) -> Option<usize> {
+        self.tree.depth(label)
+    }
+
+    pub fn post_dominators<'a>(&'a self, label: &'a str) -> Vec<&'a str> {
+        // the post-dominators of a block from itself up to the root
+        self.tree.dominators(label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominanceFrontiers {
+    frontiers: HashMap<String, BTreeSet<String>>,
+}
+
+impl DominanceFrontiers {
+    pub fn new(cfg: &ControlFlowGraph, dom_tree: &DominatorTree) -> Self {
+        let mut frontiers = HashMap::new();
+        for label in cfg.nodes.keys() {
+            frontiers.insert(label.clone(), BTreeSet::new());
+        }
+        // walk up from each predecessor until reaching the immediate dominator of the block, which
+        // is the same as subtracting the strict dominators of the block from those of the predecessor
+        for edge in cfg.edges() {
+            let (pred, label) = (&edge.src, &edge.dst);
+            let idom = dom_tree.idom(label);
+            for dom_pred in dom_tree.dominators(pred) {
+                if Some(dom_pred) == idom {
+                    break;
+                }
+                if let Some(frontier) = frontiers.get_mut(dom_pred) {
+                    frontier.insert(label.clone());
+                }
+            }
+        }
+        Self { frontiers }
+    }
+
+    pub fn frontier(&self, label: &str) -> &BTreeSet<String> {
+        // the frontier of a block that is not in the CFG is empty
+        static EMPTY: BTreeSet<String> = BTreeSet::new();
+        self.frontiers.get(label).unwrap_or(&EMPTY)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.frontiers.keys().map(|label| label.as_str())
+    }
+
+    pub fn iterated(&self, blocks: &[&str]) -> BTreeSet<String> {
+        // the least fixed point of DF+(S) = DF(S ∪ DF+(S)), e.g., where phis of a variable assigned
+        // in the blocks go
+        let mut idf = BTreeSet::new();
+        let mut worklist: Vec<&str> = blocks.to_vec();
+        let mut visited: HashSet<&str> = blocks.iter().cloned().collect();
+        while let Some(label) = worklist.pop() {
+            for df_label in self.frontier(label) {
+                idf.insert(df_label.clone());
+                if visited.insert(df_label) {
+                    worklist.push(df_label);
+                }
+            }
+        }
+        idf
+    }
+}
+
+pub fn control_dependences(cfg: &ControlFlowGraph) -> HashMap<String, Vec<(String, bool)>> {
+    // map every block to the branch blocks it is control dependent on, along with the outcome of
+    // the branch (true or false) leading to it; sorted by branch block label
+    let pdom_tree = PostDominatorTree::new(cfg);
+    let mut deps: HashMap<String, Vec<(String, bool)>> = HashMap::new();
+    for label in cfg.nodes.keys() {
+        deps.insert(label.clone(), Vec::new());
+    }
+    for (label, block) in &cfg.nodes {
+        let (tt, ff) = match &block.term {
+            lir::Terminal::Branch { tt, ff, .. } => (tt, ff),
+            _ => continue, // the only successor post-dominates the block
+        };
+        if !pdom_tree.reaches_exit(label) {
+            continue;
+        }
+        // the blocks from the successor up to (excluding) the immediate post-dominator of the
+        // branch depend on it
+        let ipdom = pdom_tree.ipdom(label);
+        for (succ, outcome) in [(tt, true), (ff, false)] {
+            for dep in pdom_tree.post_dominators(succ) {
+                if Some(dep) == ipdom {
+                    break;
+                }
+                if let Some(dep_on) = deps.get_mut(dep) {
+                    if !dep_on.contains(&(label.clone(), outcome)) {
+                        dep_on.push((label.clone(), outcome));
+                    }
+                }
+            }
+        }
+    }
+    for dep_on in deps.values_mut() {
+        dep_on.sort();
+    }
+    deps
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lir;
+
+    fn constr_cfg(edges: &[(&str, &str)]) -> ControlFlowGraph {
+        let mut cfg = ControlFlowGraph::new();
+        for (src, dst) in edges {
+            for label in [src, dst] {
+                let block = lir::Block::new(label, &lir::Terminal::Ret(None));
+                cfg.nodes.insert(label.to_string(), block);
+            }
+            cfg.add_edge(src, dst, EdgeKind::Jump);
+        }
+        cfg
+    }
+
+    #[test]
+    fn test_dominator_tree() {
+        // entry -> B -> {C, D} -> E, and a loop E -> B
+        let cfg = constr_cfg(&[
+            ("entry", "B"),
+            ("B", "C"),
+            ("B", "D"),
+            ("C", "E"),
+            ("D", "E"),
+            ("E", "B"),
+            ("F", "E"),
+        ]);
+        let tree = DominatorTree::new(&cfg);
+        assert_eq!(tree.root(), "entry");
+        assert_eq!(tree.idom("entry"), None);
+        assert_eq!(tree.idom("B"), Some("entry"));
+        assert_eq!(tree.idom("E"), Some("B"));
+        assert_eq!(tree.children("B").collect::<Vec<_>>(), vec!["C", "D", "E"]);
+        assert_eq!(tree.depth("E"), Some(2));
+        assert_eq!(tree.dominators("C"), vec!["C", "B", "entry"]);
+        assert!(tree.dominates("B", "E"));
+        assert!(tree.dominates("E", "E"));
+        assert!(!tree.strictly_dominates("E", "E"));
+        assert!(!tree.dominates("C", "E"));
+
+        // F is unreachable
+        assert!(!tree.is_reachable("F"));
+        assert_eq!(tree.idom("F"), None);
+        assert_eq!(tree.depth("F"), None);
+        assert!(tree.dominates("F", "F"));
+        assert!(!tree.dominates("entry", "F"));
+    }
+
+    #[test]
+    fn test_large_function() {
+        // a thousand sequential diamonds, whose simple paths could never be enumerated
+        let labels: Vec<[String; 4]> = (0..1000)
+            .map(|i| {
+                [
+                    format!("head{}", i),
+                    format!("then{}", i),
+                    format!("else{}", i),
+                    format!("join{}", i),
+                ]
+            })
+            .collect();
+        let mut edges = vec![("entry", labels[0][0].as_str())];
+        for (i, [head, then, els, join]) in labels.iter().enumerate() {
+            edges.extend([(head.as_str(), then.as_str()), (head, els)]);
+            edges.extend([(then.as_str(), join.as_str()), (els, join)]);
+            if let Some(next) = labels.get(i + 1) {
+                edges.push((join, &next[0]));
+            }
+        }
+        let cfg = constr_cfg(&edges);
+        let tree = DominatorTree::new(&cfg);
+        assert_eq!(tree.idom("join999"), Some("head999"));
+        assert_eq!(tree.idom("head999"), Some("join998"));
+        assert_eq!(tree.depth("join999"), Some(2000));
+        assert!(tree.dominates("head0", "join999"));
+        assert!(!tree.dominates("then0", "join999"));
+    }
+
+    fn constr_func_cfg(src: &str) -> ControlFlowGraph {
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        ControlFlowGraph::from_function(&prog, "test")
+    }
+
+    fn dep(branch: &str, outcome: bool) -> (String, bool) {
+        (branch.to_string(), outcome)
+    }
+
+    #[test]
+    fn test_post_dominator_tree() {
+        let cfg = constr_func_cfg(CTRL_SRC);
+        let tree = PostDominatorTree::new(&cfg);
+        assert_eq!(tree.root(), "exit");
+        assert_eq!(tree.ipdom("exit"), None);
+        assert_eq!(tree.ipdom("entry"), Some("exit"));
+        assert_eq!(tree.ipdom("bb1"), Some("bb3"));
+        assert_eq!(tree.ipdom("bb2"), Some("exit"));
+        assert_eq!(tree.post_dominators("bb1"), vec!["bb1", "bb3", "exit"]);
+        assert!(tree.post_dominates("bb3", "bb1"));
+        assert!(!tree.post_dominates("bb3", "entry"));
+        assert!(tree.strictly_post_dominates("exit", "bb4"));
+        assert_eq!(
+            tree.children("exit").collect::<Vec<_>>(),
+            vec!["bb2", "bb3", "bb4", "entry"]
+        );
+        assert_eq!(tree.depth("bb1"), Some(2));
+
+        // several returning blocks and one that never reaches them
+        let cfg = constr_func_cfg(concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $branch c bb1 bb2\n\n",
+            "bb1:\n  $branch c bb3 bb4\n\n",
+            "bb2:\n  $ret 1\n\n",
+            "bb3:\n  $ret 2\n\n",
+            "bb4:\n  $jump bb4\n}\n",
+        ));
+        let tree = PostDominatorTree::new(&cfg);
+        assert_eq!(tree.root(), "dummy_exit");
+        assert_eq!(tree.ipdom("bb2"), Some("dummy_exit"));
+        assert_eq!(tree.ipdom("bb1"), Some("bb3"));
+        assert_eq!(tree.ipdom("entry"), Some("dummy_exit"));
+        assert!(!tree.reaches_exit("bb4"));
+        assert_eq!(tree.ipdom("bb4"), None);
+
+        // a block of the function named `dummy_exit` is not taken for the synthetic exit
+        let cfg = constr_func_cfg(concat!(
+            "fn test(c:int) -> int {\n",
+            "entry:\n  $branch c dummy_exit r2\n\n",
+            "dummy_exit:\n  $jump r1\n\n",
+            "r1:\n  $ret 1\n\n",
+            "r2:\n  $ret 2\n}\n",
+        ));
+        let tree = PostDominatorTree::new(&cfg);
+        assert_eq!(tree.root(), "dummy_exit_1");
+        assert_eq!(tree.ipdom("r1"), Some("dummy_exit_1"));
+        assert_eq!(tree.ipdom("dummy_exit"), Some("r1"));
+        assert_eq!(tree.ipdom("entry"), Some("dummy_exit_1"));
+        let deps = control_dependences(&cfg);
+        assert_eq!(deps["dummy_exit"], vec![dep("entry", true)]);
+        assert_eq!(deps["r1"], vec![dep("entry", true)]);
+        assert_eq!(deps["r2"], vec![dep("entry", false)]);
+    }
+
+    #[test]
+    fn test_control_dependences() {
+        let cfg = constr_func_cfg(CTRL_SRC);
+        let deps = control_dependences(&cfg);
+        assert_eq!(deps["entry"], vec![]);
+        assert_eq!(deps["bb1"], vec![dep("entry", true)]);
+        assert_eq!(deps["bb2"], vec![dep("entry", false)]);
+        assert_eq!(deps["bb3"], vec![dep("bb2", true), dep("entry", true)]);
+        assert_eq!(deps["bb4"], vec![dep("bb2", false), dep("bb4", true)]);
+        assert_eq!(deps["exit"], vec![]);
+    }
+
+    const CTRL_SRC: &str = concat!(
+        "fn test(c:int) -> int {\n",
+        "entry:\n  $branch c bb1 bb2\n\n",
+        "bb1:\n  $jump bb3\n\n",
+        "bb2:\n  $branch c bb3 bb4\n\n",
+        "bb3:\n  $jump exit\n\n",
+        "bb4:\n  $branch c bb4 exit\n\n",
+        "exit:\n  $ret c\n}\n",
+    );
+}
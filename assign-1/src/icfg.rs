@@ -0,0 +1,343 @@
+use crate::cfg::{ControlFlowGraph, EdgeKind};
+use crate::lir;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Node {
+    // a block of a function; block labels are only unique within their function
+    pub func: String,
+    pub block: String,
+}
+
+impl Node {
+    pub fn new(func: &str, block: &str) -> Self {
+        Self {
+            func: func.to_string(),
+            block: block.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}::{}", self.func, self.block)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IcfgEdgeKind {
+    Intra(EdgeKind), // an edge of the function's own CFG, including call -> next_bb
+    Call,            // from a call block to the callee's entry
+    Return,          // from the callee's exit to next_bb of the call block
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IcfgEdge {
+    pub src: Node,
+    pub dst: Node,
+    pub kind: IcfgEdgeKind,
+}
+
+pub trait CalleeResolver {
+    // the functions a `$call_idr` through `callee` in function `caller` may call
+    fn resolve(&self, prog: &lir::Program, caller: &str, callee: &lir::Variable) -> Vec<String>;
+}
+
+impl<F> CalleeResolver for F
+where
+    F: Fn(&lir::Program, &str, &lir::Variable) -> Vec<String>,
+{
+    fn resolve(&self, prog: &lir::Program, caller: &str, callee: &lir::Variable) -> Vec<String> {
+        self(prog, caller, callee)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypeBasedResolver;
+
+impl CalleeResolver for TypeBasedResolver {
+    fn resolve(&self, prog: &lir::Program, _caller: &str, callee: &lir::Variable) -> Vec<String> {
+        // every function whose type is the one the callee points to
+        let lir::Type::Pointer(ref pointee) = callee.typ else {
+            return Vec::new();
+        };
+        let lir::Type::Function(ref func_ty) = **pointee else {
+            return Vec::new();
+        };
+        let mut funcs: Vec<String> = prog
+            .functions
+            .values()
+            .filter(|func| {
+                func.ret_ty == func_ty.ret_ty
+                    && func.params.len() == func_ty.param_ty.len()
+                    && func
+                        .params
+                        .iter()
+                        .zip(&func_ty.param_ty)
+                        .all(|(param, ty)| param.typ == *ty)
+            })
+            .map(|func| func.id.clone())
+            .collect();
+        funcs.sort();
+        funcs
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PointsToResolver {
+    // a points-to result: the functions (or other objects, which are ignored) each variable may
+    // point to
+    pub points_to: HashMap<lir::Variable, BTreeSet<String>>,
+}
+
+impl CalleeResolver for PointsToResolver {
+    fn resolve(&self, prog: &lir::Program, _caller: &str, callee: &lir::Variable) -> Vec<String> {
+        self.points_to
+            .get(callee)
+            .into_iter()
+            .flatten()
+            .filter(|pointee| prog.functions.contains_key(*pointee))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Icfg {
+    // the CFGs of all functions of a program, with every call block linked to the entry of each
+    // callee and the exit of each callee linked back to the block the call returns to
+    pub cfgs: HashMap<String, ControlFlowGraph>,
+    edges: Vec<IcfgEdge>,
+    // indices into `edges` of the edges leaving / entering each node
+    succs: HashMap<Node, Vec<usize>>,
+    preds: HashMap<Node, Vec<usize>>,
+}
+
+impl Icfg {
+    pub fn new(prog: &lir::Program) -> Self {
+        Self::with_resolver(prog, &TypeBasedResolver)
+    }
+
+    pub fn with_resolver(prog: &lir::Program, resolver: &dyn CalleeResolver) -> Self {
+        let mut icfg = Self {
+            cfgs: HashMap::new(),
+            edges: Vec::new(),
+            succs: HashMap::new(),
+            preds: HashMap::new(),
+        };
+        let mut func_names: Vec<&String> = prog.functions.keys().collect();
+        func_names.sort();
+        for func_name in &func_names {
+            let cfg = ControlFlowGraph::from_function(prog, func_name);
+            for edge in cfg.edges() {
+                icfg.add_edge(
+                    Node::new(func_name, &edge.src),
+                    Node::new(func_name, &edge.dst),
+                    IcfgEdgeKind::Intra(edge.kind.clone()),
+                );
+            }
+            icfg.cfgs.insert(func_name.to_string(), cfg);
+        }
+
+        // link the call sites, in a fixed order of functions and blocks
+        for func_name in &func_names {
+            let function = &prog.functions[*func_name];
+            let mut labels: Vec<&String> = function.body.keys().collect();
+            labels.sort();
+            for label in labels {
+                let (callees, next_bb) = match function.body[label].term {
+                    lir::Terminal::CallDirect {
+                        ref callee,
+                        ref next_bb,
+                        ..
+                    } => (vec![callee.clone()], next_bb),
+                    lir::Terminal::CallIndirect {
+                        ref callee,
+                        ref next_bb,
+                        ..
+                    } => (resolver.resolve(prog, func_name, callee), next_bb),
+                    _ => continue,
+                };
+                for callee in callees {
+                    let Some(callee_cfg) = icfg.cfgs.get(&callee) else {
+                        continue;
+                    };
+                    let callee_entry = callee_cfg.get_entry_label().map(str::to_string);
+                    let callee_exit = callee_cfg
+                        .get_exit()
+                        .and_then(|exit| callee_cfg.get_block_label(exit));
+                    if let Some(callee_entry) = callee_entry {
+                        icfg.add_edge(
+                            Node::new(func_name, label),
+                            Node::new(&callee, &callee_entry),
+                            IcfgEdgeKind::Call,
+                        );
+                    }
+                    if let Some(callee_exit) = callee_exit {
+                        icfg.add_edge(
+                            Node::new(&callee, &callee_exit),
+                            Node::new(func_name, next_bb),
+                            IcfgEdgeKind::Return,
+                        );
+                    }
+                }
+            }
+        }
+        icfg
+    }
+
+    fn add_edge(&mut self, src: Node, dst: Node, kind: IcfgEdgeKind) {
+        let index = self.edges.len();
+        self.succs.entry(src.clone()).or_default().push(index);
+        self.preds.entry(dst.clone()).or_default().push(index);
+        self.edges.push(IcfgEdge { src, dst, kind });
+    }
+
+    pub fn nodes(&self) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self
+            .cfgs
+            .iter()
+            .flat_map(|(func, cfg)| cfg.nodes.keys().map(|label| Node::new(func, label)))
+            .collect();
+        nodes.sort();
+        nodes
+    }
+
+    pub fn block(&self, node: &Node) -> Option<&lir::Block> {
+        self.cfgs.get(&node.func)?.get_block(&node.block)
+    }
+
+    pub fn entry(&self, func: &str) -> Option<Node> {
+        let label = self.cfgs.get(func)?.get_entry_label()?;
+        Some(Node::new(func, label))
+    }
+
+    pub fn exit(&self, func: &str) -> Option<Node> {
+        let cfg = self.cfgs.get(func)?;
+        let label = cfg.get_block_label(cfg.get_exit()?)?;
+        Some(Node::new(func, &label))
+    }
+
+    pub fn edges(&self) -> &[IcfgEdge] {
+        &self.edges
+    }
+
+    pub fn out_edges<'a>(&'a self, node: &Node) -> impl Iterator<Item = &'a IcfgEdge> {
+        let indices = self.succs.get(node).map(|v| v.as_slice()).unwrap_or(&[]);
+        indices.iter().map(|&i| &self.edges[i])
+    }
+
+    pub fn in_edges<'a>(&'a self, node: &Node) -> impl Iterator<Item = &'a IcfgEdge> {
+        let indices = self.preds.get(node).map(|v| v.as_slice()).unwrap_or(&[]);
+        indices.iter().map(|&i| &self.edges[i])
+    }
+
+    pub fn successors(&self, node: &Node) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = Vec::new();
+        for edge in self.out_edges(node) {
+            if !nodes.contains(&&edge.dst) {
+                nodes.push(&edge.dst);
+            }
+        }
+        nodes
+    }
+
+    pub fn predecessors(&self, node: &Node) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = Vec::new();
+        for edge in self.in_edges(node) {
+            if !nodes.contains(&&edge.src) {
+                nodes.push(&edge.src);
+            }
+        }
+        nodes
+    }
+
+    pub fn callees(&self, node: &Node) -> Vec<&str> {
+        // the functions called at a call block
+        self.out_edges(node)
+            .filter(|edge| edge.kind == IcfgEdgeKind::Call)
+            .map(|edge| edge.dst.func.as_str())
+            .collect()
+    }
+
+    pub fn call_sites(&self, func: &str) -> Vec<&Node> {
+        // the call blocks that may call a function
+        match self.entry(func) {
+            Some(entry) => self
+                .in_edges(&entry)
+                .filter(|edge| edge.kind == IcfgEdgeKind::Call)
+                .map(|edge| &edge.src)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SRC: &str = concat!(
+        "f:&(int) -> int\n",
+        "g:&(int) -> int\n\n",
+        "fn f(p:int) -> int {\n",
+        "entry:\n  $ret p\n}\n\n",
+        "fn g(p:int) -> int {\n",
+        "let q:int\n",
+        "entry:\n  $branch p then exit\n\n",
+        "then:\n  q = $arith add p 1\n  $jump exit\n\n",
+        "exit:\n  $ret q\n}\n\n",
+        "fn h() -> int {\n",
+        "entry:\n  $ret 0\n}\n\n",
+        "fn main() -> int {\n",
+        "let x:int, y:int, fp:&(int) -> int\n",
+        "entry:\n  x = $call_dir f(1) then bb1\n\n",
+        "bb1:\n  fp = $copy g\n  y = $call_idr fp(x) then bb2\n\n",
+        "bb2:\n  $ret y\n}\n",
+    );
+
+    #[test]
+    fn test_direct_calls() {
+        let prog = lir::Program::parse_lir_str(SRC).unwrap();
+        let icfg = Icfg::new(&prog);
+        assert_eq!(icfg.nodes().len(), 8);
+
+        let call = Node::new("main", "entry");
+        assert_eq!(icfg.callees(&call), vec!["f"]);
+        assert_eq!(
+            icfg.successors(&call),
+            vec![&Node::new("main", "bb1"), &Node::new("f", "entry")]
+        );
+        let ret = Node::new("f", "entry");
+        assert!(icfg
+            .out_edges(&ret)
+            .any(|edge| edge.kind == IcfgEdgeKind::Return && edge.dst == Node::new("main", "bb1")));
+        // the indirect call in bb1 may also call f
+        assert_eq!(icfg.call_sites("f"), vec![&Node::new("main", "bb1"), &call]);
+        assert!(icfg.call_sites("h").is_empty());
+        assert_eq!(icfg.exit("g"), Some(Node::new("g", "exit")));
+    }
+
+    #[test]
+    fn test_indirect_calls() {
+        let prog = lir::Program::parse_lir_str(SRC).unwrap();
+        let call = Node::new("main", "bb1");
+
+        // both f and g take an int and return an int
+        let icfg = Icfg::new(&prog);
+        assert_eq!(icfg.callees(&call), vec!["f", "g"]);
+        assert_eq!(
+            icfg.predecessors(&Node::new("main", "bb2")),
+            vec![
+                &Node::new("main", "bb1"),
+                &Node::new("f", "entry"),
+                &Node::new("g", "exit")
+            ]
+        );
+
+        let only_g = |_: &lir::Program, _: &str, _: &lir::Variable| vec!["g".to_string()];
+        let icfg = Icfg::with_resolver(&prog, &only_g);
+        assert_eq!(icfg.callees(&call), vec!["g"]);
+        assert_eq!(icfg.call_sites("f"), vec![&Node::new("main", "entry")]);
+    }
+}
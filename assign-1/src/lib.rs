@@ -1,5 +1,12 @@
 pub mod abs;
+pub mod callgraph;
 pub mod cfg;
+pub mod dominance;
+pub mod icfg;
 pub mod lir;
+pub mod ssa;
 pub mod store;
+#[cfg(test)]
+mod test_utils;
+pub mod transform;
 pub mod utils;
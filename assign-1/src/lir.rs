@@ -7,7 +7,22 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+mod builder;
+mod error;
+mod parser;
+mod printer;
+mod validate;
+
+pub mod gen;
+pub mod interned;
+pub mod interp;
+pub mod typeck;
+
+pub use builder::{Arg, FunctionBuilder, ProgramBuilder};
+pub use error::LirError;
+pub use validate::Diagnostic;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Program {
     pub structs: HashMap<String, Vec<Field>>,
     pub globals: Vec<Variable>,
@@ -15,7 +30,7 @@ pub struct Program {
     pub externs: HashMap<String, Type>,       // external function declarations
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Field {
     pub name: String,
     pub typ: Type,
@@ -48,7 +63,7 @@ impl Variable {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Function {
     pub id: String,
     pub ret_ty: Option<Type>,
@@ -278,7 +293,7 @@ impl Program {
                 addrof_ints.push(var);
             }
         }
-        
+
         #[cfg(debug_assertions)]
         {
             println!("---------------------------------");
@@ -298,10 +313,22 @@ impl Program {
         blocks
     }
 
-    pub fn parse_json(fname: &str) -> Program {
-        let file = File::open(fname).expect("Failed to open file");
+    pub fn parse_json(fname: &str) -> Result<Program, LirError> {
+        let file = File::open(fname).map_err(|e| LirError::from_io(fname, &e))?;
         let reader = BufReader::new(file);
-        json::from_reader(reader).unwrap()
+        json::from_reader(reader).map_err(|e| LirError::from_json(fname, &e))
+    }
+
+    pub fn parse_lir(fname: &str) -> Result<Program, LirError> {
+        let src = std::fs::read_to_string(fname).map_err(|e| LirError::from_io(fname, &e))?;
+        Program::parse_lir_str(&src).map_err(|e| LirError {
+            file: fname.to_string(),
+            ..e
+        })
+    }
+
+    pub fn parse_lir_str(src: &str) -> Result<Program, LirError> {
+        parser::parse(src)
     }
 
     pub fn write_json(&self, fname: &str) {
@@ -310,6 +337,10 @@ impl Program {
         json::to_writer(writer, self).unwrap();
     }
 
+    pub fn write_lir(&self, fname: &str) {
+        std::fs::write(fname, self.to_string()).expect("Failed to create file");
+    }
+
     pub fn get_num_fields(&self) -> u32 {
         self.structs.values().map(|s| s.len()).sum::<usize>() as u32
     }
@@ -540,7 +571,7 @@ mod test {
     #[test]
     fn test_get_globals() {
         let prog_name = "./examples/json/lambda.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
         let global_ints = prog.get_int_globals();
         // let gg = prog.globals;
 
@@ -549,4 +580,126 @@ mod test {
             println!("{:?}", var);
         }
     }
+
+    #[test]
+    fn test_parse_lir() {
+        // the native parser must agree with lir2json on every shipped program
+        for (lir_dir, json_dir) in [
+            ("./examples/lir", "./examples/json"),
+            ("./demos/lir", "./demos/json"),
+        ] {
+            for entry in std::fs::read_dir(lir_dir).unwrap() {
+                let lir_fname = entry.unwrap().path();
+                if lir_fname.extension().unwrap() != "lir" {
+                    continue;
+                }
+                let stem = lir_fname.file_stem().unwrap().to_str().unwrap();
+                let json_fname = format!("{}/{}.json", json_dir, stem);
+                println!("comparing {} against {}", lir_fname.display(), json_fname);
+                let prog = Program::parse_json(&json_fname).unwrap();
+                assert_eq!(
+                    Program::parse_lir(lir_fname.to_str().unwrap()).unwrap(),
+                    prog
+                );
+                // printing and parsing back must be lossless
+                assert_eq!(Program::parse_lir_str(&prog.to_string()).unwrap(), prog);
+            }
+        }
+    }
+
+    #[test]
+    fn test_print_sorted() {
+        // the order globals and locals were added in does not show in the printed program
+        let mut prog = Program::parse_json("./examples/json/heap_ptr_based.json").unwrap();
+        let text = prog.to_string();
+        prog.globals.reverse();
+        for function in prog.functions.values_mut() {
+            function.locals.reverse();
+        }
+        assert_eq!(prog.to_string(), text);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let src = "fn main() -> int {\nentry:\n  $foo 1\n  $ret 0\n}\n";
+        let err = Program::parse_lir_str(src).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        assert_eq!(err.msg, "unknown instruction `$foo`");
+
+        let src = "g:\nfn main() -> int {\nentry:\n  $ret 0\n}\n";
+        let err = Program::parse_lir_str(src).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 3));
+        assert_eq!(err.msg, "expected type after `:`");
+
+        let err = Program::parse_lir("./no/such/file.lir").unwrap_err();
+        assert_eq!(err.file, "./no/such/file.lir");
+
+        let err = Program::parse_json("./no/such/file.json").unwrap_err();
+        assert_eq!(err.line, 0);
+    }
+
+    #[test]
+    fn test_validate() {
+        for dir in ["./examples/json", "./demos/json"] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let prog = Program::parse_json(entry.unwrap().path().to_str().unwrap()).unwrap();
+                assert_eq!(prog.validate(), vec![]);
+            }
+        }
+
+        let src = "extern f:() -> int\n\
+                   fn f() -> int {\nlet p:&int\nentry:\n  p = $alloc 1 [_a1]\n  $jump exit\n\
+                   loop:\n  p = $alloc 1 [_a1]\n  $jump loop\nexit:\n  $ret 0\n}\n\
+                   fn main() -> int {\nentry:\n  $call_dir main() then exit\nexit:\n  $ret 0\n}\n";
+        let diags: Vec<String> = Program::parse_lir_str(src)
+            .unwrap()
+            .validate()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diags,
+            vec![
+                "extern `f` has the same name as a function",
+                "function `f`, block `loop`: `$alloc` id `_a1` is already used in function `f`, block `entry`",
+                "function `f`, block `loop`: block is unreachable from `entry`",
+                "function `f`, block `loop`: block cannot reach the `$ret` block `exit`",
+                "function `main`, block `entry`: `main` cannot be called",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typeck() {
+        for dir in ["./examples/json", "./demos/json"] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let prog = Program::parse_json(entry.unwrap().path().to_str().unwrap()).unwrap();
+                assert_eq!(typeck::check(&prog), vec![]);
+            }
+        }
+
+        let src = "struct s {\n  f:int\n}\n\
+                   extern e:(int) -> int\n\
+                   fn main() -> int {\nlet fp:&(int) -> int, p:&int, q:&s, x:int\nentry:\n\
+                   x = $arith add p 1\n  x = $load q\n  p = $gfp q f\n  x = $call_ext e(p)\n\
+                   x = $call_idr fp(q) then exit\nexit:\n  $ret p\n}\n";
+        let mut prog = Program::parse_lir_str(src).unwrap();
+        // a stale type in the JSON form, e.g., after editing a declaration by hand
+        prog.functions.get_mut("main").unwrap().locals[3].typ = Type::Pointer(Box::new(Type::Int));
+        let errors: Vec<String> = typeck::check(&prog).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "function `main`, block `entry`, instruction 0: variable `x` is used as `int` but declared as `&int`",
+                "function `main`, block `entry`, instruction 0: the first operand of `$arith` should be `int`, found `&int`",
+                "function `main`, block `entry`, instruction 1: variable `x` is used as `int` but declared as `&int`",
+                "function `main`, block `entry`, instruction 1: `x` should be `s`, found `int`",
+                "function `main`, block `entry`, instruction 3: argument 0 of `e` should be `int`, found `&int`",
+                "function `main`, block `entry`, instruction 3: variable `x` is used as `int` but declared as `&int`",
+                "function `main`, block `entry`, terminal: argument 0 of `fp` should be `int`, found `&s`",
+                "function `main`, block `entry`, terminal: variable `x` is used as `int` but declared as `&int`",
+                "function `main`, block `exit`, terminal: the returned value should be `int`, found `&int`",
+            ]
+        );
+    }
 }
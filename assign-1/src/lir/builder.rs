@@ -0,0 +1,518 @@
+/*
+Fluent builders for constructing LIR programs in code, mostly for tests.
+
+Variables are referred to by name and resolved against the params and locals of the function under
+construction, then against the globals, so that every emitted Variable carries its declared type
+and scope. Operands are either names or constants, e.g.
+
+    let mut pb = ProgramBuilder::new();
+    pb.global("g", Type::Int);
+    let mut f = pb.function("test", &[("p", Type::Int.ptr())], Some(Type::Int));
+    f.local("x", Type::Int);
+    f.block("entry").load("x", "p").arith("x", ArithOp::Add, "x", "g").ret("x");
+    f.finish();
+    let prog = pb.finish().unwrap();
+
+ProgramBuilder::finish validates and type checks the program, after adding the trivial `main` of
+the demos if none was built.
+*/
+use super::*;
+
+impl Type {
+    pub fn ptr(self) -> Type {
+        Type::Pointer(Box::new(self))
+    }
+
+    pub fn func(param_ty: Vec<Type>, ret_ty: Option<Type>) -> Type {
+        Type::Function(Box::new(FunctionType { ret_ty, param_ty }))
+    }
+}
+
+// an operand given to a builder: the name of a variable, or a constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg<'a> {
+    Var(&'a str),
+    Const(i32),
+}
+
+impl<'a> From<&'a str> for Arg<'a> {
+    fn from(name: &'a str) -> Arg<'a> {
+        Arg::Var(name)
+    }
+}
+
+impl<'a> From<i32> for Arg<'a> {
+    fn from(n: i32) -> Arg<'a> {
+        Arg::Const(n)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgramBuilder {
+    prog: Program,
+    errors: Vec<Diagnostic>, // names that could not be resolved, blocks left open
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgramBuilder {
+    pub fn new() -> ProgramBuilder {
+        ProgramBuilder {
+            prog: Program::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn structure(&mut self, name: &str, fields: &[(&str, Type)]) -> &mut Self {
+        let fields = fields
+            .iter()
+            .map(|(name, typ)| Field {
+                name: name.to_string(),
+                typ: typ.clone(),
+            })
+            .collect();
+        self.prog.structs.insert(name.to_string(), fields);
+        self
+    }
+
+    pub fn global(&mut self, name: &str, typ: Type) -> &mut Self {
+        self.prog.globals.push(Variable {
+            name: name.to_string(),
+            typ,
+            scope: None,
+        });
+        self
+    }
+
+    pub fn external(&mut self, name: &str, param_ty: &[Type], ret_ty: Option<Type>) -> &mut Self {
+        self.prog
+            .externs
+            .insert(name.to_string(), Type::func(param_ty.to_vec(), ret_ty));
+        self
+    }
+
+    // start a function; it is added to the program by FunctionBuilder::finish
+    pub fn function(
+        &mut self,
+        name: &str,
+        params: &[(&str, Type)],
+        ret_ty: Option<Type>,
+    ) -> FunctionBuilder<'_> {
+        let params = params
+            .iter()
+            .map(|(pname, typ)| Variable {
+                name: pname.to_string(),
+                typ: typ.clone(),
+                scope: Some(name.to_string()),
+            })
+            .collect();
+        FunctionBuilder {
+            prog: self,
+            func: Function {
+                id: name.to_string(),
+                ret_ty,
+                params,
+                locals: Vec::new(),
+                body: HashMap::new(),
+            },
+            block: None,
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let mut prog = self.prog.clone();
+        if !prog.functions.contains_key("main") {
+            let mut main = Function {
+                id: "main".to_string(),
+                ret_ty: Some(Type::Int),
+                params: Vec::new(),
+                locals: Vec::new(),
+                body: HashMap::new(),
+            };
+            let entry = Block::new("entry", &Terminal::Ret(Some(Operand::CInt(0))));
+            main.body.insert(entry.id.clone(), entry);
+            prog.functions.insert(main.id.clone(), main);
+        }
+        prog.globals.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut diags = self.errors.clone();
+        diags.extend(prog.validate());
+        diags.extend(typeck::check(&prog).into_iter().map(|err| {
+            let msg = match err.index {
+                Some(idx) => format!("instruction {}: {}", idx, err.msg),
+                None => format!("terminal: {}", err.msg),
+            };
+            Diagnostic {
+                func: Some(err.func),
+                block: Some(err.block),
+                msg,
+            }
+        }));
+        if diags.is_empty() {
+            Ok(prog)
+        } else {
+            Err(diags)
+        }
+    }
+}
+
+pub struct FunctionBuilder<'a> {
+    prog: &'a mut ProgramBuilder,
+    func: Function,
+    block: Option<Block>, // the block under construction, closed by its terminal
+}
+
+impl<'a> FunctionBuilder<'a> {
+    pub fn local(&mut self, name: &str, typ: Type) -> &mut Self {
+        self.func.locals.push(Variable {
+            name: name.to_string(),
+            typ,
+            scope: Some(self.func.id.clone()),
+        });
+        self
+    }
+
+    // start a basic block, to be closed by one of the terminal methods
+    pub fn block(&mut self, label: &str) -> &mut Self {
+        if let Some(block) = self.block.take() {
+            self.error(&block.id, "block has no terminal".to_string());
+        }
+        self.block = Some(Block::new(label, &Terminal::Ret(None)));
+        self
+    }
+
+    pub fn finish(mut self) {
+        if let Some(block) = self.block.take() {
+            self.error(&block.id, "block has no terminal".to_string());
+        }
+        self.func.locals.sort_by(|a, b| a.name.cmp(&b.name));
+        self.prog
+            .prog
+            .functions
+            .insert(self.func.id.clone(), self.func);
+    }
+
+    fn error(&mut self, block: &str, msg: String) {
+        self.prog.errors.push(Diagnostic {
+            func: Some(self.func.id.clone()),
+            block: Some(block.to_string()),
+            msg,
+        });
+    }
+
+    fn label(&self) -> String {
+        match &self.block {
+            Some(block) => block.id.clone(),
+            None => String::new(),
+        }
+    }
+
+    // the declaration of `name`, or an int local if there is none (which is reported)
+    fn var(&mut self, name: &str) -> Variable {
+        let decl = self
+            .func
+            .params
+            .iter()
+            .chain(self.func.locals.iter())
+            .chain(self.prog.prog.globals.iter())
+            .find(|v| v.name == name)
+            .cloned();
+        decl.unwrap_or_else(|| {
+            let label = self.label();
+            self.error(&label, format!("variable `{}` is not declared", name));
+            Variable {
+                name: name.to_string(),
+                typ: Type::Int,
+                scope: Some(self.func.id.clone()),
+            }
+        })
+    }
+
+    fn operand(&mut self, arg: Arg) -> Operand {
+        match arg {
+            Arg::Var(name) => Operand::Var(self.var(name)),
+            Arg::Const(n) => Operand::CInt(n),
+        }
+    }
+
+    fn operands(&mut self, args: &[Arg]) -> Vec<Operand> {
+        args.iter().map(|arg| self.operand(*arg)).collect()
+    }
+
+    fn lhs(&mut self, lhs: Option<&str>) -> Option<Variable> {
+        lhs.map(|name| self.var(name))
+    }
+
+    fn push(&mut self, inst: Instruction) -> &mut Self {
+        match &mut self.block {
+            Some(block) => block.insts.push(inst),
+            None => self.error("", "instruction outside of a block".to_string()),
+        }
+        self
+    }
+
+    fn terminate(&mut self, term: Terminal) -> &mut Self {
+        match self.block.take() {
+            Some(mut block) => {
+                block.term = term;
+                self.func.body.insert(block.id.clone(), block);
+            }
+            None => self.error("", "terminal outside of a block".to_string()),
+        }
+        self
+    }
+
+    pub fn addrof(&mut self, lhs: &str, rhs: &str) -> &mut Self {
+        let lhs = self.var(lhs);
+        let rhs = self.var(rhs);
+        self.push(Instruction::AddrOf { lhs, rhs })
+    }
+
+    // `id` names the allocation site, its type is the pointee type of `lhs`
+    pub fn alloc<'n>(&mut self, lhs: &str, num: impl Into<Arg<'n>>, id: &str) -> &mut Self {
+        let lhs = self.var(lhs);
+        let num = self.operand(num.into());
+        let typ = match &lhs.typ {
+            Type::Pointer(pointee) => pointee.as_ref().clone(),
+            typ => typ.clone(), // reported by the type checker
+        };
+        let id = Variable {
+            name: id.to_string(),
+            typ,
+            scope: None,
+        };
+        self.push(Instruction::Alloc { lhs, num, id })
+    }
+
+    pub fn copy<'n>(&mut self, lhs: &str, op: impl Into<Arg<'n>>) -> &mut Self {
+        let lhs = self.var(lhs);
+        let op = self.operand(op.into());
+        self.push(Instruction::Copy { lhs, op })
+    }
+
+    pub fn gep<'n>(&mut self, lhs: &str, src: &str, idx: impl Into<Arg<'n>>) -> &mut Self {
+        let lhs = self.var(lhs);
+        let src = self.var(src);
+        let idx = self.operand(idx.into());
+        self.push(Instruction::Gep { lhs, src, idx })
+    }
+
+    pub fn arith<'n>(
+        &mut self,
+        lhs: &str,
+        aop: ArithOp,
+        op1: impl Into<Arg<'n>>,
+        op2: impl Into<Arg<'n>>,
+    ) -> &mut Self {
+        let lhs = self.var(lhs);
+        let op1 = self.operand(op1.into());
+        let op2 = self.operand(op2.into());
+        self.push(Instruction::Arith { lhs, aop, op1, op2 })
+    }
+
+    pub fn load(&mut self, lhs: &str, src: &str) -> &mut Self {
+        let lhs = self.var(lhs);
+        let src = self.var(src);
+        self.push(Instruction::Load { lhs, src })
+    }
+
+    pub fn store<'n>(&mut self, dst: &str, op: impl Into<Arg<'n>>) -> &mut Self {
+        let dst = self.var(dst);
+        let op = self.operand(op.into());
+        self.push(Instruction::Store { dst, op })
+    }
+
+    // the type of `field` is looked up in the struct `src` points to
+    pub fn gfp(&mut self, lhs: &str, src: &str, field: &str) -> &mut Self {
+        let lhs = self.var(lhs);
+        let src = self.var(src);
+        let field_ty = match &src.typ {
+            Type::Pointer(pointee) => match pointee.as_ref() {
+                Type::Struct(name) => self
+                    .prog
+                    .prog
+                    .structs
+                    .get(name)
+                    .and_then(|fields| fields.iter().find(|f| f.name == field))
+                    .map(|f| f.typ.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let field_ty = field_ty.unwrap_or_else(|| {
+            let label = self.label();
+            self.error(
+                &label,
+                format!(
+                    "`{}` does not point to a struct with field `{}`",
+                    src.name, field
+                ),
+            );
+            Type::Int
+        });
+        let field = Variable {
+            name: field.to_string(),
+            typ: field_ty,
+            scope: None,
+        };
+        self.push(Instruction::Gfp { lhs, src, field })
+    }
+
+    pub fn cmp<'n>(
+        &mut self,
+        lhs: &str,
+        rop: RelaOp,
+        op1: impl Into<Arg<'n>>,
+        op2: impl Into<Arg<'n>>,
+    ) -> &mut Self {
+        let lhs = self.var(lhs);
+        let op1 = self.operand(op1.into());
+        let op2 = self.operand(op2.into());
+        self.push(Instruction::Cmp { lhs, rop, op1, op2 })
+    }
+
+    pub fn call_ext(&mut self, lhs: Option<&str>, callee: &str, args: &[Arg]) -> &mut Self {
+        let lhs = self.lhs(lhs);
+        let args = self.operands(args);
+        self.push(Instruction::CallExt {
+            lhs,
+            ext_callee: callee.to_string(),
+            args,
+        })
+    }
+
+    pub fn jump(&mut self, label: &str) -> &mut Self {
+        self.terminate(Terminal::Jump(label.to_string()))
+    }
+
+    pub fn branch<'n>(&mut self, cond: impl Into<Arg<'n>>, tt: &str, ff: &str) -> &mut Self {
+        let cond = self.operand(cond.into());
+        self.terminate(Terminal::Branch {
+            cond,
+            tt: tt.to_string(),
+            ff: ff.to_string(),
+        })
+    }
+
+    pub fn ret<'n>(&mut self, op: impl Into<Arg<'n>>) -> &mut Self {
+        let op = self.operand(op.into());
+        self.terminate(Terminal::Ret(Some(op)))
+    }
+
+    pub fn ret_void(&mut self) -> &mut Self {
+        self.terminate(Terminal::Ret(None))
+    }
+
+    pub fn call_dir(
+        &mut self,
+        lhs: Option<&str>,
+        callee: &str,
+        args: &[Arg],
+        next_bb: &str,
+    ) -> &mut Self {
+        let lhs = self.lhs(lhs);
+        let args = self.operands(args);
+        self.terminate(Terminal::CallDirect {
+            lhs,
+            callee: callee.to_string(),
+            args,
+            next_bb: next_bb.to_string(),
+        })
+    }
+
+    pub fn call_idr(
+        &mut self,
+        lhs: Option<&str>,
+        callee: &str,
+        args: &[Arg],
+        next_bb: &str,
+    ) -> &mut Self {
+        let lhs = self.lhs(lhs);
+        let callee = self.var(callee);
+        let args = self.operands(args);
+        self.terminate(Terminal::CallIndirect {
+            lhs,
+            callee,
+            args,
+            next_bb: next_bb.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_matches_parser() {
+        let src = concat!(
+            "struct node {\n  next:&node\n  val:int\n}\n\n",
+            "g:int\n\n",
+            "extern print:(int) -> _\n\n",
+            "fn main() -> int {\nentry:\n  $ret 0\n}\n\n",
+            "fn test(p:&node) -> int {\nlet f:&int, l:&&node, n:&node, x:int\n",
+            "bb1:\n  $call_ext print(x)\n  $ret x\n\n",
+            "entry:\n  n = $alloc 1 [_a1]\n  l = $gfp n next\n  $store l p\n",
+            "  f = $gfp p val\n  x = $load f\n  x = $arith add x g\n  $jump bb1\n}\n\n",
+        );
+
+        let node = Type::Struct("node".to_string());
+        let mut pb = ProgramBuilder::new();
+        pb.structure("node", &[("next", node.clone().ptr()), ("val", Type::Int)])
+            .global("g", Type::Int)
+            .external("print", &[Type::Int], None);
+        let mut f = pb.function("test", &[("p", node.clone().ptr())], Some(Type::Int));
+        f.local("x", Type::Int)
+            .local("n", node.clone().ptr())
+            .local("l", node.ptr().ptr())
+            .local("f", Type::Int.ptr());
+        f.block("entry")
+            .alloc("n", 1, "_a1")
+            .gfp("l", "n", "next")
+            .store("l", "p")
+            .gfp("f", "p", "val")
+            .load("x", "f")
+            .arith("x", ArithOp::Add, "x", "g")
+            .jump("bb1");
+        f.block("bb1")
+            .call_ext(None, "print", &["x".into()])
+            .ret("x");
+        f.finish();
+        let prog = pb.finish().unwrap();
+
+        assert_eq!(prog, Program::parse_lir_str(src).unwrap());
+        assert_eq!(prog.to_string(), src);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let mut pb = ProgramBuilder::new();
+        let mut f = pb.function("test", &[("p", Type::Int.ptr())], None);
+        f.local("x", Type::Int);
+        f.block("entry").load("x", "q").copy("x", "p").jump("exit");
+        f.block("exit");
+        f.finish();
+        let diags: Vec<String> = pb
+            .finish()
+            .unwrap_err()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diags,
+            vec![
+                "function `test`, block `entry`: variable `q` is not declared",
+                "function `test`, block `exit`: block has no terminal",
+                "function `test`: no block ends in `$ret`",
+                "function `test`, block `entry`: jump to unknown block `exit`",
+                "function `test`, block `entry`: instruction 0: variable `q` is not declared",
+                "function `test`, block `entry`: instruction 0: `$load` expects a pointer, but `q` is `int`",
+                "function `test`, block `entry`: instruction 1: the operand of `$copy` to `x` should be `int`, found `&int`",
+            ]
+        );
+    }
+}
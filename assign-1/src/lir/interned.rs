@@ -0,0 +1,637 @@
+/*
+Interned, index-based representation of LIR programs.
+
+Every Variable of the program (globals, params, locals, and the `$alloc` ids and `$gfp` fields) is
+stored once in a symbol table and referred to by a dense VarId; functions, basic blocks and externs
+likewise get a FuncId, BlockId and ExternId. Analyses can then key their maps with small integers,
+i.e., use vectors or bitsets instead of hash maps over cloned Strings and Variables.
+
+Variables are interned by name, type and scope, so a program that uses a variable at a type other
+than its declared one still converts back losslessly (Program::to_program). Within a function the
+`entry` block is always BlockId(0), the other blocks follow in the order of their labels.
+*/
+use crate::lir;
+use crate::lir::{ArithOp, Diagnostic, Field, RelaOp, Type, Variable};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VarId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32); // local to a function
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FuncId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExternId(pub u32);
+
+impl VarId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl BlockId {
+    pub const ENTRY: BlockId = BlockId(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl FuncId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl ExternId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Var(VarId),
+    CInt(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    AddrOf {
+        lhs: VarId,
+        rhs: VarId,
+    },
+    Alloc {
+        lhs: VarId,
+        num: Operand,
+        id: VarId,
+    },
+    Copy {
+        lhs: VarId,
+        op: Operand,
+    },
+    Gep {
+        lhs: VarId,
+        src: VarId,
+        idx: Operand,
+    },
+    Arith {
+        lhs: VarId,
+        aop: ArithOp,
+        op1: Operand,
+        op2: Operand,
+    },
+    Load {
+        lhs: VarId,
+        src: VarId,
+    },
+    Store {
+        dst: VarId,
+        op: Operand,
+    },
+    Gfp {
+        lhs: VarId,
+        src: VarId,
+        field: VarId,
+    },
+    Cmp {
+        lhs: VarId,
+        rop: RelaOp,
+        op1: Operand,
+        op2: Operand,
+    },
+    CallExt {
+        lhs: Option<VarId>,
+        ext_callee: ExternId,
+        args: Vec<Operand>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Terminal {
+    Jump(BlockId),
+    Branch {
+        cond: Operand,
+        tt: BlockId,
+        ff: BlockId,
+    },
+    Ret(Option<Operand>),
+    CallDirect {
+        lhs: Option<VarId>,
+        callee: FuncId,
+        args: Vec<Operand>,
+        next_bb: BlockId,
+    },
+    CallIndirect {
+        lhs: Option<VarId>,
+        callee: VarId,
+        args: Vec<Operand>,
+        next_bb: BlockId,
+    },
+}
+
+impl Terminal {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminal::Jump(next) => vec![*next],
+            Terminal::Branch { tt, ff, .. } => vec![*tt, *ff],
+            Terminal::Ret(_) => vec![],
+            Terminal::CallDirect { next_bb, .. } => vec![*next_bb],
+            Terminal::CallIndirect { next_bb, .. } => vec![*next_bb],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub insts: Vec<Instruction>,
+    pub term: Terminal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub ret_ty: Option<Type>,
+    pub params: Vec<VarId>,
+    pub locals: Vec<VarId>,
+    pub labels: Vec<String>, // indexed by BlockId
+    pub blocks: Vec<Block>,  // indexed by BlockId
+}
+
+impl Function {
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.index()]
+    }
+
+    pub fn label(&self, id: BlockId) -> &str {
+        &self.labels[id.index()]
+    }
+
+    pub fn block_id(&self, label: &str) -> Option<BlockId> {
+        self.labels
+            .iter()
+            .position(|l| l == label)
+            .map(|idx| BlockId(idx as u32))
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub structs: HashMap<String, Vec<Field>>,
+    pub vars: Vec<Variable>, // indexed by VarId
+    pub globals: Vec<VarId>,
+    pub externs: Vec<(String, Type)>, // indexed by ExternId
+    pub functions: Vec<Function>,     // indexed by FuncId
+    var_ids: HashMap<Variable, VarId>,
+    func_ids: HashMap<String, FuncId>,
+}
+
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+impl Program {
+    // Jumps and calls must have a target (see lir::Program::validate), otherwise the first one
+    // without is reported.
+    pub fn new(prog: &lir::Program) -> Result<Program, Diagnostic> {
+        let mut interned = Program {
+            structs: prog.structs.clone(),
+            vars: Vec::new(),
+            globals: Vec::new(),
+            externs: Vec::new(),
+            functions: Vec::new(),
+            var_ids: HashMap::new(),
+            func_ids: HashMap::new(),
+        };
+        for global in &prog.globals {
+            let id = interned.intern(global);
+            interned.globals.push(id);
+        }
+        for name in sorted_keys(&prog.externs) {
+            interned
+                .externs
+                .push((name.clone(), prog.externs[name].clone()));
+        }
+        let func_names = sorted_keys(&prog.functions);
+        for (idx, name) in func_names.iter().enumerate() {
+            interned
+                .func_ids
+                .insert(name.to_string(), FuncId(idx as u32));
+        }
+        for name in func_names {
+            let func = interned.function(&prog.functions[name])?;
+            interned.functions.push(func);
+        }
+        Ok(interned)
+    }
+
+    pub fn var(&self, id: VarId) -> &Variable {
+        &self.vars[id.index()]
+    }
+
+    pub fn var_id(&self, var: &Variable) -> Option<VarId> {
+        self.var_ids.get(var).cloned()
+    }
+
+    pub fn func(&self, id: FuncId) -> &Function {
+        &self.functions[id.index()]
+    }
+
+    pub fn func_id(&self, name: &str) -> Option<FuncId> {
+        self.func_ids.get(name).cloned()
+    }
+
+    pub fn extern_id(&self, name: &str) -> Option<ExternId> {
+        self.externs
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|idx| ExternId(idx as u32))
+    }
+
+    fn intern(&mut self, var: &Variable) -> VarId {
+        if let Some(id) = self.var_ids.get(var) {
+            return *id;
+        }
+        let id = VarId(self.vars.len() as u32);
+        self.vars.push(var.clone());
+        self.var_ids.insert(var.clone(), id);
+        id
+    }
+
+    fn operand(&mut self, op: &lir::Operand) -> Operand {
+        match op {
+            lir::Operand::Var(var) => Operand::Var(self.intern(var)),
+            lir::Operand::CInt(n) => Operand::CInt(*n),
+        }
+    }
+
+    fn operands(&mut self, ops: &[lir::Operand]) -> Vec<Operand> {
+        ops.iter().map(|op| self.operand(op)).collect()
+    }
+
+    fn lhs(&mut self, lhs: &Option<Variable>) -> Option<VarId> {
+        lhs.as_ref().map(|var| self.intern(var))
+    }
+
+    fn function(&mut self, func: &lir::Function) -> Result<Function, Diagnostic> {
+        let params = func.params.iter().map(|p| self.intern(p)).collect();
+        let locals = func.locals.iter().map(|l| self.intern(l)).collect();
+        let mut labels: Vec<String> = sorted_keys(&func.body)
+            .into_iter()
+            .filter(|label| *label != "entry")
+            .cloned()
+            .collect();
+        if func.body.contains_key("entry") {
+            labels.insert(0, "entry".to_string());
+        }
+        let block_ids: HashMap<&String, BlockId> = labels
+            .iter()
+            .enumerate()
+            .map(|(idx, label)| (label, BlockId(idx as u32)))
+            .collect();
+
+        let mut blocks = Vec::new();
+        for label in &labels {
+            let block = &func.body[label];
+            let error = |msg: String| Diagnostic {
+                func: Some(func.id.clone()),
+                block: Some(label.clone()),
+                msg,
+            };
+            let target = |label: &String| {
+                block_ids
+                    .get(label)
+                    .cloned()
+                    .ok_or_else(|| error(format!("jump to unknown block `{}`", label)))
+            };
+
+            let mut insts = Vec::new();
+            for inst in &block.insts {
+                insts.push(match inst {
+                    lir::Instruction::AddrOf { lhs, rhs } => Instruction::AddrOf {
+                        lhs: self.intern(lhs),
+                        rhs: self.intern(rhs),
+                    },
+                    lir::Instruction::Alloc { lhs, num, id } => Instruction::Alloc {
+                        lhs: self.intern(lhs),
+                        num: self.operand(num),
+                        id: self.intern(id),
+                    },
+                    lir::Instruction::Copy { lhs, op } => Instruction::Copy {
+                        lhs: self.intern(lhs),
+                        op: self.operand(op),
+                    },
+                    lir::Instruction::Gep { lhs, src, idx } => Instruction::Gep {
+                        lhs: self.intern(lhs),
+                        src: self.intern(src),
+                        idx: self.operand(idx),
+                    },
+                    lir::Instruction::Arith { lhs, aop, op1, op2 } => Instruction::Arith {
+                        lhs: self.intern(lhs),
+                        aop: aop.clone(),
+                        op1: self.operand(op1),
+                        op2: self.operand(op2),
+                    },
+                    lir::Instruction::Load { lhs, src } => Instruction::Load {
+                        lhs: self.intern(lhs),
+                        src: self.intern(src),
+                    },
+                    lir::Instruction::Store { dst, op } => Instruction::Store {
+                        dst: self.intern(dst),
+                        op: self.operand(op),
+                    },
+                    lir::Instruction::Gfp { lhs, src, field } => Instruction::Gfp {
+                        lhs: self.intern(lhs),
+                        src: self.intern(src),
+                        field: self.intern(field),
+                    },
+                    lir::Instruction::Cmp { lhs, rop, op1, op2 } => Instruction::Cmp {
+                        lhs: self.intern(lhs),
+                        rop: rop.clone(),
+                        op1: self.operand(op1),
+                        op2: self.operand(op2),
+                    },
+                    lir::Instruction::CallExt {
+                        lhs,
+                        ext_callee,
+                        args,
+                    } => Instruction::CallExt {
+                        lhs: self.lhs(lhs),
+                        ext_callee: self.extern_id(ext_callee).ok_or_else(|| {
+                            error(format!("`$call_ext` to unknown extern `{}`", ext_callee))
+                        })?,
+                        args: self.operands(args),
+                    },
+                });
+            }
+
+            let term = match &block.term {
+                lir::Terminal::Jump(next) => Terminal::Jump(target(next)?),
+                lir::Terminal::Branch { cond, tt, ff } => Terminal::Branch {
+                    cond: self.operand(cond),
+                    tt: target(tt)?,
+                    ff: target(ff)?,
+                },
+                lir::Terminal::Ret(op) => Terminal::Ret(op.as_ref().map(|op| self.operand(op))),
+                lir::Terminal::CallDirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                } => Terminal::CallDirect {
+                    lhs: self.lhs(lhs),
+                    callee: self.func_id(callee).ok_or_else(|| {
+                        error(format!("`$call_dir` to unknown function `{}`", callee))
+                    })?,
+                    args: self.operands(args),
+                    next_bb: target(next_bb)?,
+                },
+                lir::Terminal::CallIndirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                } => Terminal::CallIndirect {
+                    lhs: self.lhs(lhs),
+                    callee: self.intern(callee),
+                    args: self.operands(args),
+                    next_bb: target(next_bb)?,
+                },
+            };
+            blocks.push(Block { insts, term });
+        }
+
+        Ok(Function {
+            name: func.id.clone(),
+            ret_ty: func.ret_ty.clone(),
+            params,
+            locals,
+            labels,
+            blocks,
+        })
+    }
+
+    pub fn to_program(&self) -> lir::Program {
+        let mut prog = lir::Program::new();
+        prog.structs = self.structs.clone();
+        prog.globals = self
+            .globals
+            .iter()
+            .map(|id| self.var(*id).clone())
+            .collect();
+        prog.externs = self.externs.iter().cloned().collect();
+        for func in &self.functions {
+            let func = self.to_function(func);
+            prog.functions.insert(func.id.clone(), func);
+        }
+        prog
+    }
+
+    fn to_operand(&self, op: &Operand) -> lir::Operand {
+        match op {
+            Operand::Var(id) => lir::Operand::Var(self.var(*id).clone()),
+            Operand::CInt(n) => lir::Operand::CInt(*n),
+        }
+    }
+
+    fn to_operands(&self, ops: &[Operand]) -> Vec<lir::Operand> {
+        ops.iter().map(|op| self.to_operand(op)).collect()
+    }
+
+    fn to_lhs(&self, lhs: &Option<VarId>) -> Option<Variable> {
+        lhs.map(|id| self.var(id).clone())
+    }
+
+    fn to_function(&self, func: &Function) -> lir::Function {
+        let var = |id: &VarId| self.var(*id).clone();
+        let label = |id: &BlockId| func.label(*id).to_string();
+        let mut body = HashMap::new();
+        for (id, block) in func.block_ids().zip(func.blocks.iter()) {
+            let insts = block
+                .insts
+                .iter()
+                .map(|inst| match inst {
+                    Instruction::AddrOf { lhs, rhs } => lir::Instruction::AddrOf {
+                        lhs: var(lhs),
+                        rhs: var(rhs),
+                    },
+                    Instruction::Alloc { lhs, num, id } => lir::Instruction::Alloc {
+                        lhs: var(lhs),
+                        num: self.to_operand(num),
+                        id: var(id),
+                    },
+                    Instruction::Copy { lhs, op } => lir::Instruction::Copy {
+                        lhs: var(lhs),
+                        op: self.to_operand(op),
+                    },
+                    Instruction::Gep { lhs, src, idx } => lir::Instruction::Gep {
+                        lhs: var(lhs),
+                        src: var(src),
+                        idx: self.to_operand(idx),
+                    },
+                    Instruction::Arith { lhs, aop, op1, op2 } => lir::Instruction::Arith {
+                        lhs: var(lhs),
+                        aop: aop.clone(),
+                        op1: self.to_operand(op1),
+                        op2: self.to_operand(op2),
+                    },
+                    Instruction::Load { lhs, src } => lir::Instruction::Load {
+                        lhs: var(lhs),
+                        src: var(src),
+                    },
+                    Instruction::Store { dst, op } => lir::Instruction::Store {
+                        dst: var(dst),
+                        op: self.to_operand(op),
+                    },
+                    Instruction::Gfp { lhs, src, field } => lir::Instruction::Gfp {
+                        lhs: var(lhs),
+                        src: var(src),
+                        field: var(field),
+                    },
+                    Instruction::Cmp { lhs, rop, op1, op2 } => lir::Instruction::Cmp {
+                        lhs: var(lhs),
+                        rop: rop.clone(),
+                        op1: self.to_operand(op1),
+                        op2: self.to_operand(op2),
+                    },
+                    Instruction::CallExt {
+                        lhs,
+                        ext_callee,
+                        args,
+                    } => lir::Instruction::CallExt {
+                        lhs: self.to_lhs(lhs),
+                        ext_callee: self.externs[ext_callee.index()].0.clone(),
+                        args: self.to_operands(args),
+                    },
+                })
+                .collect();
+            let term = match &block.term {
+                Terminal::Jump(next) => lir::Terminal::Jump(label(next)),
+                Terminal::Branch { cond, tt, ff } => lir::Terminal::Branch {
+                    cond: self.to_operand(cond),
+                    tt: label(tt),
+                    ff: label(ff),
+                },
+                Terminal::Ret(op) => lir::Terminal::Ret(op.as_ref().map(|op| self.to_operand(op))),
+                Terminal::CallDirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                } => lir::Terminal::CallDirect {
+                    lhs: self.to_lhs(lhs),
+                    callee: self.func(*callee).name.clone(),
+                    args: self.to_operands(args),
+                    next_bb: label(next_bb),
+                },
+                Terminal::CallIndirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                } => lir::Terminal::CallIndirect {
+                    lhs: self.to_lhs(lhs),
+                    callee: var(callee),
+                    args: self.to_operands(args),
+                    next_bb: label(next_bb),
+                },
+            };
+            let block = lir::Block {
+                id: label(&id),
+                insts,
+                term,
+            };
+            body.insert(block.id.clone(), block);
+        }
+        lir::Function {
+            id: func.name.clone(),
+            ret_ty: func.ret_ty.clone(),
+            params: func.params.iter().map(var).collect(),
+            locals: func.locals.iter().map(var).collect(),
+            body,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for dir in ["./examples/json", "./demos/json", "./tests/json"] {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let path = entry.unwrap().path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let prog = lir::Program::parse_json(path.to_str().unwrap()).unwrap();
+                let interned = Program::new(&prog).unwrap();
+                assert_eq!(interned.to_program(), prog, "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn test_ids() {
+        let src = "g:int\n\n\
+                   fn main() -> int {\nlet x:int\n\
+                   bb1:\n  x = $copy g\n  $ret x\n\n\
+                   entry:\n  $call_dir test(1) then bb1\n}\n\n\
+                   fn test(x:int) -> _ {\n\
+                   entry:\n  g = $copy x\n  $ret \n}\n";
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let interned = Program::new(&prog).unwrap();
+
+        // x of main and x of test are distinct, g is shared
+        assert_eq!(interned.vars.len(), 3);
+        let main = interned.func(interned.func_id("main").unwrap());
+        assert_eq!(main.label(BlockId::ENTRY), "entry");
+        assert_eq!(main.block_id("bb1"), Some(BlockId(1)));
+        assert_eq!(
+            main.block(BlockId::ENTRY).term,
+            Terminal::CallDirect {
+                lhs: None,
+                callee: interned.func_id("test").unwrap(),
+                args: vec![Operand::CInt(1)],
+                next_bb: BlockId(1),
+            }
+        );
+        let g = interned.globals[0];
+        assert_eq!(interned.var(g).name, "g");
+        assert_eq!(
+            main.block(BlockId(1)).insts[0],
+            Instruction::Copy {
+                lhs: main.locals[0],
+                op: Operand::Var(g),
+            }
+        );
+        assert_ne!(main.locals[0], interned.func(FuncId(1)).params[0]);
+
+        let mut bad = prog.clone();
+        bad.functions
+            .get_mut("test")
+            .unwrap()
+            .body
+            .get_mut("entry")
+            .unwrap()
+            .term = lir::Terminal::Jump("exit".to_string());
+        assert_eq!(
+            Program::new(&bad).unwrap_err().to_string(),
+            "function `test`, block `entry`: jump to unknown block `exit`"
+        );
+    }
+}
@@ -0,0 +1,780 @@
+/*
+Concrete interpreter for LIR programs.
+
+Every variable (global, parameter or local of a live frame) and every `$alloc` owns a memory object,
+i.e., a flat array of cells where a struct takes one cell per (flattened) field. A pointer is an
+object id plus a cell offset, which is enough to model `$gep`/`$gfp` arithmetic and to trap on
+out-of-bounds accesses. The constant 0 doubles as the null pointer, as in the front end.
+*/
+use super::*;
+use std::cell::Cell;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    Int(i32),
+    Ptr(Address),
+    Func(String),       // the value of a global named after a function
+    Struct(Vec<Value>), // the flattened cells of a struct value
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    pub obj: usize,    // index into Interpreter::heap
+    pub offset: usize, // cell offset within the object
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectKind {
+    Global(String),
+    Local { func: String, var: String },
+    Heap(String), // the `$alloc` site id
+}
+
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub kind: ObjectKind,
+    pub cells: Vec<Value>,
+    pub written: bool,   // false for a local that has not been assigned yet
+    pub undefined: bool, // whether the value was computed from a local read before it was assigned
+}
+
+// where a trap happened, or where a trace snapshot was taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub func: String,
+    pub block: String,
+    pub index: Option<usize>, // index of the instruction, None for the terminal
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    pub point: Point,
+    pub msg: String,
+}
+
+// the values of all scalar variables in scope right before `point` executes; locals that have not
+// been assigned yet are left out, since the analyses treat them as undefined rather than 0, and so
+// are the variables whose value was computed from such a local
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub point: Point,
+    pub values: HashMap<Variable, Value>,
+    pub diverged: bool, // whether the frame has branched on a value computed from an undefined local
+}
+
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub exit: Option<Value>,
+    pub trace: Vec<TraceEntry>, // empty unless Interpreter::trace is set
+}
+
+pub type ExternFn = Box<dyn FnMut(&[Value]) -> Result<Option<Value>, String>>;
+
+struct Frame {
+    func: String,
+    block: String,
+    pc: usize, // index of the next instruction, insts.len() for the terminal
+    vars: HashMap<String, usize>,
+    ret_lhs: Option<Variable>, // where the caller wants the returned value
+    diverged: bool,
+}
+
+pub struct Interpreter<'a> {
+    pub prog: &'a Program,
+    pub heap: Vec<Object>,
+    pub trace: bool,
+    pub max_steps: usize, // 0 for no limit
+    externs: HashMap<String, ExternFn>,
+    globals: HashMap<String, usize>,
+    stack: Vec<Frame>,
+    steps: usize,
+    read_undefined: Cell<bool>, // whether the current step has read an undefined value
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Ptr(addr) => write!(f, "&obj{}[{}]", addr.obj, addr.offset),
+            Value::Func(name) => write!(f, "&{}", name),
+            Value::Struct(cells) => {
+                let cells: Vec<String> = cells.iter().map(|c| c.to_string()).collect();
+                write!(f, "{{{}}}", cells.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(idx) => write!(f, "{}.{}.{}", self.func, self.block, idx),
+            None => write!(f, "{}.{}.term", self.func, self.block),
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "trap at {}: {}", self.point, self.msg)
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(prog: &'a Program) -> Interpreter<'a> {
+        let mut interp = Interpreter {
+            prog,
+            heap: Vec::new(),
+            trace: false,
+            max_steps: 1_000_000,
+            externs: HashMap::new(),
+            globals: HashMap::new(),
+            stack: Vec::new(),
+            steps: 0,
+            read_undefined: Cell::new(false),
+        };
+        for global in &prog.globals {
+            let obj = interp.new_object(ObjectKind::Global(global.name.clone()), &global.typ, 1);
+            if prog.functions.contains_key(&global.name) {
+                interp.heap[obj].cells[0] = Value::Func(global.name.clone());
+            }
+            interp.globals.insert(global.name.clone(), obj);
+        }
+        interp
+    }
+
+    // Externs without a callback return 0 (or nothing, if they have no return type).
+    pub fn set_extern(&mut self, name: &str, callback: ExternFn) {
+        self.externs.insert(name.to_string(), callback);
+    }
+
+    pub fn run(&mut self) -> Result<Execution, Trap> {
+        self.run_function("main", &[])
+    }
+
+    pub fn run_function(&mut self, func_name: &str, args: &[Value]) -> Result<Execution, Trap> {
+        let mut trace = Vec::new();
+        let func = match self.prog.functions.get(func_name) {
+            Some(func) => func,
+            None => {
+                return Err(Trap {
+                    point: Point {
+                        func: func_name.to_string(),
+                        block: "entry".to_string(),
+                        index: None,
+                    },
+                    msg: format!("function `{}` is not defined", func_name),
+                })
+            }
+        };
+        self.push_frame(func, args, None)
+            .map_err(|msg| self.trap_at(func_name, "entry", None, msg))?;
+
+        loop {
+            if self.trace {
+                trace.push(self.snapshot());
+            }
+            if let Some(exit) = self.step().map_err(|msg| self.trap(msg))? {
+                return Ok(Execution { exit, trace });
+            }
+        }
+    }
+
+    fn new_object(&mut self, kind: ObjectKind, typ: &Type, num: usize) -> usize {
+        let size = self.size_of(typ);
+        let written = !matches!(kind, ObjectKind::Local { .. });
+        self.heap.push(Object {
+            kind,
+            cells: vec![Value::Int(0); size * num],
+            written,
+            undefined: false,
+        });
+        self.heap.len() - 1
+    }
+
+    fn size_of(&self, typ: &Type) -> usize {
+        match typ {
+            Type::Struct(name) => self.prog.structs[name]
+                .iter()
+                .map(|field| self.size_of(&field.typ))
+                .sum(),
+            _ => 1,
+        }
+    }
+
+    fn field_offset(&self, struct_name: &str, field_name: &str) -> Option<usize> {
+        let mut offset = 0;
+        for field in &self.prog.structs[struct_name] {
+            if field.name == field_name {
+                return Some(offset);
+            }
+            offset += self.size_of(&field.typ);
+        }
+        None
+    }
+
+    fn push_frame(
+        &mut self,
+        func: &Function,
+        args: &[Value],
+        ret_lhs: Option<Variable>,
+    ) -> Result<(), String> {
+        if args.len() != func.params.len() {
+            return Err(format!(
+                "`{}` takes {} arguments but {} were given",
+                func.id,
+                func.params.len(),
+                args.len()
+            ));
+        }
+        let mut vars = HashMap::new();
+        for var in func.params.iter().chain(func.locals.iter()) {
+            let kind = ObjectKind::Local {
+                func: func.id.clone(),
+                var: var.name.clone(),
+            };
+            vars.insert(var.name.clone(), self.new_object(kind, &var.typ, 1));
+        }
+        for (param, arg) in func.params.iter().zip(args.iter()) {
+            let addr = Address {
+                obj: vars[&param.name],
+                offset: 0,
+            };
+            self.write_mem(&addr, arg.clone())?;
+        }
+        self.stack.push(Frame {
+            func: func.id.clone(),
+            block: "entry".to_string(),
+            pc: 0,
+            vars,
+            ret_lhs,
+            diverged: false,
+        });
+        Ok(())
+    }
+
+    fn frame(&self) -> &Frame {
+        self.stack.last().unwrap()
+    }
+
+    fn point(&self) -> Point {
+        let frame = self.frame();
+        let block = &self.prog.functions[&frame.func].body[&frame.block];
+        Point {
+            func: frame.func.clone(),
+            block: frame.block.clone(),
+            index: if frame.pc < block.insts.len() {
+                Some(frame.pc)
+            } else {
+                None
+            },
+        }
+    }
+
+    fn trap(&self, msg: String) -> Trap {
+        Trap {
+            point: self.point(),
+            msg,
+        }
+    }
+
+    fn trap_at(&self, func: &str, block: &str, index: Option<usize>, msg: String) -> Trap {
+        Trap {
+            point: Point {
+                func: func.to_string(),
+                block: block.to_string(),
+                index,
+            },
+            msg,
+        }
+    }
+
+    fn snapshot(&self) -> TraceEntry {
+        let frame = self.frame();
+        let func = &self.prog.functions[&frame.func];
+        let mut values = HashMap::new();
+        for var in self
+            .prog
+            .globals
+            .iter()
+            .chain(func.params.iter())
+            .chain(func.locals.iter())
+        {
+            if let Type::Struct(_) = var.typ {
+                continue;
+            }
+            let obj = match var.scope {
+                Some(_) => frame.vars[&var.name],
+                None => self.globals[&var.name],
+            };
+            if !self.heap[obj].written || self.heap[obj].undefined {
+                continue;
+            }
+            values.insert(var.clone(), self.heap[obj].cells[0].clone());
+        }
+        TraceEntry {
+            point: self.point(),
+            values,
+            diverged: frame.diverged,
+        }
+    }
+
+    fn var_object(&self, var: &Variable) -> Result<usize, String> {
+        let obj = match var.scope {
+            Some(_) => self.frame().vars.get(&var.name),
+            None => self.globals.get(&var.name),
+        };
+        obj.cloned()
+            .ok_or(format!("variable `{}` is not declared", var.name))
+    }
+
+    // a value of type `typ` stored at `addr`
+    fn read_mem(&self, addr: &Address, typ: &Type) -> Result<Value, String> {
+        let obj = &self.heap[addr.obj];
+        if !obj.written || obj.undefined {
+            self.read_undefined.set(true);
+        }
+        let cells = &obj.cells;
+        match typ {
+            Type::Struct(_) => {
+                let end = addr.offset + self.size_of(typ);
+                if end > cells.len() {
+                    return Err("struct access out of bounds".to_string());
+                }
+                Ok(Value::Struct(cells[addr.offset..end].to_vec()))
+            }
+            _ => Ok(cells[addr.offset].clone()),
+        }
+    }
+
+    fn write_mem(&mut self, addr: &Address, val: Value) -> Result<(), String> {
+        // the value is undefined if the current step has read an undefined value; an object of
+        // several cells stays undefined once any of them is
+        let obj = &mut self.heap[addr.obj];
+        obj.written = true;
+        obj.undefined = self.read_undefined.get() || (obj.undefined && obj.cells.len() > 1);
+        let cells = &mut obj.cells;
+        match val {
+            Value::Struct(vals) => {
+                if addr.offset + vals.len() > cells.len() {
+                    return Err("struct access out of bounds".to_string());
+                }
+                for (i, val) in vals.into_iter().enumerate() {
+                    cells[addr.offset + i] = val;
+                }
+            }
+            val => cells[addr.offset] = val,
+        }
+        Ok(())
+    }
+
+    fn read_var(&self, var: &Variable) -> Result<Value, String> {
+        let obj = self.var_object(var)?;
+        self.read_mem(&Address { obj, offset: 0 }, &var.typ)
+    }
+
+    fn write_var(&mut self, var: &Variable, val: Value) -> Result<(), String> {
+        let obj = self.var_object(var)?;
+        self.write_mem(&Address { obj, offset: 0 }, val)
+    }
+
+    fn eval(&self, op: &Operand) -> Result<Value, String> {
+        match op {
+            Operand::Var(var) => self.read_var(var),
+            Operand::CInt(n) => Ok(Value::Int(*n)),
+        }
+    }
+
+    fn eval_int(&self, op: &Operand) -> Result<i32, String> {
+        match self.eval(op)? {
+            Value::Int(n) => Ok(n),
+            val => Err(format!("expected an integer, found `{}`", val)),
+        }
+    }
+
+    fn deref(&self, var: &Variable) -> Result<Address, String> {
+        match self.read_var(var)? {
+            Value::Ptr(addr) => {
+                if addr.offset >= self.heap[addr.obj].cells.len() {
+                    return Err(format!("`{}` points out of bounds", var.name));
+                }
+                Ok(addr)
+            }
+            Value::Int(0) => Err(format!("null dereference of `{}`", var.name)),
+            val => Err(format!("`{}` is not a valid pointer: `{}`", var.name, val)),
+        }
+    }
+
+    fn pointee_type(var: &Variable) -> Result<&Type, String> {
+        match &var.typ {
+            Type::Pointer(pointee) => Ok(pointee),
+            typ => Err(format!("`{}` of type `{}` is not a pointer", var.name, typ)),
+        }
+    }
+
+    // execute one instruction or terminal; returns the exit value once the outermost frame returns
+    fn step(&mut self) -> Result<Option<Option<Value>>, String> {
+        self.steps += 1;
+        self.read_undefined.set(false);
+        if self.max_steps > 0 && self.steps > self.max_steps {
+            return Err(format!("step limit of {} exceeded", self.max_steps));
+        }
+        let prog = self.prog;
+        let frame = self.frame();
+        let block = &prog.functions[&frame.func].body[&frame.block];
+        let pc = frame.pc;
+        if pc < block.insts.len() {
+            self.exe_instr(&block.insts[pc])?;
+            self.stack.last_mut().unwrap().pc += 1;
+            Ok(None)
+        } else {
+            self.exe_term(&block.term)
+        }
+    }
+
+    fn exe_instr(&mut self, inst: &Instruction) -> Result<(), String> {
+        match inst {
+            Instruction::AddrOf { lhs, rhs } => {
+                let obj = self.var_object(rhs)?;
+                self.write_var(lhs, Value::Ptr(Address { obj, offset: 0 }))
+            }
+            Instruction::Alloc { lhs, num, id } => {
+                let num = self.eval_int(num)?;
+                if num < 0 {
+                    return Err(format!("negative allocation size {}", num));
+                }
+                let pointee = Self::pointee_type(lhs)?;
+                let obj = self.new_object(ObjectKind::Heap(id.name.clone()), pointee, num as usize);
+                self.write_var(lhs, Value::Ptr(Address { obj, offset: 0 }))
+            }
+            Instruction::Copy { lhs, op } => {
+                let val = self.eval(op)?;
+                self.write_var(lhs, val)
+            }
+            Instruction::Gep { lhs, src, idx } => {
+                let idx = self.eval_int(idx)?;
+                let addr = self.deref(src)?;
+                let size = self.size_of(Self::pointee_type(src)?) as i64;
+                let offset = addr.offset as i64 + idx as i64 * size;
+                if offset < 0 || offset >= self.heap[addr.obj].cells.len() as i64 {
+                    return Err(format!("`$gep` index {} is out of bounds", idx));
+                }
+                let addr = Address {
+                    obj: addr.obj,
+                    offset: offset as usize,
+                };
+                self.write_var(lhs, Value::Ptr(addr))
+            }
+            Instruction::Arith { lhs, aop, op1, op2 } => {
+                let n1 = self.eval_int(op1)?;
+                let n2 = self.eval_int(op2)?;
+                let res = match aop {
+                    ArithOp::Add => n1.wrapping_add(n2),
+                    ArithOp::Subtract => n1.wrapping_sub(n2),
+                    ArithOp::Multiply => n1.wrapping_mul(n2),
+                    ArithOp::Divide => {
+                        if n2 == 0 {
+                            return Err("division by zero".to_string());
+                        }
+                        n1.wrapping_div(n2)
+                    }
+                };
+                self.write_var(lhs, Value::Int(res))
+            }
+            Instruction::Load { lhs, src } => {
+                let addr = self.deref(src)?;
+                let val = self.read_mem(&addr, Self::pointee_type(src)?)?;
+                self.write_var(lhs, val)
+            }
+            Instruction::Store { dst, op } => {
+                let addr = self.deref(dst)?;
+                let val = self.eval(op)?;
+                self.write_mem(&addr, val)
+            }
+            Instruction::Gfp { lhs, src, field } => {
+                let addr = self.deref(src)?;
+                let offset = match Self::pointee_type(src)? {
+                    Type::Struct(name) => self.field_offset(name, &field.name),
+                    _ => None,
+                };
+                let offset =
+                    offset.ok_or(format!("`{}` has no field `{}`", src.name, field.name))?;
+                let addr = Address {
+                    obj: addr.obj,
+                    offset: addr.offset + offset,
+                };
+                self.write_var(lhs, Value::Ptr(addr))
+            }
+            Instruction::Cmp { lhs, rop, op1, op2 } => {
+                let v1 = self.eval(op1)?;
+                let v2 = self.eval(op2)?;
+                let ord = match (&v1, &v2) {
+                    (Value::Int(n1), Value::Int(n2)) => n1.cmp(n2),
+                    (Value::Ptr(a1), Value::Ptr(a2)) => a1.cmp(a2),
+                    // a valid pointer sorts after null
+                    (Value::Ptr(_), Value::Int(_)) => std::cmp::Ordering::Greater,
+                    (Value::Int(_), Value::Ptr(_)) => std::cmp::Ordering::Less,
+                    _ if matches!(rop, RelaOp::Eq | RelaOp::Neq) => {
+                        if v1 == v2 {
+                            std::cmp::Ordering::Equal
+                        } else {
+                            std::cmp::Ordering::Less
+                        }
+                    }
+                    _ => return Err(format!("cannot order `{}` and `{}`", v1, v2)),
+                };
+                let res = match rop {
+                    RelaOp::Eq => ord.is_eq(),
+                    RelaOp::Neq => ord.is_ne(),
+                    RelaOp::Less => ord.is_lt(),
+                    RelaOp::LessEq => ord.is_le(),
+                    RelaOp::Greater => ord.is_gt(),
+                    RelaOp::GreaterEq => ord.is_ge(),
+                };
+                self.write_var(lhs, Value::Int(res as i32))
+            }
+            Instruction::CallExt {
+                lhs,
+                ext_callee,
+                args,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<Value>, String>>()?;
+                let ret = match self.externs.get_mut(ext_callee) {
+                    Some(callback) => callback(&args)
+                        .map_err(|msg| format!("extern `{}` failed: {}", ext_callee, msg))?,
+                    None => match self.prog.externs.get(ext_callee) {
+                        Some(Type::Function(func_ty)) if func_ty.ret_ty.is_some() => {
+                            Some(Value::Int(0))
+                        }
+                        Some(_) => None,
+                        None => return Err(format!("extern `{}` is not declared", ext_callee)),
+                    },
+                };
+                match (lhs, ret) {
+                    (Some(lhs), Some(val)) => self.write_var(lhs, val),
+                    (Some(_), None) => Err(format!("extern `{}` returned nothing", ext_callee)),
+                    (None, _) => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn jump(&mut self, label: &str) -> Result<Option<Option<Value>>, String> {
+        let frame = self.stack.last_mut().unwrap();
+        if !self.prog.functions[&frame.func].body.contains_key(label) {
+            return Err(format!("jump to unknown block `{}`", label));
+        }
+        frame.block = label.to_string();
+        frame.pc = 0;
+        Ok(None)
+    }
+
+    fn call(
+        &mut self,
+        callee: &str,
+        args: &[Operand],
+        lhs: &Option<Variable>,
+        next_bb: &str,
+    ) -> Result<Option<Option<Value>>, String> {
+        let func = self
+            .prog
+            .functions
+            .get(callee)
+            .ok_or(format!("function `{}` is not defined", callee))?;
+        let args = args
+            .iter()
+            .map(|arg| self.eval(arg))
+            .collect::<Result<Vec<Value>, String>>()?;
+        // the caller resumes at next_bb once the callee returns
+        self.jump(next_bb)?;
+        self.push_frame(func, &args, lhs.clone())?;
+        Ok(None)
+    }
+
+    fn exe_term(&mut self, term: &Terminal) -> Result<Option<Option<Value>>, String> {
+        match term {
+            Terminal::Jump(label) => self.jump(label),
+            Terminal::Branch { cond, tt, ff } => {
+                let taken = match self.eval(cond)? {
+                    Value::Int(n) => n != 0,
+                    _ => true,
+                };
+                if self.read_undefined.get() {
+                    self.stack.last_mut().unwrap().diverged = true;
+                }
+                self.jump(if taken { tt } else { ff })
+            }
+            Terminal::Ret(op) => {
+                let val = match op {
+                    Some(op) => Some(self.eval(op)?),
+                    None => None,
+                };
+                let frame = self.stack.pop().unwrap();
+                if self.stack.is_empty() {
+                    return Ok(Some(val));
+                }
+                match (frame.ret_lhs, val) {
+                    (Some(lhs), Some(val)) => self.write_var(&lhs, val)?,
+                    (Some(_), None) => return Err(format!("`{}` returned nothing", frame.func)),
+                    (None, _) => {}
+                }
+                Ok(None)
+            }
+            Terminal::CallDirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => self.call(callee, args, lhs, next_bb),
+            Terminal::CallIndirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => match self.read_var(callee)? {
+                Value::Func(name) => self.call(&name, args, lhs, next_bb),
+                Value::Int(0) => Err(format!("call through null pointer `{}`", callee.name)),
+                val => Err(format!(
+                    "`{}` is not a function pointer: `{}`",
+                    callee.name, val
+                )),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_lir(src: &str) -> Result<Execution, Trap> {
+        let prog = Program::parse_lir_str(src).unwrap();
+        let mut interp = Interpreter::new(&prog);
+        interp.run()
+    }
+
+    #[test]
+    fn test_run_examples() {
+        // every shipped program terminates without trapping
+        for dir in ["./examples/json", "./demos/json", "./tests/json"] {
+            // not every assignment ships all three directories
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry.unwrap().path();
+                let prog = Program::parse_json(path.to_str().unwrap()).unwrap();
+                let mut interp = Interpreter::new(&prog);
+                let exe = interp.run();
+                assert!(exe.is_ok(), "{}: {}", path.display(), exe.unwrap_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_calls_and_heap() {
+        let src = "struct node {\n  next:&node\n  val:int\n}\n\n\
+                   fact:&(int) -> int\n\n\
+                   fn fact(n:int) -> int {\nlet c:int, m:int, r:int\n\
+                   entry:\n  c = $cmp lte n 1\n  $branch c base rec\n\
+                   base:\n  r = $copy 1\n  $jump exit\n\
+                   rec:\n  m = $arith sub n 1\n  r = $call_dir fact(m) then mul\n\
+                   mul:\n  r = $arith mul r n\n  $jump exit\n\
+                   exit:\n  $ret r\n}\n\n\
+                   fn main() -> int {\nlet a:&node, b:&node, f:&(int) -> int, p:&int, q:&&node, x:int, y:int\n\
+                   entry:\n  a = $alloc 3 [_a1]\n  b = $gep a 2\n  p = $gfp b val\n  $store p 4\n\
+                   q = $gfp a next\n  $store q b\n  b = $load q\n  p = $gfp b val\n  x = $load p\n\
+                   f = $copy fact\n  y = $call_idr f(x) then exit\n\
+                   exit:\n  y = $arith add y x\n  $ret y\n}\n";
+        let exe = run_lir(src).unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(28)));
+    }
+
+    #[test]
+    fn test_externs_and_trace() {
+        let src = "extern input:() -> int\nextern print:(int) -> _\n\n\
+                   fn main() -> int {\nlet x:int\n\
+                   entry:\n  x = $call_ext input()\n  $call_ext print(x)\n  $ret x\n}\n";
+        let prog = Program::parse_lir_str(src).unwrap();
+        let printed = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut interp = Interpreter::new(&prog);
+        interp.trace = true;
+        interp.set_extern("input", Box::new(|_| Ok(Some(Value::Int(7)))));
+        let out = printed.clone();
+        interp.set_extern(
+            "print",
+            Box::new(move |args| {
+                out.borrow_mut().push(args[0].clone());
+                Ok(None)
+            }),
+        );
+        let exe = interp.run().unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(7)));
+        assert_eq!(*printed.borrow(), vec![Value::Int(7)]);
+
+        let points: Vec<String> = exe.trace.iter().map(|e| e.point.to_string()).collect();
+        assert_eq!(
+            points,
+            vec!["main.entry.0", "main.entry.1", "main.entry.term"]
+        );
+        let x = &prog.functions["main"].locals[0];
+        assert!(!exe.trace[0].values.contains_key(x));
+        assert!(!exe.trace[2].diverged);
+        assert_eq!(exe.trace[1].values[x], Value::Int(7));
+    }
+
+    #[test]
+    fn test_undefined_values() {
+        // values computed from a local read before it was assigned are left out of the trace, and
+        // the frame diverges once it branches on one
+        let src = "fn main() -> int {\nlet u:int, x:int, y:int\n\
+                   entry:\n  x = $arith add u 1\n  y = $copy 2\n  $jump next\n\
+                   next:\n  $branch x then exit\n\
+                   then:\n  $jump exit\n\
+                   exit:\n  $ret y\n}\n";
+        let prog = Program::parse_lir_str(src).unwrap();
+        let mut interp = Interpreter::new(&prog);
+        interp.trace = true;
+        let exe = interp.run().unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(2)));
+
+        let locals = &prog.functions["main"].locals;
+        let next = exe.trace.iter().find(|e| e.point.block == "next").unwrap();
+        assert!(!next.values.contains_key(&locals[1]));
+        assert_eq!(next.values[&locals[2]], Value::Int(2));
+        assert!(!next.diverged);
+        let then = exe.trace.iter().find(|e| e.point.block == "then").unwrap();
+        assert!(then.diverged);
+    }
+
+    #[test]
+    fn test_traps() {
+        let div = "fn main() -> int {\nlet x:int\nentry:\n  x = $arith div 1 x\n  $ret x\n}\n";
+        let trap = run_lir(div).unwrap_err();
+        assert_eq!(trap.to_string(), "trap at main.entry.0: division by zero");
+
+        let gep = "fn main() -> int {\nlet p:&int, q:&int\n\
+                   entry:\n  p = $alloc 2 [_a1]\n  q = $gep p 2\n  $ret 0\n}\n";
+        let trap = run_lir(gep).unwrap_err();
+        assert_eq!(
+            trap.to_string(),
+            "trap at main.entry.1: `$gep` index 2 is out of bounds"
+        );
+
+        let load = "fn main() -> int {\nlet p:&int, x:int\n\
+                    entry:\n  p = $copy 0\n  x = $load p\n  $ret x\n}\n";
+        let trap = run_lir(load).unwrap_err();
+        assert_eq!(
+            trap.to_string(),
+            "trap at main.entry.1: null dereference of `p`"
+        );
+
+        let spin = "fn main() -> int {\nentry:\n  $jump entry\n}\n";
+        let trap = run_lir(spin).unwrap_err();
+        assert_eq!(trap.msg, "step limit of 1000000 exceeded");
+    }
+}
@@ -0,0 +1,358 @@
+/*
+Type checker for LIR programs.
+
+Every instruction and terminal is checked against the typing rules implied by lir-description.md,
+and every Variable occurrence is checked against its declaration (the analyzers trust Variable.typ
+to tell ints from pointers, so a stale type in hand-written JSON would silently skew them).
+*/
+use super::*;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub func: String,
+    pub block: String,
+    pub index: Option<usize>, // index of the instruction, None for the terminal
+    pub msg: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(idx) => write!(
+                f,
+                "function `{}`, block `{}`, instruction {}: {}",
+                self.func, self.block, idx, self.msg
+            ),
+            None => write!(
+                f,
+                "function `{}`, block `{}`, terminal: {}",
+                self.func, self.block, self.msg
+            ),
+        }
+    }
+}
+
+pub fn check(prog: &Program) -> Vec<TypeError> {
+    let mut func_names: Vec<&String> = prog.functions.keys().collect();
+    func_names.sort();
+
+    let mut errors = Vec::new();
+    for fname in func_names {
+        let func = &prog.functions[fname];
+        let mut labels: Vec<&String> = func.body.keys().collect();
+        labels.sort();
+        for label in labels {
+            let block = &func.body[label];
+            for (idx, inst) in block.insts.iter().enumerate() {
+                let mut checker = Checker::new(prog, func, label, Some(idx));
+                checker.check_inst(inst);
+                errors.append(&mut checker.errors);
+            }
+            let mut checker = Checker::new(prog, func, label, None);
+            checker.check_term(&block.term);
+            errors.append(&mut checker.errors);
+        }
+    }
+    errors
+}
+
+fn fmt_ret_ty(ret_ty: &Option<Type>) -> String {
+    match ret_ty {
+        Some(typ) => typ.to_string(),
+        None => "_".to_string(),
+    }
+}
+
+// Type checking context of a single instruction or terminal.
+struct Checker<'a> {
+    prog: &'a Program,
+    func: &'a Function,
+    block: &'a str,
+    index: Option<usize>,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(
+        prog: &'a Program,
+        func: &'a Function,
+        block: &'a str,
+        index: Option<usize>,
+    ) -> Checker<'a> {
+        Checker {
+            prog,
+            func,
+            block,
+            index,
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, msg: String) {
+        self.errors.push(TypeError {
+            func: self.func.id.clone(),
+            block: self.block.to_string(),
+            index: self.index,
+            msg,
+        });
+    }
+
+    // the type of a variable occurrence, after checking it against its declaration
+    fn var<'v>(&mut self, var: &'v Variable) -> &'v Type {
+        let decl = match &var.scope {
+            Some(scope) if *scope == self.func.id => self
+                .func
+                .locals
+                .iter()
+                .chain(self.func.params.iter())
+                .find(|v| v.name == var.name),
+            Some(scope) => {
+                self.error(format!(
+                    "variable `{}` belongs to function `{}`",
+                    var.name, scope
+                ));
+                return &var.typ;
+            }
+            None => self.prog.globals.iter().find(|v| v.name == var.name),
+        };
+        match decl {
+            Some(decl) if decl.typ != var.typ => self.error(format!(
+                "variable `{}` is used as `{}` but declared as `{}`",
+                var.name, var.typ, decl.typ
+            )),
+            Some(_) => {}
+            None => self.error(format!("variable `{}` is not declared", var.name)),
+        }
+        &var.typ
+    }
+
+    fn operand(&mut self, op: &Operand) -> Type {
+        match op {
+            Operand::Var(var) => self.var(var).clone(),
+            Operand::CInt(_) => Type::Int,
+        }
+    }
+
+    fn expect(&mut self, what: &str, expected: &Type, found: &Type) {
+        if expected != found {
+            self.error(format!(
+                "{} should be `{}`, found `{}`",
+                what, expected, found
+            ));
+        }
+    }
+
+    fn expect_int(&mut self, what: &str, op: &Operand) {
+        let typ = self.operand(op);
+        self.expect(what, &Type::Int, &typ);
+    }
+
+    // like expect, but the constant 0 also stands for the null pointer
+    fn expect_operand(&mut self, what: &str, expected: &Type, op: &Operand) {
+        if let (Type::Pointer(_), Operand::CInt(0)) = (expected, op) {
+            return;
+        }
+        let typ = self.operand(op);
+        self.expect(what, expected, &typ);
+    }
+
+    // the pointee type of `var`, reporting an error if it is not a pointer
+    fn pointee(&mut self, inst: &str, var: &Variable) -> Option<Type> {
+        match self.var(var) {
+            Type::Pointer(pointee) => Some(pointee.as_ref().clone()),
+            typ => {
+                self.error(format!(
+                    "`{}` expects a pointer, but `{}` is `{}`",
+                    inst, var.name, typ
+                ));
+                None
+            }
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        inst: &str,
+        callee: &str,
+        func_ty: &FunctionType,
+        lhs: &Option<Variable>,
+        args: &[Operand],
+    ) {
+        if args.len() != func_ty.param_ty.len() {
+            self.error(format!(
+                "`{}` passes {} arguments to `{}`, which takes {}",
+                inst,
+                args.len(),
+                callee,
+                func_ty.param_ty.len()
+            ));
+        }
+        for (i, (arg, param_ty)) in args.iter().zip(func_ty.param_ty.iter()).enumerate() {
+            self.expect_operand(&format!("argument {} of `{}`", i, callee), param_ty, arg);
+        }
+        match (lhs, &func_ty.ret_ty) {
+            (Some(lhs), Some(ret_ty)) => {
+                let typ = self.var(lhs).clone();
+                self.expect(&format!("`{}`", lhs.name), ret_ty, &typ);
+            }
+            (Some(lhs), None) => self.error(format!(
+                "`{}` returns nothing, but its result is assigned to `{}`",
+                callee, lhs.name
+            )),
+            (None, _) => {}
+        }
+    }
+
+    fn check_inst(&mut self, inst: &Instruction) {
+        match inst {
+            Instruction::AddrOf { lhs, rhs } => {
+                let expected = Type::Pointer(Box::new(self.var(rhs).clone()));
+                let typ = self.var(lhs).clone();
+                self.expect(&format!("`{}`", lhs.name), &expected, &typ);
+            }
+            Instruction::Alloc { lhs, num, id } => {
+                self.expect_int("the number of allocated elements", num);
+                if let Some(pointee) = self.pointee("$alloc", lhs) {
+                    self.expect(&format!("allocation `{}`", id.name), &pointee, &id.typ);
+                }
+            }
+            Instruction::Copy { lhs, op } => {
+                let expected = self.var(lhs).clone();
+                self.expect_operand(
+                    &format!("the operand of `$copy` to `{}`", lhs.name),
+                    &expected,
+                    op,
+                );
+            }
+            Instruction::Gep { lhs, src, idx } => {
+                self.expect_int("the index of `$gep`", idx);
+                if self.pointee("$gep", src).is_some() {
+                    let typ = self.var(lhs).clone();
+                    self.expect(&format!("`{}`", lhs.name), &src.typ, &typ);
+                }
+            }
+            Instruction::Arith { lhs, op1, op2, .. } => {
+                let typ = self.var(lhs).clone();
+                self.expect(&format!("`{}`", lhs.name), &Type::Int, &typ);
+                self.expect_int("the first operand of `$arith`", op1);
+                self.expect_int("the second operand of `$arith`", op2);
+            }
+            Instruction::Load { lhs, src } => {
+                if let Some(pointee) = self.pointee("$load", src) {
+                    let typ = self.var(lhs).clone();
+                    self.expect(&format!("`{}`", lhs.name), &pointee, &typ);
+                }
+            }
+            Instruction::Store { dst, op } => {
+                if let Some(pointee) = self.pointee("$store", dst) {
+                    self.expect_operand("the stored value", &pointee, op);
+                }
+            }
+            Instruction::Gfp { lhs, src, field } => {
+                let struct_name = match self.pointee("$gfp", src) {
+                    Some(Type::Struct(name)) => name,
+                    Some(typ) => {
+                        self.error(format!(
+                            "`$gfp` expects a pointer to a struct, but `{}` points to `{}`",
+                            src.name, typ
+                        ));
+                        return;
+                    }
+                    None => return,
+                };
+                let field_ty = self
+                    .prog
+                    .structs
+                    .get(&struct_name)
+                    .and_then(|fields| fields.iter().find(|f| f.name == field.name))
+                    .map(|f| f.typ.clone());
+                match field_ty {
+                    Some(field_ty) => {
+                        self.expect(&format!("field `{}`", field.name), &field_ty, &field.typ);
+                        let typ = self.var(lhs).clone();
+                        self.expect(
+                            &format!("`{}`", lhs.name),
+                            &Type::Pointer(Box::new(field_ty)),
+                            &typ,
+                        );
+                    }
+                    None => self.error(format!(
+                        "struct `{}` has no field `{}`",
+                        struct_name, field.name
+                    )),
+                }
+            }
+            Instruction::Cmp { lhs, op1, op2, .. } => {
+                let typ = self.var(lhs).clone();
+                self.expect(&format!("`{}`", lhs.name), &Type::Int, &typ);
+                let typ1 = self.operand(op1);
+                let typ2 = self.operand(op2);
+                let null_cmp = matches!((&typ1, op2), (Type::Pointer(_), Operand::CInt(0)))
+                    || matches!((op1, &typ2), (Operand::CInt(0), Type::Pointer(_)));
+                if !null_cmp {
+                    self.expect("the second operand of `$cmp`", &typ1, &typ2);
+                }
+            }
+            Instruction::CallExt {
+                lhs,
+                ext_callee,
+                args,
+            } => match self.prog.externs.get(ext_callee) {
+                Some(Type::Function(func_ty)) => {
+                    self.check_call("$call_ext", ext_callee, func_ty, lhs, args)
+                }
+                Some(typ) => self.error(format!(
+                    "extern `{}` has non-function type `{}`",
+                    ext_callee, typ
+                )),
+                None => self.error(format!("extern `{}` is not declared", ext_callee)),
+            },
+        }
+    }
+
+    fn check_term(&mut self, term: &Terminal) {
+        match term {
+            Terminal::Jump(_) => {}
+            Terminal::Branch { cond, .. } => self.expect_int("the condition of `$branch`", cond),
+            Terminal::Ret(Some(op)) if self.func.ret_ty.is_some() => {
+                let ret_ty = self.func.ret_ty.clone().unwrap();
+                self.expect_operand("the returned value", &ret_ty, op);
+            }
+            Terminal::Ret(op) => {
+                let typ = op.as_ref().map(|op| self.operand(op));
+                if typ != self.func.ret_ty {
+                    self.error(format!(
+                        "function returns `{}`, but `$ret` gives `{}`",
+                        fmt_ret_ty(&self.func.ret_ty),
+                        fmt_ret_ty(&typ)
+                    ));
+                }
+            }
+            Terminal::CallDirect {
+                lhs, callee, args, ..
+            } => match self.prog.functions.get(callee) {
+                Some(func) => {
+                    let func_ty = FunctionType {
+                        ret_ty: func.ret_ty.clone(),
+                        param_ty: func.params.iter().map(|p| p.typ.clone()).collect(),
+                    };
+                    self.check_call("$call_dir", callee, &func_ty, lhs, args);
+                }
+                None => self.error(format!("function `{}` is not defined", callee)),
+            },
+            Terminal::CallIndirect {
+                lhs, callee, args, ..
+            } => match self.pointee("$call_idr", callee) {
+                Some(Type::Function(func_ty)) => {
+                    self.check_call("$call_idr", &callee.name, &func_ty, lhs, args)
+                }
+                Some(typ) => self.error(format!(
+                    "`$call_idr` expects a function pointer, but `{}` points to `{}`",
+                    callee.name, typ
+                )),
+                None => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,683 @@
+/*
+Static single assignment form of LIR functions.
+
+Int and pointer parameters and locals whose address is never taken (`$addrof`) are renamed so that
+every definition gets a fresh version `<name>.<n>`, with phis placed at the iterated dominance
+frontier of the blocks defining each of them. Everything else (globals, structs and address-taken
+variables) is memory and keeps its name. A use that no definition reaches reads the variable itself,
+i.e., its version 0, which is the parameter or the still undefined local.
+
+Out of SSA, the phis of a block become `$copy` instructions at the end of each predecessor ending in
+`$jump`, or in a new block on the edge from any other predecessor (whose terminal may read the
+variables copied to, or define one of their sources when it is a call).
+*/
+use crate::cfg::ControlFlowGraph;
+use crate::dominance::{DominanceFrontiers, DominatorTree};
+use crate::lir;
+use crate::transform::retarget;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phi {
+    pub lhs: lir::Variable,
+    pub args: Vec<(String, lir::Operand)>, // the value from each predecessor, sorted by its label
+}
+
+#[derive(Debug, Clone)]
+pub struct SsaFunction {
+    pub function: lir::Function, // the renamed body, with all versions declared as locals
+    pub phis: HashMap<String, Vec<Phi>>, // phis at the start of each block, evaluated in parallel
+    pub origins: HashMap<lir::Variable, lir::Variable>, // the variable each version is one of
+}
+
+impl SsaFunction {
+    pub fn from_function(prog: &lir::Program, func_name: &str) -> Self {
+        let function = prog.functions.get(func_name).unwrap();
+        let cfg = ControlFlowGraph::from_function(prog, func_name);
+        let dom_tree = DominatorTree::new(&cfg);
+        let frontiers = DominanceFrontiers::new(&cfg, &dom_tree);
+
+        let addr_taken: HashSet<&lir::Variable> = function
+            .body
+            .values()
+            .flat_map(|block| &block.insts)
+            .filter_map(|inst| match inst {
+                lir::Instruction::AddrOf { rhs, .. } => Some(rhs),
+                _ => None,
+            })
+            .collect();
+        let mut def_blocks: HashMap<&lir::Variable, Vec<&str>> = HashMap::new();
+        let mut labels: Vec<&String> = function.body.keys().collect();
+        labels.sort();
+        for label in labels {
+            if dom_tree.is_reachable(label) {
+                for var in block_defs(&function.body[label]) {
+                    def_blocks.entry(var).or_default().push(label);
+                }
+            }
+        }
+
+        // only variables read in some block before being defined there may need phis (semi-pruned
+        // SSA); every variable is defined at entry as well (by the call, or as undefined), and one
+        // that would need a phi at entry, where the initial value comes from no predecessor, is
+        // left as it is
+        let live_across: HashSet<&lir::Variable> = function
+            .body
+            .values()
+            .flat_map(upward_exposed_uses)
+            .collect();
+        let mut renamed = HashSet::new();
+        let mut phi_vars: HashMap<String, Vec<lir::Variable>> = HashMap::new();
+        for var in function.params.iter().chain(&function.locals) {
+            if !matches!(var.typ, lir::Type::Int | lir::Type::Pointer(_))
+                || addr_taken.contains(var)
+            {
+                continue;
+            }
+            if !live_across.contains(var) {
+                renamed.insert(var.clone());
+                continue;
+            }
+            let mut blocks = def_blocks.get(var).cloned().unwrap_or_default();
+            blocks.push(dom_tree.root());
+            let idf = frontiers.iterated(&blocks);
+            if idf.contains(dom_tree.root()) {
+                continue;
+            }
+            for label in idf {
+                phi_vars.entry(label).or_default().push(var.clone());
+            }
+            renamed.insert(var.clone());
+        }
+
+        let mut used: HashSet<String> = function
+            .params
+            .iter()
+            .chain(&function.locals)
+            .chain(&prog.globals)
+            .map(|var| var.name.clone())
+            .collect();
+        used.extend(prog.functions.keys().cloned());
+        let phis = phi_vars
+            .iter()
+            .map(|(label, vars)| {
+                let phis = vars
+                    .iter()
+                    .map(|var| Phi {
+                        lhs: var.clone(),
+                        args: Vec::new(),
+                    })
+                    .collect();
+                (label.clone(), phis)
+            })
+            .collect();
+        let mut renamer = Renamer {
+            cfg: &cfg,
+            dom_tree: &dom_tree,
+            renamed,
+            stacks: HashMap::new(),
+            counters: HashMap::new(),
+            used,
+            body: function.body.clone(),
+            phi_vars,
+            phis,
+            origins: HashMap::new(),
+        };
+        renamer.rename(dom_tree.root());
+
+        let mut phis = renamer.phis;
+        for block_phis in phis.values_mut() {
+            for phi in block_phis.iter_mut() {
+                phi.args.sort_by(|(a, _), (b, _)| a.cmp(b));
+            }
+        }
+        let mut versions: Vec<lir::Variable> = renamer.origins.keys().cloned().collect();
+        versions.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut ssa_function = function.clone();
+        ssa_function.body = renamer.body;
+        ssa_function.locals.extend(versions);
+        Self {
+            function: ssa_function,
+            phis,
+            origins: renamer.origins,
+        }
+    }
+
+    pub fn origin<'a>(&'a self, var: &'a lir::Variable) -> &'a lir::Variable {
+        // the variable a version is one of, or the variable itself if it is not a version
+        self.origins.get(var).unwrap_or(var)
+    }
+
+    pub fn to_function(&self) -> lir::Function {
+        // lower the phis to copies (see the top of this file)
+        let mut function = self.function.clone();
+        let mut used: HashSet<String> = function
+            .params
+            .iter()
+            .chain(&function.locals)
+            .map(|var| var.name.clone())
+            .collect();
+        let mut labels: Vec<&String> = self.phis.keys().collect();
+        labels.sort();
+        for label in labels {
+            let phis = &self.phis[label];
+            let preds: BTreeSet<&String> = phis
+                .iter()
+                .flat_map(|phi| phi.args.iter().map(|(pred, _)| pred))
+                .collect();
+            for pred in preds {
+                let copies = phis
+                    .iter()
+                    .filter_map(|phi| {
+                        let (_, arg) = phi.args.iter().find(|(p, _)| p == pred)?;
+                        Some((phi.lhs.clone(), arg.clone()))
+                    })
+                    .collect();
+                let mut temps = Vec::new();
+                let insts = sequentialize(copies, &mut |var: &lir::Variable| {
+                    let mut n = 1;
+                    while used.contains(&format!("{}.tmp{}", var.name, n)) {
+                        n += 1;
+                    }
+                    let temp = lir::Variable {
+                        name: format!("{}.tmp{}", var.name, n),
+                        typ: var.typ.clone(),
+                        scope: var.scope.clone(),
+                    };
+                    used.insert(temp.name.clone());
+                    temps.push(temp.clone());
+                    temp
+                });
+                function.locals.extend(temps);
+
+                let pred_block = function.body.get_mut(pred).unwrap();
+                if let lir::Terminal::Jump(_) = pred_block.term {
+                    pred_block.insts.extend(insts);
+                    continue;
+                }
+                let mut split_label = format!("split_{}_{}", pred, label);
+                let mut suffix = 1;
+                while function.body.contains_key(&split_label) {
+                    split_label = format!("split_{}_{}_{}", pred, label, suffix);
+                    suffix += 1;
+                }
+                let pred_block = function.body.get_mut(pred).unwrap();
+                retarget(&mut pred_block.term, label, &split_label);
+                let mut split = lir::Block::new(&split_label, &lir::Terminal::Jump(label.clone()));
+                split.insts = insts;
+                function.body.insert(split_label, split);
+            }
+        }
+        function.locals.sort_by(|a, b| a.name.cmp(&b.name));
+        function
+    }
+}
+
+impl fmt::Display for SsaFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // as lir::Function, with the phis of each block before its instructions
+        let decl = |var: &lir::Variable| format!("{}:{}", var.name, var.typ);
+        let params: Vec<String> = self.function.params.iter().map(decl).collect();
+        let ret_ty = match self.function.ret_ty {
+            Some(ref typ) => typ.to_string(),
+            None => "_".to_string(),
+        };
+        writeln!(
+            f,
+            "fn {}({}) -> {} {{",
+            self.function.id,
+            params.join(", "),
+            ret_ty
+        )?;
+        if !self.function.locals.is_empty() {
+            let locals: Vec<String> = self.function.locals.iter().map(decl).collect();
+            writeln!(f, "let {}", locals.join(", "))?;
+        }
+        let mut labels: Vec<&String> = self.function.body.keys().collect();
+        labels.sort();
+        for (i, label) in labels.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}:", label)?;
+            for phi in self.phis.get(label).into_iter().flatten() {
+                let args: Vec<String> = phi
+                    .args
+                    .iter()
+                    .map(|(pred, arg)| format!("{}: {}", pred, arg))
+                    .collect();
+                writeln!(f, "  {} = $phi({})", phi.lhs, args.join(", "))?;
+            }
+            let block = &self.function.body[label];
+            for inst in &block.insts {
+                writeln!(f, "  {}", inst)?;
+            }
+            writeln!(f, "  {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+struct Renamer<'a> {
+    cfg: &'a ControlFlowGraph,
+    dom_tree: &'a DominatorTree,
+    renamed: HashSet<lir::Variable>,
+    stacks: HashMap<lir::Variable, Vec<lir::Variable>>, // the versions in scope, innermost last
+    counters: HashMap<lir::Variable, usize>,
+    used: HashSet<String>, // names of variables the function may refer to
+    body: HashMap<String, lir::Block>,
+    phi_vars: HashMap<String, Vec<lir::Variable>>, // the variable of each phi
+    phis: HashMap<String, Vec<Phi>>,
+    origins: HashMap<lir::Variable, lir::Variable>,
+}
+
+impl Renamer<'_> {
+    fn rename(&mut self, label: &str) {
+        // rename the block and those it immediately dominates, with the versions in scope at its
+        // start on the stacks
+        let mut pushed = Vec::new();
+        let mut phis = self.phis.remove(label).unwrap_or_default();
+        for phi in phis.iter_mut() {
+            self.def_var(&mut phi.lhs, &mut pushed);
+        }
+        if !phis.is_empty() {
+            self.phis.insert(label.to_string(), phis);
+        }
+
+        let mut block = self.body.remove(label).unwrap();
+        for inst in block.insts.iter_mut() {
+            self.rename_inst(inst, &mut pushed);
+        }
+        self.rename_term(&mut block.term, &mut pushed);
+        self.body.insert(label.to_string(), block);
+
+        for succ in self.cfg.get_successor_labels(label) {
+            let Some(vars) = self.phi_vars.get(&succ) else {
+                continue;
+            };
+            let args: Vec<lir::Operand> = vars
+                .iter()
+                .map(|var| lir::Operand::Var(self.current(var)))
+                .collect();
+            for (phi, arg) in self.phis.get_mut(&succ).unwrap().iter_mut().zip(args) {
+                phi.args.push((label.to_string(), arg));
+            }
+        }
+
+        let children: Vec<String> = self.dom_tree.children(label).map(String::from).collect();
+        for child in children {
+            self.rename(&child);
+        }
+        for var in pushed {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+
+    fn rename_inst(&mut self, inst: &mut lir::Instruction, pushed: &mut Vec<lir::Variable>) {
+        // uses first, then the definition
+        match inst {
+            lir::Instruction::AddrOf { lhs, rhs: _ } => self.def_var(lhs, pushed),
+            lir::Instruction::Alloc { lhs, num, id: _ } => {
+                self.use_op(num);
+                self.def_var(lhs, pushed);
+            }
+            lir::Instruction::Copy { lhs, op } => {
+                self.use_op(op);
+                self.def_var(lhs, pushed);
+            }
+            lir::Instruction::Gep { lhs, src, idx } => {
+                self.use_var(src);
+                self.use_op(idx);
+                self.def_var(lhs, pushed);
+            }
+            lir::Instruction::Arith { lhs, op1, op2, .. }
+            | lir::Instruction::Cmp { lhs, op1, op2, .. } => {
+                self.use_op(op1);
+                self.use_op(op2);
+                self.def_var(lhs, pushed);
+            }
+            lir::Instruction::Load { lhs, src } | lir::Instruction::Gfp { lhs, src, .. } => {
+                self.use_var(src);
+                self.def_var(lhs, pushed);
+            }
+            lir::Instruction::Store { dst, op } => {
+                self.use_var(dst);
+                self.use_op(op);
+            }
+            lir::Instruction::CallExt { lhs, args, .. } => {
+                args.iter_mut().for_each(|arg| self.use_op(arg));
+                if let Some(lhs) = lhs {
+                    self.def_var(lhs, pushed);
+                }
+            }
+        }
+    }
+
+    fn rename_term(&mut self, term: &mut lir::Terminal, pushed: &mut Vec<lir::Variable>) {
+        match term {
+            lir::Terminal::Jump(_) | lir::Terminal::Ret(None) => {}
+            lir::Terminal::Branch { cond, .. } => self.use_op(cond),
+            lir::Terminal::Ret(Some(op)) => self.use_op(op),
+            lir::Terminal::CallDirect { lhs, args, .. } => {
+                args.iter_mut().for_each(|arg| self.use_op(arg));
+                if let Some(lhs) = lhs {
+                    self.def_var(lhs, pushed);
+                }
+            }
+            lir::Terminal::CallIndirect {
+                lhs, callee, args, ..
+            } => {
+                self.use_var(callee);
+                args.iter_mut().for_each(|arg| self.use_op(arg));
+                if let Some(lhs) = lhs {
+                    self.def_var(lhs, pushed);
+                }
+            }
+        }
+    }
+
+    fn current(&self, var: &lir::Variable) -> lir::Variable {
+        let version = self.stacks.get(var).and_then(|stack| stack.last());
+        version.unwrap_or(var).clone()
+    }
+
+    fn use_var(&self, var: &mut lir::Variable) {
+        if self.renamed.contains(var) {
+            *var = self.current(var);
+        }
+    }
+
+    fn use_op(&self, op: &mut lir::Operand) {
+        if let lir::Operand::Var(var) = op {
+            self.use_var(var);
+        }
+    }
+
+    fn def_var(&mut self, var: &mut lir::Variable, pushed: &mut Vec<lir::Variable>) {
+        if !self.renamed.contains(var) {
+            return;
+        }
+        let counter = self.counters.entry(var.clone()).or_insert(0);
+        let mut name = var.name.clone();
+        while self.used.contains(&name) {
+            *counter += 1;
+            name = format!("{}.{}", var.name, counter);
+        }
+        self.used.insert(name.clone());
+        let version = lir::Variable {
+            name,
+            typ: var.typ.clone(),
+            scope: var.scope.clone(),
+        };
+        self.origins.insert(version.clone(), var.clone());
+        self.stacks
+            .entry(var.clone())
+            .or_default()
+            .push(version.clone());
+        pushed.push(var.clone());
+        *var = version;
+    }
+}
+
+fn operand_var(op: &lir::Operand) -> Option<&lir::Variable> {
+    match op {
+        lir::Operand::Var(var) => Some(var),
+        lir::Operand::CInt(_) => None,
+    }
+}
+
+pub fn inst_def(inst: &lir::Instruction) -> Option<&lir::Variable> {
+    match inst {
+        lir::Instruction::AddrOf { lhs, .. }
+        | lir::Instruction::Alloc { lhs, .. }
+        | lir::Instruction::Copy { lhs, .. }
+        | lir::Instruction::Gep { lhs, .. }
+        | lir::Instruction::Arith { lhs, .. }
+        | lir::Instruction::Load { lhs, .. }
+        | lir::Instruction::Gfp { lhs, .. }
+        | lir::Instruction::Cmp { lhs, .. } => Some(lhs),
+        lir::Instruction::CallExt { lhs, .. } => lhs.as_ref(),
+        lir::Instruction::Store { .. } => None,
+    }
+}
+
+pub fn inst_uses(inst: &lir::Instruction) -> Vec<&lir::Variable> {
+    // the variables whose values are read, which excludes the operand of `$addrof`
+    let mut vars = Vec::new();
+    match inst {
+        lir::Instruction::AddrOf { .. } => {}
+        lir::Instruction::Alloc { num, .. } => vars.extend(operand_var(num)),
+        lir::Instruction::Copy { op, .. } => vars.extend(operand_var(op)),
+        lir::Instruction::Gep { src, idx, .. } => {
+            vars.push(src);
+            vars.extend(operand_var(idx));
+        }
+        lir::Instruction::Arith { op1, op2, .. } | lir::Instruction::Cmp { op1, op2, .. } => {
+            vars.extend(operand_var(op1));
+            vars.extend(operand_var(op2));
+        }
+        lir::Instruction::Load { src, .. } | lir::Instruction::Gfp { src, .. } => vars.push(src),
+        lir::Instruction::Store { dst, op } => {
+            vars.push(dst);
+            vars.extend(operand_var(op));
+        }
+        lir::Instruction::CallExt { args, .. } => vars.extend(args.iter().filter_map(operand_var)),
+    }
+    vars
+}
+
+pub fn term_def(term: &lir::Terminal) -> Option<&lir::Variable> {
+    match term {
+        lir::Terminal::CallDirect { lhs, .. } | lir::Terminal::CallIndirect { lhs, .. } => {
+            lhs.as_ref()
+        }
+        _ => None,
+    }
+}
+
+pub fn term_uses(term: &lir::Terminal) -> Vec<&lir::Variable> {
+    match term {
+        lir::Terminal::Jump(_) | lir::Terminal::Ret(None) => Vec::new(),
+        lir::Terminal::Branch { cond, .. } => operand_var(cond).into_iter().collect(),
+        lir::Terminal::Ret(Some(op)) => operand_var(op).into_iter().collect(),
+        lir::Terminal::CallDirect { args, .. } => args.iter().filter_map(operand_var).collect(),
+        lir::Terminal::CallIndirect { callee, args, .. } => std::iter::once(callee)
+            .chain(args.iter().filter_map(operand_var))
+            .collect(),
+    }
+}
+
+fn block_defs(block: &lir::Block) -> Vec<&lir::Variable> {
+    let mut defs: Vec<&lir::Variable> = block.insts.iter().filter_map(inst_def).collect();
+    defs.extend(term_def(&block.term));
+    defs
+}
+
+fn upward_exposed_uses(block: &lir::Block) -> Vec<&lir::Variable> {
+    // the variables read in a block before any definition of them in the block
+    let mut defined = HashSet::new();
+    let mut uses = Vec::new();
+    for inst in &block.insts {
+        uses.extend(
+            inst_uses(inst)
+                .into_iter()
+                .filter(|var| !defined.contains(var)),
+        );
+        defined.extend(inst_def(inst));
+    }
+    uses.extend(
+        term_uses(&block.term)
+            .into_iter()
+            .filter(|var| !defined.contains(var)),
+    );
+    uses
+}
+
+fn sequentialize(
+    mut copies: Vec<(lir::Variable, lir::Operand)>,
+    temp: &mut impl FnMut(&lir::Variable) -> lir::Variable,
+) -> Vec<lir::Instruction> {
+    // parallel copies as a sequence of `$copy`, saving a destination in a temporary to break
+    // each cycle of copies
+    copies.retain(|(dst, src)| *src != lir::Operand::Var(dst.clone()));
+    let mut insts = Vec::new();
+    while !copies.is_empty() {
+        let is_read = |var: &lir::Variable, copies: &[(lir::Variable, lir::Operand)]| {
+            copies
+                .iter()
+                .any(|(_, src)| matches!(src, lir::Operand::Var(v) if v == var))
+        };
+        match (0..copies.len()).find(|&i| !is_read(&copies[i].0, &copies)) {
+            Some(i) => {
+                let (lhs, op) = copies.remove(i);
+                insts.push(lir::Instruction::Copy { lhs, op });
+            }
+            None => {
+                let dst = copies[0].0.clone();
+                let saved = temp(&dst);
+                insts.push(lir::Instruction::Copy {
+                    lhs: saved.clone(),
+                    op: lir::Operand::Var(dst.clone()),
+                });
+                for (_, src) in copies.iter_mut() {
+                    if *src == lir::Operand::Var(dst.clone()) {
+                        *src = lir::Operand::Var(saved.clone());
+                    }
+                }
+            }
+        }
+    }
+    insts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lir::interp::{Interpreter, Value};
+    use crate::test_utils::{assert_same_results, test_programs};
+
+    const LOOP_SRC: &str = concat!(
+        "fn test(n:int) -> int {\n",
+        "let c:int, i:int, s:int\n",
+        "entry:\n  i = $copy 0\n  s = $copy 0\n  $jump loop\n\n",
+        "loop:\n  c = $cmp lt i n\n  $branch c body exit\n\n",
+        "body:\n  s = $arith add s i\n  i = $arith add i 1\n  $jump loop\n\n",
+        "exit:\n  $ret s\n}\n",
+    );
+
+    #[test]
+    fn test_ssa_form() {
+        let prog = lir::Program::parse_lir_str(LOOP_SRC).unwrap();
+        let ssa = SsaFunction::from_function(&prog, "test");
+        let expected = concat!(
+            "fn test(n:int) -> int {\n",
+            "let c:int, i:int, s:int, c.1:int, i.1:int, i.2:int, i.3:int, s.1:int, s.2:int, ",
+            "s.3:int\n",
+            "body:\n  s.3 = $arith add s.2 i.2\n  i.3 = $arith add i.2 1\n  $jump loop\n\n",
+            "entry:\n  i.1 = $copy 0\n  s.1 = $copy 0\n  $jump loop\n\n",
+            "exit:\n  $ret s.2\n\n",
+            "loop:\n",
+            "  i.2 = $phi(body: i.3, entry: i.1)\n",
+            "  s.2 = $phi(body: s.3, entry: s.1)\n",
+            "  c.1 = $cmp lt i.2 n\n  $branch c.1 body exit\n",
+            "}\n",
+        );
+        assert_eq!(ssa.to_string(), expected);
+        let version = ssa
+            .function
+            .locals
+            .iter()
+            .find(|v| v.name == "s.3")
+            .unwrap();
+        assert_eq!(ssa.origin(version).name, "s");
+
+        // the copies for the back edge go at the end of body, which ends in a jump
+        let function = ssa.to_function();
+        let body = &function.body["body"];
+        assert_eq!(body.insts.len(), 4);
+        assert_eq!(body.insts[2].to_string(), "i.2 = $copy i.3");
+        assert_eq!(body.insts[3].to_string(), "s.2 = $copy s.3");
+    }
+
+    #[test]
+    fn test_copies_on_new_blocks() {
+        // x is defined by the call, and the branch reads y, so neither block can take copies
+        let src = concat!(
+            "fn f() -> int {\nentry:\n  $ret 1\n}\n\n",
+            "fn main() -> int {\nentry:\n  $ret 0\n}\n\n",
+            "fn test(y:int) -> int {\n",
+            "let x:int\n",
+            "entry:\n  x = $copy 0\n  $branch y call join\n\n",
+            "call:\n  x = $call_dir f() then join\n\n",
+            "join:\n  $ret x\n}\n",
+        );
+        let mut prog = lir::Program::parse_lir_str(src).unwrap();
+        let ssa = SsaFunction::from_function(&prog, "test");
+        assert_eq!(
+            ssa.phis["join"][0].to_owned().args,
+            vec![
+                ("call".to_string(), lir::Operand::Var(var("x.2"))),
+                ("entry".to_string(), lir::Operand::Var(var("x.1"))),
+            ]
+        );
+        let function = ssa.to_function();
+        assert!(function.body.contains_key("split_call_join"));
+        assert!(function.body.contains_key("split_entry_join"));
+        prog.functions.insert("test".to_string(), function);
+        assert!(prog.validate().is_empty());
+        for (arg, exit) in [(0, 0), (1, 1)] {
+            let exe = Interpreter::new(&prog).run_function("test", &[Value::Int(arg)]);
+            assert_eq!(exe.unwrap().exit, Some(Value::Int(exit)));
+        }
+    }
+
+    fn var(name: &str) -> lir::Variable {
+        lir::Variable {
+            name: name.to_string(),
+            typ: lir::Type::Int,
+            scope: Some("test".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_sequentialize() {
+        // a = b, b = a, c = a in parallel
+        let copies = vec![
+            (var("a"), lir::Operand::Var(var("b"))),
+            (var("b"), lir::Operand::Var(var("a"))),
+            (var("c"), lir::Operand::Var(var("a"))),
+        ];
+        let insts = sequentialize(copies, &mut |v: &lir::Variable| {
+            var(&format!("{}.tmp", v.name))
+        });
+        let insts: Vec<String> = insts.iter().map(|inst| inst.to_string()).collect();
+        assert_eq!(
+            insts,
+            vec![
+                "c = $copy a",
+                "a.tmp = $copy a",
+                "a = $copy b",
+                "b = $copy a.tmp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_demos() {
+        // into SSA and back keeps the programs valid and their results the same
+        for (path, prog) in test_programs() {
+            let mut lowered = prog.clone();
+            for func_name in prog.functions.keys() {
+                let ssa = SsaFunction::from_function(&prog, func_name);
+                lowered
+                    .functions
+                    .insert(func_name.clone(), ssa.to_function());
+            }
+            assert_eq!(prog.validate().len(), lowered.validate().len(), "{}", path);
+            let reparsed = lir::Program::parse_lir_str(&lowered.to_string()).unwrap();
+            assert_eq!(reparsed, lowered, "{}", path);
+            assert_same_results(&prog, &lowered, &path);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/*
+Differential-test helpers shared by the tests of the transformations (see ssa and transform): the
+programs they are tested on, and a check that a transformed program runs like the original in the
+interpreter.
+*/
+use crate::lir::interp::{Interpreter, Value};
+use crate::lir::{Program, Type};
+
+// the programs the transformations are tested on (the examples being the pointer- and heap-heavy
+// ones), sorted by path
+pub(crate) fn test_programs() -> Vec<(String, Program)> {
+    let mut paths: Vec<std::path::PathBuf> = ["./demos/json", "./examples/json"]
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let path = path.to_str().unwrap().to_string();
+            let prog = Program::parse_json(&path).unwrap();
+            (path, prog)
+        })
+        .collect()
+}
+
+// every function of `prog` with only int parameters returns the same in `transformed` for a few
+// arguments, or traps in both; a run of the original that exceeds the step limit is inconclusive
+pub(crate) fn assert_same_results(prog: &Program, transformed: &Program, context: &str) {
+    let mut func_names: Vec<&String> = prog.functions.keys().collect();
+    func_names.sort();
+    for func_name in func_names {
+        let function = &prog.functions[func_name];
+        if function.params.iter().any(|p| p.typ != Type::Int) {
+            continue;
+        }
+        for arg in [0, 1, 7] {
+            let args = vec![Value::Int(arg); function.params.len()];
+            let mut interp = Interpreter::new(prog);
+            interp.max_steps = 100_000;
+            let expected = interp.run_function(func_name, &args);
+            if matches!(&expected, Err(trap) if trap.msg.starts_with("step limit")) {
+                continue;
+            }
+            let actual = Interpreter::new(transformed).run_function(func_name, &args);
+            let call = format!("{}: {}({})", context, func_name, arg);
+            match (expected, actual) {
+                (Ok(expected), Ok(actual)) => match (&expected.exit, &actual.exit) {
+                    (Some(Value::Int(a)), Some(Value::Int(b))) => assert_eq!(a, b, "{}", call),
+                    (a, b) => assert_eq!(a.is_some(), b.is_some(), "{}", call),
+                },
+                (Err(_), Err(_)) => {}
+                (Ok(_), Err(trap)) => panic!("{}: {}", call, trap),
+                (Err(trap), Ok(_)) => panic!("{}: only the original traps: {}", call, trap),
+            }
+        }
+    }
+}
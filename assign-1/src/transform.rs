@@ -0,0 +1,1109 @@
+/*
+Transformations of LIR functions, which keep them valid LIR.
+
+simplify_cfg cleans up the CFG, and replace_constants, decide_branches and eliminate_dead_code apply
+the results of the constant and interval analyses, in that order in optimize (which the optimizer
+binary runs). inline_calls splices the callees of direct calls into a function, within a budget, to
+be analyzed or optimized after.
+*/
+use crate::abs::execution::{AbstractExecution, Analyzer};
+use crate::abs::semantics::AbstractSemantics;
+use crate::callgraph::CallGraph;
+use crate::lir;
+use crate::ssa::{inst_def, inst_uses, term_def, term_uses};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub fn simplify_cfg(function: &lir::Function) -> (lir::Function, HashMap<String, String>) {
+    // simplify the CFG of a function until none of the following applies:
+    // 1. fold `$branch` on a constant, or to the same block twice, into `$jump`
+    // 2. delete the blocks unreachable from entry
+    // 3. thread the jumps to an empty block ending in `$jump` to where it jumps
+    // 4. merge a block ending in `$jump` with the block it jumps to if it is its only predecessor
+    // entry stays the entry and the `$ret` block keeps its label, and the mapping takes each label
+    // of the original function to the block now holding its instructions (deleted unreachable
+    // blocks have none, and a threaded empty block maps to where it jumped)
+    let mut function = function.clone();
+    let mut labels: HashMap<String, String> = function
+        .body
+        .keys()
+        .map(|label| (label.clone(), label.clone()))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = fold_branches(&mut function);
+        changed |= delete_unreachable(&mut function, &mut labels);
+        changed |= thread_jumps(&mut function, &mut labels);
+        changed |= merge_blocks(&mut function, &mut labels);
+    }
+    (function, labels)
+}
+
+fn fold_branches(function: &mut lir::Function) -> bool {
+    // a branch is only folded if the block it keeps may still reach the `$ret` block without
+    // going through the branch again, so that every block still does
+    let mut changed = false;
+    let mut labels: Vec<String> = function.body.keys().cloned().collect();
+    labels.sort();
+    for label in labels {
+        let lir::Terminal::Branch {
+            ref cond,
+            ref tt,
+            ref ff,
+        } = function.body[&label].term
+        else {
+            continue;
+        };
+        let target = match cond {
+            _ if tt == ff => tt.clone(),
+            lir::Operand::CInt(0) => ff.clone(),
+            lir::Operand::CInt(_) => tt.clone(),
+            lir::Operand::Var(_) => continue,
+        };
+        if !reaches_ret(function, &target, &label) {
+            continue;
+        }
+        function.body.get_mut(&label).unwrap().term = lir::Terminal::Jump(target);
+        changed = true;
+    }
+    changed
+}
+
+fn delete_unreachable(function: &mut lir::Function, labels: &mut HashMap<String, String>) -> bool {
+    let mut reachable: HashSet<String> = HashSet::new();
+    let mut queue = VecDeque::from(["entry".to_string()]);
+    while let Some(label) = queue.pop_front() {
+        let Some(block) = function.body.get(&label) else {
+            continue;
+        };
+        if reachable.insert(label) {
+            queue.extend(successors(&block.term).into_iter().cloned());
+        }
+    }
+    let unreachable: Vec<String> = function
+        .body
+        .iter()
+        .filter(|(label, block)| {
+            !reachable.contains(*label) && !matches!(block.term, lir::Terminal::Ret(_))
+        })
+        .map(|(label, _)| label.clone())
+        .collect();
+    for label in &unreachable {
+        function.body.remove(label);
+        labels.retain(|_, new| new != label);
+    }
+    !unreachable.is_empty()
+}
+
+fn thread_jumps(function: &mut lir::Function, labels: &mut HashMap<String, String>) -> bool {
+    let mut changed = false;
+    let mut empty: Vec<String> = function
+        .body
+        .iter()
+        .filter(|(label, block)| {
+            *label != "entry"
+                && block.insts.is_empty()
+                && matches!(block.term, lir::Terminal::Jump(ref next) if next != *label)
+        })
+        .map(|(label, _)| label.clone())
+        .collect();
+    empty.sort();
+    for label in empty {
+        // an earlier threading may have made it jump to itself
+        let lir::Terminal::Jump(ref next) = function.body[&label].term else {
+            continue;
+        };
+        if *next == label {
+            continue;
+        }
+        let next = next.clone();
+        for block in function.body.values_mut() {
+            retarget(&mut block.term, &label, &next);
+        }
+        function.body.remove(&label);
+        rename(labels, &label, &next);
+        changed = true;
+    }
+    changed
+}
+
+fn merge_blocks(function: &mut lir::Function, labels: &mut HashMap<String, String>) -> bool {
+    let mut changed = false;
+    let mut candidates: Vec<String> = function.body.keys().cloned().collect();
+    candidates.sort();
+    for label in candidates {
+        let Some(block) = function.body.get(&label) else {
+            continue;
+        };
+        let lir::Terminal::Jump(ref next) = block.term else {
+            continue;
+        };
+        let next = next.clone();
+        let Some(succ) = function.body.get(&next) else {
+            continue;
+        };
+        let ends_in_ret = matches!(succ.term, lir::Terminal::Ret(_));
+        if next == label || next == "entry" || (label == "entry" && ends_in_ret) {
+            continue;
+        }
+        let pred_count: usize = function
+            .body
+            .values()
+            .map(|block| {
+                successors(&block.term)
+                    .into_iter()
+                    .filter(|succ| **succ == next)
+                    .count()
+            })
+            .sum();
+        if pred_count != 1 {
+            continue;
+        }
+
+        let succ = function.body.remove(&next).unwrap();
+        let mut merged = function.body.remove(&label).unwrap();
+        merged.insts.extend(succ.insts);
+        merged.term = succ.term;
+        if ends_in_ret {
+            // the block keeps the label of the `$ret` block instead
+            for block in function.body.values_mut() {
+                retarget(&mut block.term, &label, &next);
+            }
+            merged.id = next.clone();
+            function.body.insert(next.clone(), merged);
+            rename(labels, &label, &next);
+        } else {
+            function.body.insert(label.clone(), merged);
+            rename(labels, &next, &label);
+        }
+        changed = true;
+    }
+    changed
+}
+
+fn reaches_ret(function: &lir::Function, from: &str, avoiding: &str) -> bool {
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(label) = queue.pop_front() {
+        if label == avoiding || !visited.insert(label) {
+            continue;
+        }
+        let Some(block) = function.body.get(label) else {
+            continue;
+        };
+        if let lir::Terminal::Ret(_) = block.term {
+            return true;
+        }
+        queue.extend(successors(&block.term).into_iter().map(String::as_str));
+    }
+    false
+}
+
+fn rename(labels: &mut HashMap<String, String>, from: &str, to: &str) {
+    for new in labels.values_mut() {
+        if new == from {
+            *new = to.to_string();
+        }
+    }
+}
+
+pub fn replace_constants<F>(function: &mut lir::Function, constant: F) -> usize
+where
+    F: Fn(&str, usize, &lir::Variable) -> Option<i32>,
+{
+    // replace each int variable read with a literal where `constant` knows its value, given the
+    // block and the index of the instruction reading it (that of the terminal is the number of
+    // instructions), and return the number of operands replaced
+    let mut replaced = 0;
+    for (label, block) in function.body.iter_mut() {
+        let inst_count = block.insts.len();
+        let term_ops = term_operands_mut(&mut block.term);
+        let ops = block
+            .insts
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(i, inst)| inst_operands_mut(inst).into_iter().map(move |op| (i, op)))
+            .chain(term_ops.into_iter().map(|op| (inst_count, op)));
+        for (i, op) in ops {
+            let lir::Operand::Var(ref var) = op else {
+                continue;
+            };
+            if var.typ != lir::Type::Int {
+                continue;
+            }
+            if let Some(c) = constant(label, i, var) {
+                *op = lir::Operand::CInt(c);
+                replaced += 1;
+            }
+        }
+    }
+    replaced
+}
+
+pub fn decide_branches(
+    function: &mut lir::Function,
+    reachable_successors: &HashMap<String, Vec<String>>,
+) -> usize {
+    // make the condition of each `$branch` an analysis found to go one way only a literal, for
+    // simplify_cfg to fold, and return the number of branches decided
+    let mut decided = 0;
+    for (label, block) in function.body.iter_mut() {
+        let lir::Terminal::Branch {
+            ref mut cond,
+            ref tt,
+            ref ff,
+        } = block.term
+        else {
+            continue;
+        };
+        let Some([succ]) = reachable_successors.get(label).map(Vec::as_slice) else {
+            continue;
+        };
+        if tt == ff || matches!(cond, lir::Operand::CInt(_)) {
+            continue;
+        }
+        *cond = lir::Operand::CInt((succ == tt) as i32);
+        decided += 1;
+    }
+    decided
+}
+
+pub fn eliminate_dead_code(function: &mut lir::Function) -> usize {
+    // remove the instructions without side effects defining a local not live after them, until
+    // there are none, and return the number removed; globals and variables whose address is taken
+    // may be read through pointers or by callees, so they are always live, and instructions that
+    // may trap are kept
+    let addr_taken: HashSet<lir::Variable> = function
+        .body
+        .values()
+        .flat_map(|block| &block.insts)
+        .filter_map(|inst| match inst {
+            lir::Instruction::AddrOf { rhs, .. } => Some(rhs.clone()),
+            _ => None,
+        })
+        .collect();
+    let tracked: HashSet<lir::Variable> = function
+        .params
+        .iter()
+        .chain(&function.locals)
+        .filter(|var| !addr_taken.contains(*var))
+        .cloned()
+        .collect();
+    let mut removed = 0;
+    loop {
+        let live_out = live_out(function);
+        let mut changed = false;
+        for (label, block) in function.body.iter_mut() {
+            let mut live = live_out[label].clone();
+            if let Some(def) = term_def(&block.term) {
+                live.remove(def);
+            }
+            live.extend(term_uses(&block.term).into_iter().cloned());
+            let mut kept = Vec::new();
+            for inst in block.insts.drain(..).rev() {
+                if let Some(def) = inst_def(&inst) {
+                    let is_pure =
+                        !matches!(inst, lir::Instruction::CallExt { .. }) && !may_trap(&inst);
+                    if is_pure && tracked.contains(def) && !live.contains(def) {
+                        removed += 1;
+                        changed = true;
+                        continue;
+                    }
+                    live.remove(def);
+                }
+                live.extend(inst_uses(&inst).into_iter().cloned());
+                kept.push(inst);
+            }
+            kept.reverse();
+            block.insts = kept;
+        }
+        if !changed {
+            return removed;
+        }
+    }
+}
+
+fn may_trap(inst: &lir::Instruction) -> bool {
+    // whether an instruction may trap when executed (see lir::interp), e.g., by dividing by zero
+    // or dereferencing null
+    match inst {
+        lir::Instruction::Arith {
+            aop: lir::ArithOp::Divide,
+            op2,
+            ..
+        } => !matches!(op2, lir::Operand::CInt(n) if *n != 0),
+        lir::Instruction::Alloc { num, .. } => !matches!(num, lir::Operand::CInt(n) if *n >= 0),
+        lir::Instruction::Gep { .. }
+        | lir::Instruction::Gfp { .. }
+        | lir::Instruction::Load { .. } => true,
+        _ => false,
+    }
+}
+
+fn live_out(function: &lir::Function) -> HashMap<String, HashSet<lir::Variable>> {
+    // the variables live at the end of each block, by the usual backward fixpoint
+    let mut live_in: HashMap<&String, HashSet<&lir::Variable>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (label, block) in &function.body {
+            let mut live = block_live_out(function, label, &live_in);
+            if let Some(def) = term_def(&block.term) {
+                live.remove(def);
+            }
+            live.extend(term_uses(&block.term));
+            for inst in block.insts.iter().rev() {
+                if let Some(def) = inst_def(inst) {
+                    live.remove(def);
+                }
+                live.extend(inst_uses(inst));
+            }
+            if live_in.get(label) != Some(&live) {
+                live_in.insert(label, live);
+                changed = true;
+            }
+        }
+    }
+    function
+        .body
+        .keys()
+        .map(|label| {
+            let live = block_live_out(function, label, &live_in);
+            (label.clone(), live.into_iter().cloned().collect())
+        })
+        .collect()
+}
+
+fn block_live_out<'a>(
+    function: &'a lir::Function,
+    label: &String,
+    live_in: &HashMap<&String, HashSet<&'a lir::Variable>>,
+) -> HashSet<&'a lir::Variable> {
+    let mut live = HashSet::new();
+    for succ in successors(&function.body[label].term) {
+        live.extend(live_in.get(succ).into_iter().flatten());
+    }
+    live
+}
+
+pub fn optimize<T>(
+    analyzer: &Analyzer<T>,
+    func_name: &str,
+    constant: impl Fn(&T) -> Option<i32>,
+) -> (lir::Function, usize, usize, usize)
+where
+    T: Clone + PartialEq + AbstractSemantics,
+    Analyzer<T>: AbstractExecution,
+{
+    // the function optimized with the results of an analysis that has run, where `constant` gives
+    // the integer a value stands for (if only one), along with the number of uses replaced by
+    // literals, of branches decided and of `$branch`es on a literal left in the function, which
+    // simplify_cfg does not fold when the other target is the only way to the `$ret` block
+    let stores = analyzer.stores_before_instrs();
+    let mut function = analyzer.prog.functions[func_name].clone();
+    let replaced = replace_constants(&mut function, |label, i, var| {
+        constant(stores.get(label)?[i].get(var)?)
+    });
+    let decided = decide_branches(&mut function, &analyzer.reachable_successors);
+    eliminate_dead_code(&mut function);
+    let (function, _) = simplify_cfg(&function);
+    let kept = function
+        .body
+        .values()
+        .filter(|block| match &block.term {
+            lir::Terminal::Branch { cond, tt, ff } => {
+                matches!(cond, lir::Operand::CInt(_)) && tt != ff
+            }
+            _ => false,
+        })
+        .count();
+    (function, replaced, decided, kept)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InlineOptions {
+    // inline calls at most this deep, where the calls of the function itself are at depth 1 and
+    // those spliced in with a callee at depth 2, and so on
+    pub max_depth: usize,
+    // inline only callees with at most this many instructions
+    pub max_size: usize,
+}
+
+impl Default for InlineOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            max_size: 50,
+        }
+    }
+}
+
+pub fn inline_calls(
+    prog: &lir::Program,
+    func_name: &str,
+    options: &InlineOptions,
+) -> (lir::Function, usize) {
+    // splice the callees of the `$call_dir`s of a function into it, along with the number of calls
+    // inlined; callees in a recursive SCC, and calls beyond the budget, are left alone
+    // the blocks of an inlined callee are labeled `{callee}.{k}.{label}` and its parameters and
+    // locals are renamed alike, as are its allocation ids but for a `{caller}.` in front since they
+    // are unique in the whole program, where k is the first instance that clashes with no name of
+    // the program; the locals are reset to 0 (as in a new frame) and the parameters are bound by
+    // `$copy`s at the end of the calling block, and the `$ret` block of the callee copies the return
+    // value to the lhs and jumps to the next block; callees with struct locals, which cannot be
+    // reset by a `$copy`, are left alone too
+    let recursive = CallGraph::new(prog).recursive_functions();
+    let mut function = prog.functions[func_name].clone();
+    let mut used: HashSet<String> = prog.globals.iter().map(|var| var.name.clone()).collect();
+    used.extend(prog.functions.keys().cloned());
+    used.extend(prog.externs.keys().cloned());
+    used.extend(function.params.iter().map(|var| var.name.clone()));
+    used.extend(function.locals.iter().map(|var| var.name.clone()));
+    used.extend(function.body.keys().cloned());
+    for callee_fn in prog.functions.values() {
+        used.extend(alloc_ids(callee_fn).into_iter().map(|id| id.name));
+    }
+
+    let mut labels: Vec<&String> = function.body.keys().collect();
+    labels.sort();
+    let mut worklist: VecDeque<(String, usize)> =
+        labels.into_iter().map(|label| (label.clone(), 1)).collect();
+    let mut inlined = 0;
+    while let Some((label, depth)) = worklist.pop_front() {
+        let lir::Terminal::CallDirect {
+            lhs,
+            callee,
+            args,
+            next_bb,
+        } = function.body[&label].term.clone()
+        else {
+            continue;
+        };
+        let Some(callee_fn) = prog.functions.get(&callee) else {
+            continue;
+        };
+        let size: usize = callee_fn.body.values().map(|block| block.insts.len()).sum();
+        if depth > options.max_depth
+            || size > options.max_size
+            || recursive.contains(&callee)
+            || callee_fn.params.len() != args.len()
+            || callee_fn
+                .locals
+                .iter()
+                .any(|var| matches!(var.typ, lir::Type::Struct(_)))
+        {
+            continue;
+        }
+
+        let ids = alloc_ids(callee_fn);
+        let mut names: Vec<&String> = callee_fn.params.iter().map(|var| &var.name).collect();
+        names.extend(callee_fn.locals.iter().map(|var| &var.name));
+        names.extend(callee_fn.body.keys());
+        let renamed_names = |prefix: &str| -> Vec<String> {
+            let mut renamed: Vec<String> = names
+                .iter()
+                .map(|name| format!("{}{}", prefix, name))
+                .collect();
+            renamed.extend(
+                ids.iter()
+                    .map(|id| format!("{}.{}{}", func_name, prefix, id.name)),
+            );
+            renamed
+        };
+        let prefix = (1..)
+            .map(|k| format!("{}.{}.", callee, k))
+            .find(|prefix| {
+                renamed_names(prefix)
+                    .iter()
+                    .all(|name| !used.contains(name))
+            })
+            .unwrap();
+        used.extend(renamed_names(&prefix));
+
+        let renamed = |var: &lir::Variable, prefix: &str, scope: Option<String>| lir::Variable {
+            name: format!("{}{}", prefix, var.name),
+            typ: var.typ.clone(),
+            scope,
+        };
+        let vars: HashMap<lir::Variable, lir::Variable> = callee_fn
+            .params
+            .iter()
+            .chain(&callee_fn.locals)
+            .map(|var| {
+                (
+                    var.clone(),
+                    renamed(var, &prefix, Some(func_name.to_string())),
+                )
+            })
+            .collect();
+        let id_prefix = format!("{}.{}", func_name, prefix);
+        let ids: HashMap<lir::Variable, lir::Variable> = ids
+            .iter()
+            .map(|id| (id.clone(), renamed(id, &id_prefix, None)))
+            .collect();
+        function.locals.extend(vars.values().cloned());
+
+        let block = function.body.get_mut(&label).unwrap();
+        for local in &callee_fn.locals {
+            block.insts.push(lir::Instruction::Copy {
+                lhs: vars[local].clone(),
+                op: lir::Operand::CInt(0),
+            });
+        }
+        for (param, arg) in callee_fn.params.iter().zip(args) {
+            block.insts.push(lir::Instruction::Copy {
+                lhs: vars[param].clone(),
+                op: arg,
+            });
+        }
+        block.term = lir::Terminal::Jump(format!("{}entry", prefix));
+
+        let mut callee_labels: Vec<&String> = callee_fn.body.keys().collect();
+        callee_labels.sort();
+        for callee_label in callee_labels {
+            let mut block = callee_fn.body[callee_label].clone();
+            block.id = format!("{}{}", prefix, callee_label);
+            for inst in block.insts.iter_mut() {
+                for var in inst_vars_mut(inst) {
+                    if let Some(new) = vars.get(var) {
+                        *var = new.clone();
+                    }
+                }
+                if let lir::Instruction::Alloc { id, .. } = inst {
+                    *id = ids[id].clone();
+                }
+            }
+            for var in term_vars_mut(&mut block.term) {
+                if let Some(new) = vars.get(var) {
+                    *var = new.clone();
+                }
+            }
+            match &mut block.term {
+                lir::Terminal::Ret(op) => {
+                    if let (Some(lhs), Some(op)) = (&lhs, op.take()) {
+                        block.insts.push(lir::Instruction::Copy {
+                            lhs: lhs.clone(),
+                            op,
+                        });
+                    }
+                    block.term = lir::Terminal::Jump(next_bb.clone());
+                }
+                lir::Terminal::Jump(next)
+                | lir::Terminal::CallDirect { next_bb: next, .. }
+                | lir::Terminal::CallIndirect { next_bb: next, .. } => {
+                    *next = format!("{}{}", prefix, next);
+                }
+                lir::Terminal::Branch { tt, ff, .. } => {
+                    *tt = format!("{}{}", prefix, tt);
+                    *ff = format!("{}{}", prefix, ff);
+                }
+            }
+            worklist.push_back((block.id.clone(), depth + 1));
+            function.body.insert(block.id.clone(), block);
+        }
+        inlined += 1;
+    }
+    function.locals.sort_by(|a, b| a.name.cmp(&b.name));
+    (function, inlined)
+}
+
+fn alloc_ids(function: &lir::Function) -> Vec<lir::Variable> {
+    // the allocation ids of a function, in the order of its labels
+    let mut labels: Vec<&String> = function.body.keys().collect();
+    labels.sort();
+    let mut ids = Vec::new();
+    for label in labels {
+        for inst in &function.body[label].insts {
+            if let lir::Instruction::Alloc { id, .. } = inst {
+                ids.push(id.clone());
+            }
+        }
+    }
+    ids
+}
+
+fn inst_vars_mut(inst: &mut lir::Instruction) -> Vec<&mut lir::Variable> {
+    // the variables an instruction reads or writes, but for allocation ids and fields
+    let (lhs, src, operands): (Option<&mut lir::Variable>, _, Vec<&mut lir::Operand>) = match inst {
+        lir::Instruction::AddrOf { lhs, rhs: src }
+        | lir::Instruction::Load { lhs, src }
+        | lir::Instruction::Gfp { lhs, src, .. } => (Some(lhs), Some(src), vec![]),
+        lir::Instruction::Gep { lhs, src, idx } => (Some(lhs), Some(src), vec![idx]),
+        lir::Instruction::Store { dst, op } => (None, Some(dst), vec![op]),
+        lir::Instruction::Alloc { lhs, num: op, .. } | lir::Instruction::Copy { lhs, op } => {
+            (Some(lhs), None, vec![op])
+        }
+        lir::Instruction::Arith { lhs, op1, op2, .. }
+        | lir::Instruction::Cmp { lhs, op1, op2, .. } => (Some(lhs), None, vec![op1, op2]),
+        lir::Instruction::CallExt { lhs, args, .. } => {
+            (lhs.as_mut(), None, args.iter_mut().collect())
+        }
+    };
+    lhs.into_iter()
+        .chain(src)
+        .chain(operands.into_iter().filter_map(|op| match op {
+            lir::Operand::Var(var) => Some(var),
+            lir::Operand::CInt(_) => None,
+        }))
+        .collect()
+}
+
+fn term_vars_mut(term: &mut lir::Terminal) -> Vec<&mut lir::Variable> {
+    // the variables a terminal reads or writes
+    let (lhs, callee, operands): (Option<&mut lir::Variable>, _, Vec<&mut lir::Operand>) =
+        match term {
+            lir::Terminal::Branch { cond, .. } => (None, None, vec![cond]),
+            lir::Terminal::Ret(op) => (None, None, op.iter_mut().collect()),
+            lir::Terminal::CallDirect { lhs, args, .. } => {
+                (lhs.as_mut(), None, args.iter_mut().collect())
+            }
+            lir::Terminal::CallIndirect {
+                lhs, callee, args, ..
+            } => (lhs.as_mut(), Some(callee), args.iter_mut().collect()),
+            lir::Terminal::Jump(_) => (None, None, vec![]),
+        };
+    lhs.into_iter()
+        .chain(callee)
+        .chain(operands.into_iter().filter_map(|op| match op {
+            lir::Operand::Var(var) => Some(var),
+            lir::Operand::CInt(_) => None,
+        }))
+        .collect()
+}
+
+fn inst_operands_mut(inst: &mut lir::Instruction) -> Vec<&mut lir::Operand> {
+    // the operands an instruction reads
+    match inst {
+        lir::Instruction::Alloc { num: op, .. }
+        | lir::Instruction::Copy { op, .. }
+        | lir::Instruction::Gep { idx: op, .. }
+        | lir::Instruction::Store { op, .. } => vec![op],
+        lir::Instruction::Arith { op1, op2, .. } | lir::Instruction::Cmp { op1, op2, .. } => {
+            vec![op1, op2]
+        }
+        lir::Instruction::CallExt { args, .. } => args.iter_mut().collect(),
+        lir::Instruction::AddrOf { .. }
+        | lir::Instruction::Load { .. }
+        | lir::Instruction::Gfp { .. } => vec![],
+    }
+}
+
+fn term_operands_mut(term: &mut lir::Terminal) -> Vec<&mut lir::Operand> {
+    match term {
+        lir::Terminal::Branch { cond, .. } => vec![cond],
+        lir::Terminal::Ret(Some(op)) => vec![op],
+        lir::Terminal::CallDirect { args, .. } | lir::Terminal::CallIndirect { args, .. } => {
+            args.iter_mut().collect()
+        }
+        lir::Terminal::Jump(_) | lir::Terminal::Ret(None) => vec![],
+    }
+}
+
+pub(crate) fn successors(term: &lir::Terminal) -> Vec<&String> {
+    match term {
+        lir::Terminal::Jump(next)
+        | lir::Terminal::CallDirect { next_bb: next, .. }
+        | lir::Terminal::CallIndirect { next_bb: next, .. } => vec![next],
+        lir::Terminal::Branch { tt, ff, .. } => vec![tt, ff],
+        lir::Terminal::Ret(_) => vec![],
+    }
+}
+
+pub(crate) fn retarget(term: &mut lir::Terminal, from: &str, to: &str) {
+    // make a terminal go to `to` wherever it goes to `from`
+    let retarget = |target: &mut String| {
+        if target == from {
+            *target = to.to_string();
+        }
+    };
+    match term {
+        lir::Terminal::Jump(next)
+        | lir::Terminal::CallDirect { next_bb: next, .. }
+        | lir::Terminal::CallIndirect { next_bb: next, .. } => retarget(next),
+        lir::Terminal::Branch { tt, ff, .. } => {
+            retarget(tt);
+            retarget(ff);
+        }
+        lir::Terminal::Ret(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::abs::domain;
+    use crate::abs::execution::{ConstantAnalyzer, IntervalAnalyzer};
+    use crate::lir::interp::{Interpreter, Value};
+    use crate::test_utils::{assert_same_results, test_programs};
+
+    #[test]
+    fn test_simplify_cfg() {
+        let src = concat!(
+            "fn main() -> int {\n",
+            "let a:int, b:int\n",
+            "entry:\n  a = $copy 1\n  $jump bb1\n\n",
+            "bb1:\n  $jump bb2\n\n",
+            "bb2:\n  b = $arith add a 1\n  $branch 1 bb3 bb4\n\n",
+            "bb3:\n  a = $arith mul b 2\n  $jump exit\n\n",
+            "bb4:\n  a = $copy 0\n  $jump exit\n\n",
+            "bb5:\n  $jump bb3\n\n",
+            "exit:\n  $ret a\n}\n",
+        );
+        let mut prog = lir::Program::parse_lir_str(src).unwrap();
+        let (function, labels) = simplify_cfg(&prog.functions["main"]);
+        let expected = concat!(
+            "fn main() -> int {\n",
+            "let a:int, b:int\n",
+            "entry:\n",
+            "  a = $copy 1\n  b = $arith add a 1\n  a = $arith mul b 2\n  $jump exit\n\n",
+            "exit:\n  $ret a\n}\n",
+        );
+        assert_eq!(function.to_string(), expected);
+        for label in ["entry", "bb1", "bb2", "bb3"] {
+            assert_eq!(labels[label], "entry");
+        }
+        assert_eq!(labels["exit"], "exit");
+        assert!(!labels.contains_key("bb4") && !labels.contains_key("bb5"));
+
+        prog.functions.insert("main".to_string(), function);
+        assert!(prog.validate().is_empty());
+        let exe = Interpreter::new(&prog).run().unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(4)));
+    }
+
+    #[test]
+    fn test_keep_ret_reachable() {
+        // folding the branch in loop would leave it looping forever, away from the `$ret`
+        let src = concat!(
+            "fn main() -> int {\n",
+            "let x:int\n",
+            "entry:\n  x = $copy 0\n  $branch x loop body\n\n",
+            "loop:\n  $branch 1 loop body\n\n",
+            "body:\n  x = $copy 2\n  $jump exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let mut prog = lir::Program::parse_lir_str(src).unwrap();
+        let (function, labels) = simplify_cfg(&prog.functions["main"]);
+        let expected = concat!(
+            "fn main() -> int {\n",
+            "let x:int\n",
+            "entry:\n  x = $copy 0\n  $branch x loop exit\n\n",
+            "exit:\n  x = $copy 2\n  $ret x\n\n",
+            "loop:\n  $branch 1 loop exit\n}\n",
+        );
+        assert_eq!(function.to_string(), expected);
+        assert_eq!(labels["body"], "exit");
+        prog.functions.insert("main".to_string(), function);
+        assert!(prog.validate().is_empty());
+    }
+
+    #[test]
+    fn test_simplify_demos() {
+        // the simplified programs are as valid, no larger, and compute the same results
+        for (path, prog) in test_programs() {
+            let mut simplified = prog.clone();
+            for (func_name, function) in &prog.functions {
+                let (new_function, labels) = simplify_cfg(function);
+                assert!(new_function.body.len() <= function.body.len());
+                for new in labels.values() {
+                    assert!(new_function.body.contains_key(new), "{}", path);
+                }
+                simplified.functions.insert(func_name.clone(), new_function);
+            }
+            assert!(
+                simplified.validate().len() <= prog.validate().len(),
+                "{}",
+                path
+            );
+            assert_same_results(&prog, &simplified, &path);
+        }
+    }
+
+    #[test]
+    fn test_eliminate_dead_code() {
+        // t and u are only read by dead instructions, g is a global, and q may divide by zero
+        let src = concat!(
+            "g:int\n\n",
+            "fn main() -> int {\n",
+            "let a:int, d:int, p:&int, q:int, t:int, u:int, x:int\n",
+            "entry:\n",
+            "  t = $copy 1\n  u = $arith add t 2\n  x = $arith mul u u\n  x = $copy 3\n",
+            "  g = $copy 4\n  p = $addrof a\n  a = $copy 5\n",
+            "  d = $arith div 6 2\n  q = $arith div 6 g\n  $jump exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let mut function = prog.functions["main"].clone();
+        assert_eq!(eliminate_dead_code(&mut function), 5);
+        let insts: Vec<String> = function.body["entry"]
+            .insts
+            .iter()
+            .map(|inst| inst.to_string())
+            .collect();
+        assert_eq!(
+            insts,
+            vec![
+                "x = $copy 3",
+                "g = $copy 4",
+                "a = $copy 5",
+                "q = $arith div 6 g"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_constants() {
+        let src = concat!(
+            "fn main() -> int {\n",
+            "let c:int, x:int\n",
+            "entry:\n  x = $copy 2\n  c = $cmp gt x 1\n  $branch c then exit\n\n",
+            "then:\n  x = $arith add x 1\n  $jump exit\n\n",
+            "exit:\n  $ret x\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let mut analyzer = ConstantAnalyzer::new(prog.clone(), "main");
+        analyzer.mfp();
+        let stores = analyzer.stores_before_instrs();
+        let mut function = prog.functions["main"].clone();
+        let replaced = replace_constants(&mut function, |label, i, var| {
+            match stores.get(label)?[i].get(var)? {
+                domain::Constant::CInt(c) => Some(*c),
+                _ => None,
+            }
+        });
+        // the branch only goes to then, so every use is replaced, and the definitions are dead
+        assert_eq!(replaced, 4);
+        assert_eq!(
+            decide_branches(&mut function, &analyzer.reachable_successors),
+            0
+        );
+        assert_eq!(
+            function.body["entry"].term.to_string(),
+            "$branch 1 then exit"
+        );
+        assert_eq!(eliminate_dead_code(&mut function), 3);
+        let (function, _) = simplify_cfg(&function);
+        let expected = concat!(
+            "fn main() -> int {\n",
+            "let c:int, x:int\n",
+            "entry:\n  $jump exit\n\n",
+            "exit:\n  $ret 3\n}\n",
+        );
+        assert_eq!(function.to_string(), expected);
+    }
+
+    #[test]
+    fn test_optimize_keeps_branches() {
+        // x is 1, so the branch always loops, and folding it would cut off the `$ret` block
+        let src = concat!(
+            "fn main() -> int {\n",
+            "let x:int\n",
+            "entry:\n  x = $copy 1\n  $branch x loop exit\n\n",
+            "loop:\n  $jump loop\n\n",
+            "exit:\n  $ret 0\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let mut analyzer = ConstantAnalyzer::new(prog, "main");
+        analyzer.mfp();
+        let (function, replaced, decided, kept) = optimize(&analyzer, "main", |val| match val {
+            domain::Constant::CInt(c) => Some(*c),
+            _ => None,
+        });
+        assert_eq!((replaced, decided, kept), (1, 0, 1));
+        assert_eq!(
+            function.body["entry"].term.to_string(),
+            "$branch 1 loop exit"
+        );
+    }
+
+    #[test]
+    fn test_optimize_demos() {
+        // the functions optimized with either analysis are as valid and compute the same results
+        for (path, prog) in test_programs() {
+            for func_name in prog.functions.keys() {
+                let mut constants = ConstantAnalyzer::new(prog.clone(), func_name);
+                constants.mfp();
+                let (by_constants, ..) = optimize(&constants, func_name, |val| match val {
+                    domain::Constant::CInt(c) => Some(*c),
+                    _ => None,
+                });
+                let mut intervals = IntervalAnalyzer::new(prog.clone(), func_name);
+                intervals.mfp();
+                let (by_intervals, ..) = optimize(&intervals, func_name, |val| match val {
+                    domain::Interval::Range(
+                        domain::Number::Integer(l),
+                        domain::Number::Integer(u),
+                    ) if l == u => Some(*l),
+                    _ => None,
+                });
+                for optimized in [by_constants, by_intervals] {
+                    let mut optimized_prog = prog.clone();
+                    optimized_prog
+                        .functions
+                        .insert(func_name.clone(), optimized);
+                    let context = format!("{} {}", path, func_name);
+                    assert!(
+                        optimized_prog.validate().len() <= prog.validate().len(),
+                        "{}",
+                        context
+                    );
+                    assert_same_results(&prog, &optimized_prog, &context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_inline_calls() {
+        let src = concat!(
+            "fn inc(x:int) -> int {\n",
+            "let y:int\n",
+            "entry:\n  y = $arith add x 1\n  $ret y\n}\n\n",
+            "fn main() -> int {\n",
+            "let a:int, y:int\n",
+            "entry:\n  y = $copy 5\n  a = $call_dir inc(y) then exit\n\n",
+            "exit:\n  a = $arith add a y\n  $ret a\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let (function, inlined) = inline_calls(&prog, "main", &InlineOptions::default());
+        assert_eq!(inlined, 1);
+        let expected = concat!(
+            "fn main() -> int {\n",
+            "let a:int, inc.1.x:int, inc.1.y:int, y:int\n",
+            "entry:\n  y = $copy 5\n  inc.1.y = $copy 0\n  inc.1.x = $copy y\n",
+            "  $jump inc.1.entry\n\n",
+            "exit:\n  a = $arith add a y\n  $ret a\n\n",
+            "inc.1.entry:\n  inc.1.y = $arith add inc.1.x 1\n  a = $copy inc.1.y\n  $jump exit\n}\n",
+        );
+        assert_eq!(function.to_string(), expected);
+
+        let mut inlined_prog = prog.clone();
+        inlined_prog.functions.insert("main".to_string(), function);
+        assert!(inlined_prog.validate().is_empty());
+        let exe = Interpreter::new(&inlined_prog).run().unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(11)));
+
+        // the function can now be analyzed as a whole
+        let mut analyzer = ConstantAnalyzer::new(inlined_prog, "main");
+        analyzer.mfp();
+        let stores = analyzer.stores_before_instrs();
+        let a = lir::Variable {
+            name: "a".to_string(),
+            typ: lir::Type::Int,
+            scope: Some("main".to_string()),
+        };
+        assert_eq!(
+            stores["exit"].last().unwrap().get(&a),
+            Some(&domain::Constant::CInt(11))
+        );
+    }
+
+    #[test]
+    fn test_inline_budget() {
+        let src = concat!(
+            "fn fact(n:int) -> int {\n",
+            "let c:int, r:int\n",
+            "entry:\n  r = $copy 1\n  c = $cmp gt n 1\n  $branch c rec exit\n\n",
+            "rec:\n  r = $arith sub n 1\n  r = $call_dir fact(r) then mul\n\n",
+            "mul:\n  r = $arith mul r n\n  $jump exit\n\n",
+            "exit:\n  $ret r\n}\n\n",
+            "fn g(n:int) -> int {\n",
+            "let r:int\n",
+            "entry:\n  n = $arith add n 0\n  r = $call_dir h(n) then exit\n\n",
+            "exit:\n  $ret r\n}\n\n",
+            "fn h(n:int) -> int {\n",
+            "entry:\n  $ret n\n}\n\n",
+            "fn main() -> int {\n",
+            "let a:int, b:int\n",
+            "entry:\n  a = $call_dir fact(4) then next\n\n",
+            "next:\n  b = $call_dir g(a) then exit\n\n",
+            "exit:\n  a = $arith add a b\n  $ret a\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let budgets = [
+            (InlineOptions::default(), 2),
+            (
+                InlineOptions {
+                    max_depth: 1,
+                    ..Default::default()
+                },
+                1,
+            ),
+            (
+                InlineOptions {
+                    max_size: 0,
+                    ..Default::default()
+                },
+                0,
+            ),
+        ];
+        for (options, expected) in budgets {
+            let (function, inlined) = inline_calls(&prog, "main", &options);
+            // fact is recursive, so only g and, within the depth and size, h are inlined
+            assert_eq!(inlined, expected, "{:?}", options);
+            assert!(function.body.contains_key("entry"));
+            assert_eq!(
+                function.body["entry"].term.to_string(),
+                "a = $call_dir fact(4) then next"
+            );
+            let mut inlined_prog = prog.clone();
+            inlined_prog.functions.insert("main".to_string(), function);
+            assert!(inlined_prog.validate().is_empty());
+            let exe = Interpreter::new(&inlined_prog).run().unwrap();
+            assert_eq!(exe.exit, Some(Value::Int(48)));
+        }
+    }
+
+    #[test]
+    fn test_inline_resets_locals() {
+        // an inlined callee reads its locals as 0 on every call, as a new frame would
+        let src = concat!(
+            "fn bump() -> int {\n",
+            "let j:int\n",
+            "entry:\n  j = $arith add j 1\n  $ret j\n}\n\n",
+            "fn main() -> int {\n",
+            "let c:int, k:int, r:int, s:int\n",
+            "entry:\n  $jump head\n\n",
+            "head:\n  c = $cmp lt k 2\n  $branch c call exit\n\n",
+            "call:\n  r = $call_dir bump() then add\n\n",
+            "add:\n  s = $arith add s r\n  k = $arith add k 1\n  $jump head\n\n",
+            "exit:\n  $ret s\n}\n",
+        );
+        let prog = lir::Program::parse_lir_str(src).unwrap();
+        let (function, inlined) = inline_calls(&prog, "main", &InlineOptions::default());
+        assert_eq!(inlined, 1);
+        let mut inlined_prog = prog.clone();
+        inlined_prog.functions.insert("main".to_string(), function);
+        let exe = Interpreter::new(&inlined_prog).run().unwrap();
+        assert_eq!(exe.exit, Some(Value::Int(2)));
+
+        // grow reads a local before assigning it, and is called in the loop of insert
+        let prog = lir::Program::parse_json("./examples/json/heap_array_based.json").unwrap();
+        let (function, inlined) = inline_calls(&prog, "main", &InlineOptions::default());
+        assert!(inlined > 0);
+        let mut inlined_prog = prog.clone();
+        inlined_prog.functions.insert("main".to_string(), function);
+        assert_same_results(&prog, &inlined_prog, "heap_array_based");
+    }
+
+    #[test]
+    fn test_inline_demos() {
+        // the inlined programs are as valid and compute the same results
+        let options = InlineOptions {
+            max_depth: 3,
+            max_size: 100,
+        };
+        for (path, prog) in test_programs() {
+            let mut inlined = prog.clone();
+            for func_name in prog.functions.keys() {
+                let (function, _) = inline_calls(&prog, func_name, &options);
+                inlined.functions.insert(func_name.clone(), function);
+            }
+            assert!(
+                inlined.validate().len() <= prog.validate().len(),
+                "{}",
+                path
+            );
+            assert_same_results(&prog, &inlined, &path);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/*
+Check the constant and interval analyses against concrete executions of the demos and examples.
+*/
+use cs260::abs::execution::{ConstantAnalyzer, IntervalAnalyzer, Strategy};
+use cs260::abs::oracle;
+use cs260::lir;
+use cs260::lir::interp::{Interpreter, TraceEntry, Value};
+use std::path::PathBuf;
+
+// arguments for the int parameters of the demos' `test` functions, small enough for no demo to
+// overflow i32 (the analyses do not model wrap-around)
+const ARGS: [i32; 6] = [-7, -1, 0, 1, 2, 10];
+
+fn json_files(dir: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn arg_lists(arity: usize) -> Vec<Vec<Value>> {
+    let mut lists = vec![vec![]];
+    for _ in 0..arity {
+        lists = lists
+            .into_iter()
+            .flat_map(|list| {
+                ARGS.iter().map(move |n| {
+                    let mut list = list.clone();
+                    list.push(Value::Int(*n));
+                    list
+                })
+            })
+            .collect();
+    }
+    lists
+}
+
+// report every unsound block/variable pair of both analyses of `func_name`, with both strategies
+fn check(prog: &lir::Program, func_name: &str, trace: &[TraceEntry], errors: &mut Vec<String>) {
+    for strategy in [Strategy::Worklist, Strategy::Recursive] {
+        let mut analyzer = ConstantAnalyzer::new(prog.clone(), func_name);
+        analyzer.strategy = strategy;
+        for unsound in oracle::check(analyzer, func_name, trace) {
+            errors.push(format!(
+                "{} (constant, {:?}): {}",
+                func_name, strategy, unsound
+            ));
+        }
+        let mut analyzer = IntervalAnalyzer::new(prog.clone(), func_name);
+        analyzer.strategy = strategy;
+        for unsound in oracle::check(analyzer, func_name, trace) {
+            errors.push(format!(
+                "{} (interval, {:?}): {}",
+                func_name, strategy, unsound
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_demos_sound() {
+    let mut errors = Vec::new();
+    for path in json_files("./demos/json") {
+        let prog = lir::Program::parse_json(path.to_str().unwrap()).unwrap();
+        let mut trace = Vec::new();
+        for args in arg_lists(prog.functions["test"].params.len()) {
+            let mut interp = Interpreter::new(&prog);
+            interp.trace = true;
+            interp.max_steps = 10_000;
+            // runs that trap (e.g., divide by zero or unbounded recursion) are not checked
+            if let Ok(exe) = interp.run_function("test", &args) {
+                trace.extend(exe.trace);
+            }
+        }
+        let mut prog_errors = Vec::new();
+        check(&prog, "test", &trace, &mut prog_errors);
+        errors.extend(
+            prog_errors
+                .into_iter()
+                .map(|err| format!("{}: {}", path.display(), err)),
+        );
+    }
+    assert!(errors.is_empty(), "unsound:\n{}", errors.join("\n"));
+}
+
+#[test]
+fn test_examples_sound() {
+    let mut errors = Vec::new();
+    for path in json_files("./examples/json") {
+        let prog = lir::Program::parse_json(path.to_str().unwrap()).unwrap();
+        let mut interp = Interpreter::new(&prog);
+        interp.trace = true;
+        let exe = interp.run().unwrap();
+        let mut func_names: Vec<&String> = prog.functions.keys().collect();
+        func_names.sort();
+        let mut prog_errors = Vec::new();
+        for func_name in func_names {
+            check(&prog, func_name, &exe.trace, &mut prog_errors);
+        }
+        errors.extend(
+            prog_errors
+                .into_iter()
+                .map(|err| format!("{}: {}", path.display(), err)),
+        );
+    }
+    assert!(errors.is_empty(), "unsound:\n{}", errors.join("\n"));
+}
+
+#[test]
+fn test_oracle_reports() {
+    // a value outside the abstract one is reported, and so is a variable the store has no value for
+    let src = "fn main() -> int {\nlet x:int, y:int\n\
+               entry:\n  x = $copy 1\n  $jump exit\n\
+               exit:\n  $ret x\n}\n";
+    let prog = lir::Program::parse_lir_str(src).unwrap();
+    let mut interp = Interpreter::new(&prog);
+    interp.trace = true;
+    let mut trace = interp.run().unwrap().trace;
+    let analyzer = ConstantAnalyzer::new(prog.clone(), "main");
+    assert!(oracle::check(analyzer, "main", &trace).is_empty());
+
+    let locals = &prog.functions["main"].locals;
+    let entry = trace.iter_mut().find(|e| e.point.block == "exit").unwrap();
+    entry.values.insert(locals[0].clone(), Value::Int(2));
+    entry.values.insert(locals[1].clone(), Value::Int(3));
+    let z = lir::Variable {
+        name: "z".to_string(),
+        typ: lir::Type::Int,
+        scope: Some("main".to_string()),
+    };
+    entry.values.insert(z, Value::Int(4));
+    let analyzer = ConstantAnalyzer::new(prog.clone(), "main");
+    let unsound: Vec<String> = oracle::check(analyzer, "main", &trace)
+        .iter()
+        .map(|unsound| unsound.to_string())
+        .collect();
+    assert_eq!(
+        unsound,
+        vec![
+            "block `exit`: `x` is 2, which is not in 1",
+            "block `exit`: `y` is 3, which is not in ⊥",
+            "block `exit`: `z` is 4, but the analysis has no value for it",
+        ]
+    );
+}
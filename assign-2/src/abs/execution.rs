@@ -1,6 +1,7 @@
 use super::domain;
 use super::semantics::AbstractSemantics;
 use crate::cfg;
+use crate::dominance;
 use crate::hashset;
 use crate::lir;
 use crate::lir::Instruction;
@@ -40,7 +41,8 @@ pub struct ReachingDefinitionAnalyzer {
 pub struct ControlDependenceAnalyzer {
     pub prog: lir::Program,
     pub cfg: cfg::ControlFlowGraph,
-    pub solution: HashMap<String, HashSet<String>>, // mapping from blocks to block sets
+    pub solution: HashMap<String, HashSet<String>>, // mapping from blocks to their dominance frontiers
+    pub deps: HashMap<String, Vec<(String, bool)>>, // mapping from blocks to the branches (and outcomes) they depend on
     pub executed: bool,
 }
 
@@ -790,8 +792,10 @@ impl AbstractExecution for ReachingDefinitionAnalyzer {
         }
         */
 
+        let mut queued: HashSet<String> = self.worklist.iter().map(|b| b.id.clone()).collect();
         while !self.worklist.is_empty() {
             let block = self.worklist.pop_front().unwrap();
+            queued.remove(&block.id);
             let store_before = self.bb2store.get(&block.id).unwrap().clone();
             if &block != self.cfg.get_entry().unwrap() {
                 // join ann predecessors' stores
@@ -806,7 +810,7 @@ impl AbstractExecution for ReachingDefinitionAnalyzer {
             if store_before != store_after || visited.get(&block.id).unwrap() == &0 {
                 // add all successors to worklist
                 for succ in self.cfg.get_successors(&block) {
-                    if !self.worklist.contains(&succ) {
+                    if queued.insert(succ.id.clone()) {
                         // TODO: 这两行代码是否不需要？
                         self.worklist.push_back(succ.clone());
                     }
@@ -883,6 +887,7 @@ impl ControlDependenceAnalyzer {
             prog,
             cfg,
             solution,
+            deps: HashMap::new(),
             executed: false,
         }
     }
@@ -906,22 +911,13 @@ impl ControlDependenceAnalyzer {
             return frontiers
 
         */
-        let dominators = self.cfg.get_dominators(false);
-        for (label, doms) in &dominators {
-            let strict_doms = doms
-                .clone()
-                .difference(&hashset! {label.clone()})
-                .cloned()
-                .collect();
-            for pred in &self.cfg.get_predecessor_labels(label) {
-                for dom_pred in dominators.get(pred).unwrap().difference(&strict_doms) {
-                    self.solution
-                        .get_mut(dom_pred)
-                        .unwrap()
-                        .insert(label.clone());
-                }
-            }
+        let frontiers = self.cfg.dominance_frontiers();
+        for (label, frontier) in self.solution.iter_mut() {
+            frontier.extend(frontiers.frontier(label).iter().cloned());
         }
+
+        // control dependence proper is computed on post-dominators
+        self.deps = dominance::control_dependences(&self.cfg);
     }
 }
 
@@ -3,10 +3,11 @@ use cs260::lir;
 use cs260::utils;
 
 fn main() {
-    // accept command line arguments (./ctrl_analysis <json_file> <func_name>)
+    // accept command line arguments (./ctrl_analysis <json_file> <func_name> [--deps])
+    // the dominance frontiers are printed by default, and the control dependences with `--deps`
     let args: Vec<String> = std::env::args().collect();
-    if args.len() != 3 {
-        println!("Usage: cargo run --bin ctrl_analysis <json_file> <func_name>");
+    if args.len() != 3 && !(args.len() == 4 && args[3] == "--deps") {
+        println!("Usage: cargo run --bin ctrl_analysis <json_file> <func_name> [--deps]");
         std::process::exit(1);
     }
     let json_fname = &args[1];
@@ -18,8 +19,15 @@ fn main() {
         println!("func_name: {}", func_name);
     }
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     let mut analyzer = abs::execution::ControlDependenceAnalyzer::new(prog, &func_name);
     analyzer.execute();
-    utils::display_ctrl_solution(&analyzer.solution);
+    if args.len() == 4 {
+        utils::display_ctrl_deps(&analyzer.deps);
+    } else {
+        utils::display_ctrl_solution(&analyzer.solution);
+    }
 }
@@ -21,7 +21,10 @@ fn main() {
         println!("func_name: {}", func_name);
     }
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     let cfg = cfg::ControlFlowGraph::from_function(&prog, &func_name);
     let _ = cfg.to_graphml_file(&filename);
 }
@@ -19,13 +19,17 @@ fn main() {
         println!("func_name: {}", func_name);
     }
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     let mut analyzer = abs::execution::ReachingDefinitionAnalyzer::new(prog, &func_name);
     analyzer.mfp();
 
+    // the CFG with the reaching definitions after each block, see visualize_cfg.sh
     let _ = analyzer
         .cfg
-        .to_dot_file(format!("{}.dot", func_name).as_str());
+        .to_detailed_dot_file_with(format!("{}.dot", func_name).as_str(), &analyzer.bb2store);
     
     #[cfg(debug_assertions)]
     {
@@ -9,7 +9,10 @@ fn main() {
     for path in paths {
         let path = path.unwrap().path();
         let path_str = path.to_str().unwrap();
-        let prog = lir::Program::parse_json(&path_str);
+        let prog = lir::Program::parse_json(&path_str).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
         let func = prog.functions.get("test").unwrap();
         for (_, block) in &func.body {
             for instr in &block.insts {
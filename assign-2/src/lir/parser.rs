@@ -0,0 +1,775 @@
+/*
+Parser of the textual LIR format (see lir-description.md).
+
+It builds the same Program/Function/Block/Instruction values that lir2json + serde produce,
+including the type and scope of every Variable occurrence.
+*/
+use super::error::LirError;
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(i64),
+    Op(String), // `$addrof`, `$branch`, ...
+    Arrow,      // `->`
+    Punct(char),
+    Newline,
+    Eof,
+}
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Ident(id) => write!(f, "`{}`", id),
+            Tok::Num(n) => write!(f, "`{}`", n),
+            Tok::Op(op) => write!(f, "`{}`", op),
+            Tok::Arrow => write!(f, "`->`"),
+            Tok::Punct(c) => write!(f, "`{}`", c),
+            Tok::Newline => write!(f, "end of line"),
+            Tok::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+    col: usize,
+}
+
+type PResult<T> = Result<T, LirError>;
+
+fn tokenize(src: &str) -> PResult<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut col) = (0, 1, 1);
+
+    let is_ident_char = |c: char| c == '_' || c == '.' || c.is_ascii_alphanumeric();
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start_line, start_col) = (line, col);
+        let mut push = |tok: Tok| {
+            tokens.push(Token {
+                tok,
+                line: start_line,
+                col: start_col,
+            })
+        };
+
+        if c == '\n' {
+            push(Tok::Newline);
+            i += 1;
+            line += 1;
+            col = 1;
+            continue;
+        }
+        if c == ' ' || c == '\t' || c == '\r' {
+            i += 1;
+            col += 1;
+            continue;
+        }
+        if c == '/' && i + 1 < chars.len() && chars[i + 1] == '/' {
+            // comment up to end of line
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+                col += 1;
+            }
+            continue;
+        }
+
+        let start = i;
+        if c.is_ascii_alphabetic() || c == '_' {
+            // id ::= ((`_`+ [a-zA-Z0-9]) | [a-zA-Z]) (`_` | `.` | [a-zA-Z0-9])*
+            while i < chars.len() && chars[i] == '_' {
+                i += 1;
+            }
+            if i > start && (i == chars.len() || !chars[i].is_ascii_alphanumeric()) {
+                if i - start == 1 && c == '_' {
+                    // a lone `_` means "no return type"
+                    push(Tok::Punct('_'));
+                    col += 1;
+                    continue;
+                }
+                return Err(LirError::new("", line, col, "malformed identifier"));
+            }
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            push(Tok::Ident(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit()
+            || (c == '-' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit())
+        {
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match text.parse::<i64>() {
+                Ok(n) => push(Tok::Num(n)),
+                Err(_) => {
+                    return Err(LirError::new(
+                        "",
+                        line,
+                        col,
+                        &format!("integer constant `{}` out of range", text),
+                    ))
+                }
+            }
+        } else if c == '-' && i + 1 < chars.len() && chars[i + 1] == '>' {
+            i += 2;
+            push(Tok::Arrow);
+        } else if c == '$' {
+            i += 1;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            push(Tok::Op(chars[start..i].iter().collect()));
+        } else if "{}()[]:,=&".contains(c) {
+            i += 1;
+            push(Tok::Punct(c));
+        } else {
+            return Err(LirError::new(
+                "",
+                line,
+                col,
+                &format!("unexpected character `{}`", c),
+            ));
+        }
+        col += i - start;
+    }
+    tokens.push(Token {
+        tok: Tok::Eof,
+        line,
+        col,
+    });
+    Ok(tokens)
+}
+
+struct FunctionHeader {
+    id: String,
+    ret_ty: Option<Type>,
+    params: Vec<Variable>,
+    locals: Vec<Variable>,
+    body_start: usize, // token index of the first basic block
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, offset: usize) -> &Tok {
+        let idx = usize::min(self.pos + offset, self.tokens.len() - 1);
+        &self.tokens[idx].tok
+    }
+
+    fn advance(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].tok.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error<T>(&self, msg: String) -> PResult<T> {
+        let token = &self.tokens[self.pos];
+        Err(LirError::new("", token.line, token.col, &msg))
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Tok::Newline = self.peek() {
+            self.advance();
+        }
+    }
+
+    fn expect_punct(&mut self, c: char, context: &str) -> PResult<()> {
+        if self.peek() == &Tok::Punct(c) {
+            self.advance();
+            Ok(())
+        } else {
+            self.error(format!(
+                "expected `{}` {}, found {}",
+                c,
+                context,
+                self.peek()
+            ))
+        }
+    }
+
+    fn expect_arrow(&mut self, context: &str) -> PResult<()> {
+        if self.peek() == &Tok::Arrow {
+            self.advance();
+            Ok(())
+        } else {
+            self.error(format!("expected `->` {}, found {}", context, self.peek()))
+        }
+    }
+
+    fn expect_newline(&mut self) -> PResult<()> {
+        match self.peek() {
+            Tok::Newline => {
+                self.advance();
+                Ok(())
+            }
+            Tok::Eof | Tok::Punct('}') => Ok(()),
+            tok => self.error(format!("expected end of line, found {}", tok)),
+        }
+    }
+
+    fn expect_ident(&mut self, what: &str) -> PResult<String> {
+        match self.peek().clone() {
+            Tok::Ident(id) => {
+                self.advance();
+                Ok(id)
+            }
+            tok => self.error(format!("expected {}, found {}", what, tok)),
+        }
+    }
+
+    fn parse_type(&mut self, context: &str) -> PResult<Type> {
+        match self.peek().clone() {
+            Tok::Ident(id) => {
+                self.advance();
+                if id == "int" {
+                    Ok(Type::Int)
+                } else {
+                    Ok(Type::Struct(id))
+                }
+            }
+            Tok::Punct('&') => {
+                self.advance();
+                let pointee = self.parse_type("after `&`")?;
+                Ok(Type::Pointer(Box::new(pointee)))
+            }
+            Tok::Punct('(') => Ok(Type::Function(Box::new(self.parse_func_type()?))),
+            _ => self.error(format!("expected type {}", context)),
+        }
+    }
+
+    fn parse_func_type(&mut self) -> PResult<FunctionType> {
+        // func_type ::= `(` (type (`,` type)*)? `)` `->` return_type
+        self.expect_punct('(', "to start a function type")?;
+        let mut param_ty = Vec::new();
+        if self.peek() != &Tok::Punct(')') {
+            param_ty.push(self.parse_type("in parameter list")?);
+            while self.peek() == &Tok::Punct(',') {
+                self.advance();
+                param_ty.push(self.parse_type("after `,`")?);
+            }
+        }
+        self.expect_punct(')', "to close the parameter types")?;
+        self.expect_arrow("in function type")?;
+        let ret_ty = self.parse_ret_type()?;
+        Ok(FunctionType { ret_ty, param_ty })
+    }
+
+    fn parse_ret_type(&mut self) -> PResult<Option<Type>> {
+        if self.peek() == &Tok::Punct('_') {
+            self.advance();
+            Ok(None)
+        } else {
+            Ok(Some(self.parse_type("after `->`")?))
+        }
+    }
+
+    fn parse_typed_id(&mut self, what: &str, scope: &Option<String>) -> PResult<Variable> {
+        let name = self.expect_ident(what)?;
+        self.expect_punct(':', &format!("after `{}`", name))?;
+        let typ = self.parse_type("after `:`")?;
+        Ok(Variable {
+            name,
+            typ,
+            scope: scope.clone(),
+        })
+    }
+
+    fn parse_struct(&mut self, prog: &mut Program) -> PResult<()> {
+        // struct_def ::= `struct` id `{` `\n` (id `:` type `\n`)+ `}` `\n`
+        self.advance();
+        let name = self.expect_ident("struct name")?;
+        self.expect_punct('{', "after struct name")?;
+        self.skip_newlines();
+        let mut fields = Vec::new();
+        while self.peek() != &Tok::Punct('}') {
+            let field = self.parse_typed_id("field name", &None)?;
+            fields.push(Field {
+                name: field.name,
+                typ: field.typ,
+            });
+            self.expect_newline()?;
+            self.skip_newlines();
+        }
+        self.advance();
+        if fields.is_empty() {
+            return self.error(format!("struct `{}` has no fields", name));
+        }
+        prog.structs.insert(name, fields);
+        self.expect_newline()
+    }
+
+    fn parse_extern(&mut self, prog: &mut Program) -> PResult<()> {
+        // extern_decl ::= `extern` id `:` func_type `\n`
+        self.advance();
+        let name = self.expect_ident("extern function name")?;
+        self.expect_punct(':', &format!("after `{}`", name))?;
+        if self.peek() != &Tok::Punct('(') {
+            return self.error("expected function type after `:`".to_string());
+        }
+        let typ = Type::Function(Box::new(self.parse_func_type()?));
+        prog.externs.insert(name, typ);
+        self.expect_newline()
+    }
+
+    fn parse_function_header(&mut self) -> PResult<FunctionHeader> {
+        // function_def ::= `fn` id `(` parameters? `)` `->` return_type `{` `\n` body `}` `\n`
+        self.advance();
+        let id = self.expect_ident("function name")?;
+        let scope = Some(id.clone());
+        self.expect_punct('(', "after function name")?;
+        let mut params = Vec::new();
+        if self.peek() != &Tok::Punct(')') {
+            params.push(self.parse_typed_id("parameter name", &scope)?);
+            while self.peek() == &Tok::Punct(',') {
+                self.advance();
+                params.push(self.parse_typed_id("parameter name", &scope)?);
+            }
+        }
+        self.expect_punct(')', "to close the parameter list")?;
+        self.expect_arrow("after parameter list")?;
+        let ret_ty = self.parse_ret_type()?;
+        self.expect_punct('{', "to start function body")?;
+        self.skip_newlines();
+
+        // decl ::= `let` id `:` type (`,` id `:` type)* `\n`
+        let mut locals = Vec::new();
+        if self.peek() == &Tok::Ident("let".to_string()) && self.peek_at(1) != &Tok::Punct(':') {
+            self.advance();
+            locals.push(self.parse_typed_id("local variable name", &scope)?);
+            while self.peek() == &Tok::Punct(',') {
+                self.advance();
+                locals.push(self.parse_typed_id("local variable name", &scope)?);
+            }
+            self.expect_newline()?;
+        }
+        // lir2json lists locals by name
+        locals.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // the body is parsed once all globals are known, skip it for now
+        let body_start = self.pos;
+        loop {
+            match self.peek() {
+                Tok::Punct('}') => break,
+                Tok::Eof => return self.error(format!("unterminated body of function `{}`", id)),
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+        self.advance();
+        self.expect_newline()?;
+
+        Ok(FunctionHeader {
+            id,
+            ret_ty,
+            params,
+            locals,
+            body_start,
+        })
+    }
+
+    fn parse_program(&mut self) -> PResult<Program> {
+        // program ::= (struct_def | global_def | extern_decl | function_def)+
+        let mut prog = Program::new();
+        let mut headers = Vec::new();
+        self.skip_newlines();
+        while self.peek() != &Tok::Eof {
+            match self.peek().clone() {
+                Tok::Ident(kw) if kw == "struct" && self.peek_at(1) != &Tok::Punct(':') => {
+                    self.parse_struct(&mut prog)?
+                }
+                Tok::Ident(kw) if kw == "extern" && self.peek_at(1) != &Tok::Punct(':') => {
+                    self.parse_extern(&mut prog)?
+                }
+                Tok::Ident(kw) if kw == "fn" && self.peek_at(1) != &Tok::Punct(':') => {
+                    headers.push(self.parse_function_header()?)
+                }
+                Tok::Ident(_) => {
+                    // global_def ::= id `:` type `\n`
+                    let global = self.parse_typed_id("global name", &None)?;
+                    prog.globals.push(global);
+                    self.expect_newline()?;
+                }
+                tok => {
+                    return self.error(format!(
+                        "expected `struct`, `extern`, `fn` or a global declaration, found {}",
+                        tok
+                    ))
+                }
+            }
+            self.skip_newlines();
+        }
+        // lir2json lists globals by name
+        prog.globals.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for header in headers {
+            self.pos = header.body_start;
+            let body = FunctionScope {
+                prog: &prog,
+                params: &header.params,
+                locals: &header.locals,
+            }
+            .parse_body(self)?;
+            prog.functions.insert(
+                header.id.clone(),
+                Function {
+                    id: header.id,
+                    ret_ty: header.ret_ty,
+                    params: header.params,
+                    locals: header.locals,
+                    body,
+                },
+            );
+        }
+        Ok(prog)
+    }
+}
+
+// Name resolution context for parsing the basic blocks of one function.
+struct FunctionScope<'a> {
+    prog: &'a Program,
+    params: &'a [Variable],
+    locals: &'a [Variable],
+}
+
+impl FunctionScope<'_> {
+    fn lookup(&self, parser: &Parser, name: &str) -> PResult<Variable> {
+        self.locals
+            .iter()
+            .chain(self.params.iter())
+            .chain(self.prog.globals.iter())
+            .find(|v| v.name == name)
+            .cloned()
+            .map_or_else(|| parser.error(format!("unknown variable `{}`", name)), Ok)
+    }
+
+    fn variable(&self, parser: &mut Parser) -> PResult<Variable> {
+        let name = parser.expect_ident("variable")?;
+        parser.pos -= 1;
+        let var = self.lookup(parser, &name)?;
+        parser.advance();
+        Ok(var)
+    }
+
+    fn operand(&self, parser: &mut Parser) -> PResult<Operand> {
+        match parser.peek().clone() {
+            Tok::Num(n) => {
+                if n < i32::MIN as i64 || n > i32::MAX as i64 {
+                    return parser.error(format!("integer constant `{}` out of range", n));
+                }
+                parser.advance();
+                Ok(Operand::CInt(n as i32))
+            }
+            Tok::Ident(_) => Ok(Operand::Var(self.variable(parser)?)),
+            tok => parser.error(format!("expected operand, found {}", tok)),
+        }
+    }
+
+    fn args(&self, parser: &mut Parser) -> PResult<Vec<Operand>> {
+        parser.expect_punct('(', "before call arguments")?;
+        let mut args = Vec::new();
+        if parser.peek() != &Tok::Punct(')') {
+            args.push(self.operand(parser)?);
+            while parser.peek() == &Tok::Punct(',') {
+                parser.advance();
+                args.push(self.operand(parser)?);
+            }
+        }
+        parser.expect_punct(')', "after call arguments")?;
+        Ok(args)
+    }
+
+    fn parse_body(&self, parser: &mut Parser) -> PResult<HashMap<String, Block>> {
+        // body ::= decl? block+
+        let mut body = HashMap::new();
+        parser.skip_newlines();
+        while parser.peek() != &Tok::Punct('}') {
+            let block = self.parse_block(parser)?;
+            if body.contains_key(&block.id) {
+                return parser.error(format!("duplicate basic block `{}`", block.id));
+            }
+            body.insert(block.id.clone(), block);
+            parser.skip_newlines();
+        }
+        if body.is_empty() {
+            return parser.error("expected at least one basic block".to_string());
+        }
+        Ok(body)
+    }
+
+    fn parse_block(&self, parser: &mut Parser) -> PResult<Block> {
+        // block ::= id `:` `\n` inst* term
+        let id = parser.expect_ident("basic block label")?;
+        parser.expect_punct(':', "after basic block label")?;
+        parser.skip_newlines();
+        let mut insts = Vec::new();
+        loop {
+            match self.parse_statement(parser)? {
+                Statement::Inst(inst) => insts.push(inst),
+                Statement::Term(term) => {
+                    return Ok(Block { id, insts, term });
+                }
+            }
+            parser.skip_newlines();
+        }
+    }
+
+    fn parse_statement(&self, parser: &mut Parser) -> PResult<Statement> {
+        let lhs = match (parser.peek().clone(), parser.peek_at(1)) {
+            (Tok::Ident(_), Tok::Punct('=')) => {
+                let lhs = self.variable(parser)?;
+                parser.advance();
+                Some(lhs)
+            }
+            (Tok::Op(_), _) => None,
+            (tok, _) => return parser.error(format!("expected instruction, found {}", tok)),
+        };
+        let op = match parser.peek().clone() {
+            Tok::Op(op) => op,
+            tok => return parser.error(format!("expected instruction after `=`, found {}", tok)),
+        };
+        let needs_lhs = |parser: &Parser| -> PResult<Variable> {
+            match &lhs {
+                Some(var) => Ok(var.clone()),
+                None => parser.error(format!("`{}` must be assigned to a variable", op)),
+            }
+        };
+        let no_lhs = |parser: &Parser| -> PResult<()> {
+            match &lhs {
+                Some(var) => parser.error(format!(
+                    "`{}` does not produce a value to assign to `{}`",
+                    op, var.name
+                )),
+                None => Ok(()),
+            }
+        };
+
+        let stmt = match op.as_str() {
+            "$addrof" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let rhs = self.variable(parser)?;
+                Statement::Inst(Instruction::AddrOf { lhs, rhs })
+            }
+            "$alloc" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let num = self.operand(parser)?;
+                parser.expect_punct('[', "before allocation id")?;
+                let name = parser.expect_ident("allocation id")?;
+                parser.expect_punct(']', "after allocation id")?;
+                // the allocation site is typed by what it holds, i.e. the pointee of lhs
+                let typ = match &lhs.typ {
+                    Type::Pointer(pointee) => pointee.as_ref().clone(),
+                    _ => {
+                        return parser.error(format!(
+                            "`$alloc` must be assigned to a pointer, `{}` is not",
+                            lhs.name
+                        ))
+                    }
+                };
+                let id = Variable {
+                    name,
+                    typ,
+                    scope: None,
+                };
+                Statement::Inst(Instruction::Alloc { lhs, num, id })
+            }
+            "$arith" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let aop = match parser.expect_ident("arithmetic operator")?.as_str() {
+                    "add" => ArithOp::Add,
+                    "sub" => ArithOp::Subtract,
+                    "mul" => ArithOp::Multiply,
+                    "div" => ArithOp::Divide,
+                    other => {
+                        parser.pos -= 1;
+                        return parser.error(format!("unknown arithmetic operator `{}`", other));
+                    }
+                };
+                let op1 = self.operand(parser)?;
+                let op2 = self.operand(parser)?;
+                Statement::Inst(Instruction::Arith { lhs, aop, op1, op2 })
+            }
+            "$cmp" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let rop = match parser.expect_ident("relational operator")?.as_str() {
+                    "eq" => RelaOp::Eq,
+                    "neq" => RelaOp::Neq,
+                    "lt" => RelaOp::Less,
+                    "lte" => RelaOp::LessEq,
+                    "gt" => RelaOp::Greater,
+                    "gte" => RelaOp::GreaterEq,
+                    other => {
+                        parser.pos -= 1;
+                        return parser.error(format!("unknown relational operator `{}`", other));
+                    }
+                };
+                let op1 = self.operand(parser)?;
+                let op2 = self.operand(parser)?;
+                Statement::Inst(Instruction::Cmp { lhs, rop, op1, op2 })
+            }
+            "$copy" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let op = self.operand(parser)?;
+                Statement::Inst(Instruction::Copy { lhs, op })
+            }
+            "$gep" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let src = self.variable(parser)?;
+                let idx = self.operand(parser)?;
+                Statement::Inst(Instruction::Gep { lhs, src, idx })
+            }
+            "$gfp" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let src = self.variable(parser)?;
+                let name = parser.expect_ident("field name")?;
+                parser.pos -= 1;
+                let field_typ = match &src.typ {
+                    Type::Pointer(pointee) => match pointee.as_ref() {
+                        Type::Struct(struct_name) => self
+                            .prog
+                            .structs
+                            .get(struct_name)
+                            .and_then(|fields| fields.iter().find(|f| f.name == name))
+                            .map(|f| f.typ.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let field = match field_typ {
+                    Some(typ) => Variable {
+                        name,
+                        typ,
+                        scope: None,
+                    },
+                    None => {
+                        return parser.error(format!(
+                            "`{}` is not a field of the struct pointed to by `{}`",
+                            name, src.name
+                        ))
+                    }
+                };
+                parser.advance();
+                Statement::Inst(Instruction::Gfp { lhs, src, field })
+            }
+            "$load" => {
+                let lhs = needs_lhs(parser)?;
+                parser.advance();
+                let src = self.variable(parser)?;
+                Statement::Inst(Instruction::Load { lhs, src })
+            }
+            "$store" => {
+                no_lhs(parser)?;
+                parser.advance();
+                let dst = self.variable(parser)?;
+                let op = self.operand(parser)?;
+                Statement::Inst(Instruction::Store { dst, op })
+            }
+            "$call_ext" => {
+                parser.advance();
+                let ext_callee = parser.expect_ident("extern function name")?;
+                let args = self.args(parser)?;
+                Statement::Inst(Instruction::CallExt {
+                    lhs,
+                    ext_callee,
+                    args,
+                })
+            }
+            "$branch" => {
+                no_lhs(parser)?;
+                parser.advance();
+                let cond = self.operand(parser)?;
+                let tt = parser.expect_ident("basic block label")?;
+                let ff = parser.expect_ident("basic block label")?;
+                Statement::Term(Terminal::Branch { cond, tt, ff })
+            }
+            "$jump" => {
+                no_lhs(parser)?;
+                parser.advance();
+                Statement::Term(Terminal::Jump(parser.expect_ident("basic block label")?))
+            }
+            "$ret" => {
+                no_lhs(parser)?;
+                parser.advance();
+                match parser.peek() {
+                    Tok::Newline | Tok::Eof | Tok::Punct('}') => {
+                        Statement::Term(Terminal::Ret(None))
+                    }
+                    _ => Statement::Term(Terminal::Ret(Some(self.operand(parser)?))),
+                }
+            }
+            "$call_dir" => {
+                parser.advance();
+                let callee = parser.expect_ident("function name")?;
+                let args = self.args(parser)?;
+                let next_bb = self.then_label(parser)?;
+                Statement::Term(Terminal::CallDirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                })
+            }
+            "$call_idr" => {
+                parser.advance();
+                let callee = self.variable(parser)?;
+                let args = self.args(parser)?;
+                let next_bb = self.then_label(parser)?;
+                Statement::Term(Terminal::CallIndirect {
+                    lhs,
+                    callee,
+                    args,
+                    next_bb,
+                })
+            }
+            _ => return parser.error(format!("unknown instruction `{}`", op)),
+        };
+        parser.expect_newline()?;
+        Ok(stmt)
+    }
+
+    fn then_label(&self, parser: &mut Parser) -> PResult<String> {
+        if parser.peek() != &Tok::Ident("then".to_string()) {
+            return parser.error(format!(
+                "expected `then` after call, found {}",
+                parser.peek()
+            ));
+        }
+        parser.advance();
+        parser.expect_ident("basic block label")
+    }
+}
+
+enum Statement {
+    Inst(Instruction),
+    Term(Terminal),
+}
+
+pub fn parse(src: &str) -> Result<Program, LirError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.parse_program()
+}
@@ -0,0 +1,293 @@
+/*
+Validity requirements of lir-description.md that are not already enforced by the grammar.
+
+Well-typedness is left to the type checker, everything else is reported here, one Diagnostic per
+violation, ordered by function and then by basic block.
+*/
+use super::*;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub func: Option<String>,  // None for program-level violations
+    pub block: Option<String>, // None for function-level violations
+    pub msg: String,
+}
+
+impl Diagnostic {
+    fn new(func: Option<&str>, block: Option<&str>, msg: String) -> Diagnostic {
+        Diagnostic {
+            func: func.map(|f| f.to_string()),
+            block: block.map(|b| b.to_string()),
+            msg,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.func, &self.block) {
+            (Some(func), Some(block)) => {
+                write!(f, "function `{}`, block `{}`: {}", func, block, self.msg)
+            }
+            (Some(func), None) => write!(f, "function `{}`: {}", func, self.msg),
+            _ => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn successors(term: &Terminal) -> Vec<&String> {
+    match term {
+        Terminal::Jump(label) => vec![label],
+        Terminal::Branch { tt, ff, .. } => vec![tt, ff],
+        Terminal::Ret(_) => vec![],
+        Terminal::CallDirect { next_bb, .. } => vec![next_bb],
+        Terminal::CallIndirect { next_bb, .. } => vec![next_bb],
+    }
+}
+
+// labels reachable from `start` following `edges` (label -> neighbor labels)
+fn reachable(start: &str, edges: &HashMap<&String, Vec<&String>>) -> HashSet<String> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([start.to_string()]);
+    while let Some(label) = queue.pop_front() {
+        if !visited.insert(label.clone()) {
+            continue;
+        }
+        if let Some(next) = edges.get(&label) {
+            queue.extend(next.iter().map(|l| l.to_string()));
+        }
+    }
+    visited
+}
+
+fn function_type(func: &Function) -> Type {
+    Type::Function(Box::new(FunctionType {
+        ret_ty: func.ret_ty.clone(),
+        param_ty: func.params.iter().map(|p| p.typ.clone()).collect(),
+    }))
+}
+
+impl Program {
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+
+        // main : () -> int
+        match self.functions.get("main") {
+            None => diags.push(Diagnostic::new(
+                None,
+                None,
+                "missing function `main`".to_string(),
+            )),
+            Some(main) => {
+                if !main.params.is_empty() || main.ret_ty != Some(Type::Int) {
+                    diags.push(Diagnostic::new(
+                        Some("main"),
+                        None,
+                        format!(
+                            "`main` must have type `() -> int`, found `{}`",
+                            function_type(main)
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for name in sorted_keys(&self.structs) {
+            if self.structs[name].is_empty() {
+                diags.push(Diagnostic::new(
+                    None,
+                    None,
+                    format!("struct `{}` has no fields", name),
+                ));
+            }
+        }
+
+        for name in sorted_keys(&self.externs) {
+            if self.functions.contains_key(name) {
+                diags.push(Diagnostic::new(
+                    None,
+                    None,
+                    format!("extern `{}` has the same name as a function", name),
+                ));
+            }
+        }
+
+        // a global named after a function is a pointer to that function
+        for global in &self.globals {
+            if global.name == "main" {
+                diags.push(Diagnostic::new(
+                    None,
+                    None,
+                    "global variable cannot be named `main`".to_string(),
+                ));
+            } else if let Some(func) = self.functions.get(&global.name) {
+                let expected = Type::Pointer(Box::new(function_type(func)));
+                if global.typ != expected {
+                    diags.push(Diagnostic::new(
+                        None,
+                        None,
+                        format!(
+                            "global `{}` must have type `{}` to point to function `{}`, found `{}`",
+                            global.name, expected, func.id, global.typ
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let mut alloc_ids: HashMap<&String, (&String, &String)> = HashMap::new();
+        for fname in sorted_keys(&self.functions) {
+            let func = &self.functions[fname];
+            self.validate_function(func, &mut alloc_ids, &mut diags);
+        }
+        diags
+    }
+
+    fn validate_function<'a>(
+        &self,
+        func: &'a Function,
+        alloc_ids: &mut HashMap<&'a String, (&'a String, &'a String)>,
+        diags: &mut Vec<Diagnostic>,
+    ) {
+        let fname = func.id.as_str();
+        let labels = sorted_keys(&func.body);
+
+        if !func.body.contains_key("entry") {
+            diags.push(Diagnostic::new(
+                Some(fname),
+                None,
+                "missing `entry` block".to_string(),
+            ));
+        }
+        let ret_blocks: Vec<&String> = labels
+            .iter()
+            .filter(|label| matches!(func.body[**label].term, Terminal::Ret(_)))
+            .cloned()
+            .collect();
+        if ret_blocks.is_empty() {
+            diags.push(Diagnostic::new(
+                Some(fname),
+                None,
+                "no block ends in `$ret`".to_string(),
+            ));
+        } else if ret_blocks.len() > 1 {
+            let found = ret_blocks
+                .iter()
+                .map(|l| format!("`{}`", l))
+                .collect::<Vec<String>>();
+            diags.push(Diagnostic::new(
+                Some(fname),
+                None,
+                format!(
+                    "expected exactly one block ending in `$ret`, found {}",
+                    found.join(", ")
+                ),
+            ));
+        }
+
+        let mut forward: HashMap<&String, Vec<&String>> = HashMap::new();
+        let mut backward: HashMap<&String, Vec<&String>> = HashMap::new();
+        for label in &labels {
+            let block = &func.body[*label];
+            if block.id != **label {
+                diags.push(Diagnostic::new(
+                    Some(fname),
+                    Some(label),
+                    format!("block is labeled `{}`", block.id),
+                ));
+            }
+
+            for inst in &block.insts {
+                match inst {
+                    Instruction::Alloc { id, .. } => match alloc_ids.get(&id.name) {
+                        Some((first_func, first_block)) => diags.push(Diagnostic::new(
+                            Some(fname),
+                            Some(label),
+                            format!(
+                                "`$alloc` id `{}` is already used in function `{}`, block `{}`",
+                                id.name, first_func, first_block
+                            ),
+                        )),
+                        None => {
+                            alloc_ids.insert(&id.name, (&func.id, &block.id));
+                        }
+                    },
+                    Instruction::CallExt { ext_callee, .. }
+                        if !self.externs.contains_key(ext_callee) =>
+                    {
+                        diags.push(Diagnostic::new(
+                            Some(fname),
+                            Some(label),
+                            format!("`$call_ext` to unknown extern `{}`", ext_callee),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+
+            match &block.term {
+                Terminal::CallDirect { callee, .. } if callee == "main" => {
+                    diags.push(Diagnostic::new(
+                        Some(fname),
+                        Some(label),
+                        "`main` cannot be called".to_string(),
+                    ))
+                }
+                Terminal::CallDirect { callee, .. } if !self.functions.contains_key(callee) => {
+                    diags.push(Diagnostic::new(
+                        Some(fname),
+                        Some(label),
+                        format!("`$call_dir` to unknown function `{}`", callee),
+                    ))
+                }
+                _ => {}
+            }
+
+            for succ in successors(&block.term) {
+                if func.body.contains_key(succ) {
+                    forward.entry(*label).or_default().push(succ);
+                    backward.entry(succ).or_default().push(*label);
+                } else {
+                    diags.push(Diagnostic::new(
+                        Some(fname),
+                        Some(label),
+                        format!("jump to unknown block `{}`", succ),
+                    ));
+                }
+            }
+        }
+
+        if func.body.contains_key("entry") {
+            let from_entry = reachable("entry", &forward);
+            for label in &labels {
+                if !from_entry.contains(*label) {
+                    diags.push(Diagnostic::new(
+                        Some(fname),
+                        Some(label),
+                        "block is unreachable from `entry`".to_string(),
+                    ));
+                }
+            }
+        }
+        if ret_blocks.len() == 1 {
+            let to_ret = reachable(ret_blocks[0], &backward);
+            for label in &labels {
+                if !to_ret.contains(*label) {
+                    diags.push(Diagnostic::new(
+                        Some(fname),
+                        Some(label),
+                        format!("block cannot reach the `$ret` block `{}`", ret_blocks[0]),
+                    ));
+                }
+            }
+        }
+    }
+}
@@ -82,6 +82,22 @@ pub fn display_ctrl_solution(solution: &HashMap<String, HashSet<String>>) {
     println!();
 }
 
+pub fn display_ctrl_deps(deps: &HashMap<String, Vec<(String, bool)>>) {
+    // deps maps each block to the branch blocks it depends on, e.g., `bb3 -> {bb1:true, bb2:false}`
+    let mut bbs: Vec<String> = deps.keys().cloned().collect();
+    bbs.sort();
+    for bb in bbs {
+        let dep_on: Vec<String> = deps
+            .get(&bb)
+            .unwrap()
+            .iter()
+            .map(|(branch, outcome)| format!("{}:{}", branch, outcome))
+            .collect();
+        println!("{} -> {{{}}}", bb, dep_on.join(", "));
+    }
+    println!();
+}
+
 pub fn able_to_reach_int(to: &Box<lir::Type>) -> bool {
     // "to" is the target pointed by a pointer
     /*
@@ -777,8 +777,10 @@ impl AbstractExecution for ReachingDefinitionAnalyzer {
         }
         */
 
+        let mut queued: HashSet<String> = self.worklist.iter().map(|b| b.id.clone()).collect();
         while !self.worklist.is_empty() {
             let block = self.worklist.pop_front().unwrap();
+            queued.remove(&block.id);
             let store_before = self.bb2store.get(&block.id).unwrap().clone();
             if &block != self.cfg.get_entry().unwrap() {
                 // join ann predecessors' stores
@@ -793,7 +795,7 @@ impl AbstractExecution for ReachingDefinitionAnalyzer {
             if store_before != store_after || visited.get(&block.id).unwrap() == &0 {
                 // add all successors to worklist
                 for succ in self.cfg.get_successors(&block) {
-                    if !self.worklist.contains(&succ) {
+                    if queued.insert(succ.id.clone()) {
                         // TODO: 这两行代码是否不需要？
                         self.worklist.push_back(succ.clone());
                     }
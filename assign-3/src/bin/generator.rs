@@ -11,7 +11,10 @@ fn main() {
     }
     let json_fname = &args[1];
 
-    let prog = lir::Program::parse_json(&json_fname);
+    let prog = lir::Program::parse_json(&json_fname).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     let mut analyzer = abs::execution::ConstraintGenerator::new(prog);
     // analyzer.execute();
     // utils::display_ctrl_solution(&analyzer.solution);
@@ -1,3 +1,4 @@
+use crate::dominance::DominatorTree;
 use crate::lir;
 use std::{
     collections::{HashMap, HashSet},
@@ -347,44 +348,20 @@ impl ControlFlowGraph {
     }
 
     pub fn get_dominators(&self, strict: bool) -> HashMap<String, HashSet<String>> {
-        let entry_label = "entry";
+        // blocks unreachable from entry are dominated only by themselves
+        let dom_tree = DominatorTree::new(self);
         let mut dominators = HashMap::new();
         for label in self.nodes.keys() {
-            dominators.insert(label.clone(), HashSet::new());
-        }
-
-        for label in self.nodes.keys() {
-            if label == entry_label {
-                continue;
-            }
-            let mut through_nodes = Vec::new();
-            for path in self.all_simple_paths(entry_label, label) {
-                through_nodes.push(path);
+            let mut bb_doms: HashSet<String> = dom_tree
+                .dominators(label)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            if strict {
+                bb_doms.remove(label);
             }
-            // set intersection all elements in through_nodes, assign to bb_doms
-            let bb_doms = through_nodes.iter().fold(HashSet::new(), |acc, path| {
-                if acc.is_empty() {
-                    return path.iter().cloned().collect();
-                }
-                acc.intersection(&path.iter().cloned().collect())
-                    .cloned()
-                    .collect()
-            });
             dominators.insert(label.clone(), bb_doms);
         }
-
-        if strict {
-            for label in self.nodes.keys() {
-                if dominators.get(label).unwrap().contains(label) {
-                    dominators.get_mut(label).unwrap().remove(label);
-                }
-            }
-        } else {
-            for label in self.nodes.keys() {
-                dominators.get_mut(label).unwrap().insert(label.clone());
-            }
-        }
-
         dominators
     }
 
@@ -403,19 +380,11 @@ impl ControlFlowGraph {
     }
 
     pub fn get_imm_dominators(&self) -> HashMap<String, HashSet<String>> {
-        let dominators = self.get_dominators(true);
+        let dom_tree = DominatorTree::new(self);
         let mut imm_dominators = HashMap::new();
         for label in self.nodes.keys() {
-            imm_dominators.insert(label.clone(), HashSet::new());
-        }
-        for label in self.nodes.keys() {
-            for dom in dominators.get(label).unwrap() {
-                let mut bb_doms_exclude_this = dominators.get(label).unwrap().clone();
-                bb_doms_exclude_this.remove(dom);
-                if bb_doms_exclude_this.is_subset(&dominators.get(dom).unwrap()) {
-                    imm_dominators.get_mut(label).unwrap().insert(dom.clone());
-                }
-            }
+            let idom = dom_tree.idom(label).map(String::from);
+            imm_dominators.insert(label.clone(), idom.into_iter().collect());
         }
         imm_dominators
     }
@@ -488,7 +457,7 @@ mod test {
 
     #[test]
     fn test_example_program() {
-        let prog = lir::Program::parse_json("./examples/json/tortoise_and_hare.json");
+        let prog = lir::Program::parse_json("./examples/json/tortoise_and_hare.json").unwrap();
         // let prog = lir::Program::parse_json("./examples/json/lambda.json");
 
         println!("number for basic blocks: {}", prog.get_num_basic_blocks());
@@ -502,7 +471,7 @@ mod test {
 
     #[test]
     fn test_example_function() {
-        let prog = lir::Program::parse_json("./demos/json/test3.json");
+        let prog = lir::Program::parse_json("./demos/json/test3.json").unwrap();
         let cfg: ControlFlowGraph = ControlFlowGraph::from_function(&prog, "test");
 
         // println!("{:#?}", prog);
@@ -0,0 +1,280 @@
+/*
+Dominator trees of control flow graphs.
+
+The immediate dominators are computed by the iterative algorithm of Cooper, Harvey and Kennedy
+("A Simple, Fast Dominance Algorithm"), which runs over the blocks in reverse postorder and is
+near-linear on the CFGs of real programs. Blocks unreachable from the root are not in the tree:
+they have no immediate dominator and are dominated only by themselves.
+*/
+use crate::cfg::ControlFlowGraph;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    root: String,
+    idom: HashMap<String, String>,
+    children: HashMap<String, Vec<String>>, // sorted by label
+    depth: HashMap<String, usize>,
+    interval: HashMap<String, (usize, usize)>, // preorder numbers of a block and its last descendant
+}
+
+impl DominatorTree {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        // rooted at `entry`, or else at the only block without predecessors (if there is one)
+        let mut root = "entry";
+        if !cfg.nodes.contains_key(root) {
+            let mut sources = cfg
+                .nodes
+                .keys()
+                .filter(|label| cfg.get_predecessor_labels(label).is_empty());
+            if let (Some(source), None) = (sources.next(), sources.next()) {
+                root = source;
+            }
+        }
+        Self::with_root(cfg, root)
+    }
+
+    pub fn with_root(cfg: &ControlFlowGraph, root: &str) -> Self {
+        let mut succs: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut preds: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (src, dst) in &cfg.edges {
+            succs.entry(src.as_str()).or_default().push(dst.as_str());
+            preds.entry(dst.as_str()).or_default().push(src.as_str());
+        }
+
+        // number the reachable blocks in postorder (iteratively, functions can be large)
+        let mut postorder: Vec<&str> = Vec::new();
+        let mut post_num: HashMap<&str, usize> = HashMap::new();
+        if cfg.nodes.contains_key(root) {
+            let mut visited = HashSet::from([root]);
+            let mut stack: Vec<(&str, usize)> = vec![(root, 0)];
+            while let Some((label, next)) = stack.pop() {
+                let label_succs = succs.get(label).map(|s| s.as_slice()).unwrap_or(&[]);
+                if next < label_succs.len() {
+                    stack.push((label, next + 1));
+                    let succ = label_succs[next];
+                    if cfg.nodes.contains_key(succ) && visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                } else {
+                    post_num.insert(label, postorder.len());
+                    postorder.push(label);
+                }
+            }
+        }
+
+        // idoms[b] is the postorder number of the immediate dominator of block b
+        let mut idoms: Vec<Option<usize>> = vec![None; postorder.len()];
+        if let Some(root_num) = idoms.len().checked_sub(1) {
+            idoms[root_num] = Some(root_num);
+        }
+        let intersect = |idoms: &[Option<usize>], mut b1: usize, mut b2: usize| {
+            while b1 != b2 {
+                while b1 < b2 {
+                    b1 = idoms[b1].unwrap();
+                }
+                while b2 < b1 {
+                    b2 = idoms[b2].unwrap();
+                }
+            }
+            b1
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..postorder.len().saturating_sub(1)).rev() {
+                let mut new_idom = None;
+                for pred in preds.get(postorder[b]).map(|p| p.as_slice()).unwrap_or(&[]) {
+                    let p = match post_num.get(pred) {
+                        Some(p) if idoms[*p].is_some() => *p,
+                        _ => continue, // unreachable, or not processed yet
+                    };
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(other) => intersect(&idoms, p, other),
+                    });
+                }
+                if new_idom.is_some() && idoms[b] != new_idom {
+                    idoms[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut tree = Self {
+            root: root.to_string(),
+            idom: HashMap::new(),
+            children: HashMap::new(),
+            depth: HashMap::new(),
+            interval: HashMap::new(),
+        };
+        for (b, idom) in idoms.iter().enumerate() {
+            let idom = idom.unwrap();
+            tree.children.entry(postorder[b].to_string()).or_default();
+            if idom != b {
+                tree.idom
+                    .insert(postorder[b].to_string(), postorder[idom].to_string());
+                tree.children
+                    .entry(postorder[idom].to_string())
+                    .or_default()
+                    .push(postorder[b].to_string());
+            }
+        }
+        for children in tree.children.values_mut() {
+            children.sort();
+        }
+
+        // number the tree in preorder for constant-time dominance queries
+        if !postorder.is_empty() {
+            let mut counter = 0;
+            let mut stack = vec![(tree.root.clone(), 0, false)];
+            while let Some((label, depth, done)) = stack.pop() {
+                if done {
+                    tree.interval.get_mut(&label).unwrap().1 = counter - 1;
+                    continue;
+                }
+                tree.depth.insert(label.clone(), depth);
+                tree.interval.insert(label.clone(), (counter, counter));
+                counter += 1;
+                stack.push((label.clone(), depth, true));
+                for child in tree.children[&label].iter().rev() {
+                    stack.push((child.clone(), depth + 1, false));
+                }
+            }
+        }
+        tree
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn is_reachable(&self, label: &str) -> bool {
+        self.depth.contains_key(label)
+    }
+
+    pub fn idom(&self, label: &str) -> Option<&str> {
+        self.idom.get(label).map(|idom| idom.as_str())
+    }
+
+    pub fn dominates(&self, a: &str, b: &str) -> bool {
+        // every block dominates itself, even an unreachable one
+        if a == b {
+            return true;
+        }
+        match (self.interval.get(a), self.interval.get(b)) {
+            (Some((a_first, a_last)), Some((b_first, _))) => {
+                a_first <= b_first && b_first <= a_last
+            }
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: &str, b: &str) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    pub fn children(&self, label: &str) -> impl Iterator<Item = &str> {
+        self.children
+            .get(label)
+            .into_iter()
+            .flatten()
+            .map(|child| child.as_str())
+    }
+
+    pub fn depth(&self, label: &str) -> Option<usize> {
+        self.depth.get(label).cloned()
+    }
+
+    pub fn dominators<'a>(&'a self, label: &'a str) -> Vec<&'a str> {
+        // the dominators of a block from itself up to the root
+        let mut doms = Vec::new();
+        let mut cur = Some(label);
+        while let Some(dom) = cur {
+            doms.push(dom);
+            cur = self.idom(dom);
+        }
+        doms
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lir;
+
+    fn constr_cfg(edges: &[(&str, &str)]) -> ControlFlowGraph {
+        let mut cfg = ControlFlowGraph::new();
+        for (src, dst) in edges {
+            for label in [src, dst] {
+                let block = lir::Block::new(label, &lir::Terminal::Ret(None));
+                cfg.nodes.insert(label.to_string(), block);
+            }
+            cfg.edges.push((src.to_string(), dst.to_string()));
+        }
+        cfg
+    }
+
+    #[test]
+    fn test_dominator_tree() {
+        // entry -> B -> {C, D} -> E, and a loop E -> B
+        let cfg = constr_cfg(&[
+            ("entry", "B"),
+            ("B", "C"),
+            ("B", "D"),
+            ("C", "E"),
+            ("D", "E"),
+            ("E", "B"),
+            ("F", "E"),
+        ]);
+        let tree = DominatorTree::new(&cfg);
+        assert_eq!(tree.root(), "entry");
+        assert_eq!(tree.idom("entry"), None);
+        assert_eq!(tree.idom("B"), Some("entry"));
+        assert_eq!(tree.idom("E"), Some("B"));
+        assert_eq!(tree.children("B").collect::<Vec<_>>(), vec!["C", "D", "E"]);
+        assert_eq!(tree.depth("E"), Some(2));
+        assert_eq!(tree.dominators("C"), vec!["C", "B", "entry"]);
+        assert!(tree.dominates("B", "E"));
+        assert!(tree.dominates("E", "E"));
+        assert!(!tree.strictly_dominates("E", "E"));
+        assert!(!tree.dominates("C", "E"));
+
+        // F is unreachable
+        assert!(!tree.is_reachable("F"));
+        assert_eq!(tree.idom("F"), None);
+        assert_eq!(tree.depth("F"), None);
+        assert!(tree.dominates("F", "F"));
+        assert!(!tree.dominates("entry", "F"));
+    }
+
+    #[test]
+    fn test_large_function() {
+        // a thousand sequential diamonds, whose simple paths could never be enumerated
+        let labels: Vec<[String; 4]> = (0..1000)
+            .map(|i| {
+                [
+                    format!("head{}", i),
+                    format!("then{}", i),
+                    format!("else{}", i),
+                    format!("join{}", i),
+                ]
+            })
+            .collect();
+        let mut edges = vec![("entry", labels[0][0].as_str())];
+        for (i, [head, then, els, join]) in labels.iter().enumerate() {
+            edges.extend([(head.as_str(), then.as_str()), (head, els)]);
+            edges.extend([(then.as_str(), join.as_str()), (els, join)]);
+            if let Some(next) = labels.get(i + 1) {
+                edges.push((join, &next[0]));
+            }
+        }
+        let cfg = constr_cfg(&edges);
+        let tree = DominatorTree::new(&cfg);
+        assert_eq!(tree.idom("join999"), Some("head999"));
+        assert_eq!(tree.idom("head999"), Some("join998"));
+        assert_eq!(tree.depth("join999"), Some(2000));
+        assert!(tree.dominates("head0", "join999"));
+        assert!(!tree.dominates("then0", "join999"));
+    }
+}
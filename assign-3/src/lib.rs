@@ -1,5 +1,6 @@
 pub mod abs;
 pub mod cfg;
+pub mod dominance;
 pub mod lir;
 pub mod store;
 pub mod utils;
@@ -7,7 +7,22 @@ use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+mod builder;
+mod error;
+mod parser;
+mod printer;
+mod validate;
+
+pub mod gen;
+pub mod interned;
+pub mod interp;
+pub mod typeck;
+
+pub use builder::{Arg, FunctionBuilder, ProgramBuilder};
+pub use error::LirError;
+pub use validate::Diagnostic;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Program {
     pub structs: HashMap<String, Vec<Field>>,
     pub globals: Vec<Variable>,
@@ -15,7 +30,7 @@ pub struct Program {
     pub externs: HashMap<String, Type>,       // external function declarations
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Field {
     pub name: String,
     pub typ: Type,
@@ -91,7 +106,7 @@ impl Variable {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Function {
     pub id: String,
     pub ret_ty: Option<Type>,
@@ -501,10 +516,22 @@ impl Program {
         blocks
     }
 
-    pub fn parse_json(fname: &str) -> Program {
-        let file = File::open(fname).expect("Failed to open file");
+    pub fn parse_json(fname: &str) -> Result<Program, LirError> {
+        let file = File::open(fname).map_err(|e| LirError::from_io(fname, &e))?;
         let reader = BufReader::new(file);
-        json::from_reader(reader).unwrap()
+        json::from_reader(reader).map_err(|e| LirError::from_json(fname, &e))
+    }
+
+    pub fn parse_lir(fname: &str) -> Result<Program, LirError> {
+        let src = std::fs::read_to_string(fname).map_err(|e| LirError::from_io(fname, &e))?;
+        Program::parse_lir_str(&src).map_err(|e| LirError {
+            file: fname.to_string(),
+            ..e
+        })
+    }
+
+    pub fn parse_lir_str(src: &str) -> Result<Program, LirError> {
+        parser::parse(src)
     }
 
     pub fn write_json(&self, fname: &str) {
@@ -513,6 +540,10 @@ impl Program {
         json::to_writer(writer, self).unwrap();
     }
 
+    pub fn write_lir(&self, fname: &str) {
+        std::fs::write(fname, self.to_string()).expect("Failed to create file");
+    }
+
     pub fn get_num_fields(&self) -> u32 {
         self.structs.values().map(|s| s.len()).sum::<usize>() as u32
     }
@@ -743,7 +774,7 @@ mod test {
     #[test]
     fn test_get_globals() {
         let prog_name = "./examples/json/lambda.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
         let global_ints = prog.get_int_globals();
         let param_ints = prog.get_int_parameters("main");
         let local_ints = prog.get_int_locals("main");
@@ -768,7 +799,7 @@ mod test {
     #[test]
     fn test_reachable_types() {
         let prog_name = "./examples/json/lambda.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
 
         let globals = &prog.globals;
         let structs = &prog.structs;
@@ -800,7 +831,7 @@ mod test {
     #[test]
     fn test_get_variables() {
         let prog_name = "./examples/json/lambda.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
         let global_ints = prog.get_int_globals();
         let global_ptrs = prog.get_ptr_globals();
         let local_ints = prog.get_int_locals("add");
@@ -847,14 +878,14 @@ mod test {
     #[test]
     fn test_get_addr_taken() {
         let prog_name = "./examples/json/lambda.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
         let addr_taken = prog.get_addr_taken("add");
         for (i, var) in addr_taken.iter().enumerate() {
             println!("{}: {:#?}", i, var);
         }
 
         let prog_name = "./demos/json/test8.json";
-        let prog = Program::parse_json(prog_name);
+        let prog = Program::parse_json(prog_name).unwrap();
         let addr_taken = prog.get_addr_taken("test");
         for (i, var) in addr_taken.iter().enumerate() {
             println!("{}: {:#?}", i, var);
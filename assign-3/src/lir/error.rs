@@ -0,0 +1,53 @@
+/*
+Errors raised while reading a LIR program, either from `.lir` text or from its JSON form.
+*/
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LirError {
+    pub file: String,
+    pub line: usize, // 1-based, 0 if the error is not tied to a position (e.g., file not found)
+    pub column: usize, // 1-based, 0 if the error is not tied to a position
+    pub msg: String,
+}
+
+impl LirError {
+    pub fn new(file: &str, line: usize, column: usize, msg: &str) -> LirError {
+        LirError {
+            file: file.to_string(),
+            line,
+            column,
+            msg: msg.to_string(),
+        }
+    }
+
+    pub fn from_io(file: &str, err: &std::io::Error) -> LirError {
+        LirError::new(file, 0, 0, &format!("cannot read file: {}", err))
+    }
+
+    pub fn from_json(file: &str, err: &serde_json::Error) -> LirError {
+        // serde_json appends " at line X column Y" to its message, which we already keep apart
+        let msg = err.to_string();
+        let msg = match msg.rsplit_once(" at line ") {
+            Some((msg, _)) if err.line() > 0 => msg.to_string(),
+            _ => msg,
+        };
+        LirError::new(file, err.line(), err.column(), &msg)
+    }
+}
+
+impl fmt::Display for LirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.msg)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file, self.line, self.column, self.msg
+            )
+        }
+    }
+}
+
+impl std::error::Error for LirError {}
@@ -0,0 +1,795 @@
+/*
+Random generator of well-formed LIR programs, for fuzzing the analyses.
+
+A program is generated from a seed, so that a failure can be reproduced from the seed alone. Every
+function body is generated as nested statements (branches and loops) that are lowered to basic
+blocks on the fly, which makes every block reachable from `entry` and able to reach the single
+`$ret` block. Generated programs pass Program::validate and typeck::check, and also behave when
+run by the interpreter:
+- loops count up to a small constant with a counter nothing else writes to, and a function only
+  calls the functions defined after it, so every execution terminates;
+- pointers are only dereferenced if they were initialized (by `$alloc` or `$addrof`) in the
+  `entry` block, or are taken from a `$gep`/`$gfp` with an index/field known to be in bounds.
+The only trap left is a division by zero, for the rare divisors that are not constants.
+*/
+use super::*;
+
+const ARRAY_LEN: i32 = 4; // the number of elements of an array allocated by the generator
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub seed: u64,
+    pub max_functions: usize, // besides `main`
+    pub max_stmts: usize,     // per sequence of statements
+    pub max_depth: usize,     // nesting of branches and loops
+}
+
+impl Config {
+    pub fn new(seed: u64) -> Config {
+        Config {
+            seed,
+            max_functions: 3,
+            max_stmts: 6,
+            max_depth: 2,
+        }
+    }
+}
+
+pub fn generate(config: &Config) -> Program {
+    let mut gen = Gen {
+        config,
+        rng: Rng::new(config.seed),
+        structs: Vec::new(),
+        sigs: Vec::new(),
+        int_globals: Vec::new(),
+        ptr_globals: Vec::new(),
+        num_allocs: 0,
+    };
+    gen.program()
+}
+
+// SplitMix64, so that a seed gives the same program on every platform
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // a number in 0..n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // a number in lo..=hi
+    fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        lo + self.below((hi - lo + 1) as usize) as i32
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+fn int_ptr() -> Type {
+    Type::Pointer(Box::new(Type::Int))
+}
+
+fn struct_ptr(name: &str) -> Type {
+    Type::Pointer(Box::new(Type::Struct(name.to_string())))
+}
+
+fn global(name: &str, typ: Type) -> Variable {
+    Variable {
+        name: name.to_string(),
+        typ,
+        scope: None,
+    }
+}
+
+// program-wide state of the generator
+struct Gen<'a> {
+    config: &'a Config,
+    rng: Rng,
+    structs: Vec<(String, Vec<Field>)>,
+    sigs: Vec<(String, FunctionType)>, // all functions but `main`, in call order
+    int_globals: Vec<Variable>,
+    ptr_globals: Vec<Variable>, // `&int` globals, allocated first thing in `main`
+    num_allocs: usize,
+}
+
+impl<'a> Gen<'a> {
+    fn program(&mut self) -> Program {
+        let mut prog = Program::new();
+
+        for i in 0..self.rng.below(3) {
+            let name = format!("S{}", i);
+            let mut fields = vec![Field {
+                name: "val".to_string(),
+                typ: Type::Int,
+            }];
+            if self.rng.chance(50) {
+                fields.push(Field {
+                    name: "aux".to_string(),
+                    typ: Type::Int,
+                });
+            }
+            if self.rng.chance(50) {
+                fields.push(Field {
+                    name: "next".to_string(),
+                    typ: struct_ptr(&name),
+                });
+            }
+            prog.structs.insert(name.clone(), fields.clone());
+            self.structs.push((name, fields));
+        }
+
+        for i in 0..self.rng.below(3) {
+            self.int_globals.push(global(&format!("g{}", i), Type::Int));
+        }
+        if self.rng.chance(50) {
+            self.ptr_globals.push(global("gp", int_ptr()));
+        }
+
+        for name in ["input", "peek", "print"] {
+            let func_ty = match name {
+                "input" => FunctionType {
+                    ret_ty: Some(Type::Int),
+                    param_ty: vec![],
+                },
+                "peek" => FunctionType {
+                    ret_ty: Some(Type::Int),
+                    param_ty: vec![int_ptr()],
+                },
+                _ => FunctionType {
+                    ret_ty: None,
+                    param_ty: vec![Type::Int],
+                },
+            };
+            prog.externs
+                .insert(name.to_string(), Type::Function(Box::new(func_ty)));
+        }
+
+        let num_functions = self.rng.below(self.config.max_functions + 1);
+        for i in 1..=num_functions {
+            let mut param_ty = Vec::new();
+            for _ in 0..self.rng.below(4) {
+                param_ty.push(self.param_type());
+            }
+            let ret_ty = if self.rng.chance(75) {
+                Some(Type::Int)
+            } else {
+                None
+            };
+            self.sigs
+                .push((format!("f{}", i), FunctionType { ret_ty, param_ty }));
+        }
+
+        // functions are called indirectly through the globals named after them
+        let mut globals = self.int_globals.clone();
+        globals.extend(self.ptr_globals.iter().cloned());
+        for (name, sig) in &self.sigs {
+            let typ = Type::Pointer(Box::new(Type::Function(Box::new(sig.clone()))));
+            globals.push(global(name, typ));
+        }
+        globals.sort_by(|a, b| a.name.cmp(&b.name));
+        prog.globals = globals;
+
+        let main = FuncGen::new(self, 0, "main", Some(Type::Int), vec![]).function();
+        prog.functions.insert(main.id.clone(), main);
+        for i in 0..self.sigs.len() {
+            let (name, sig) = self.sigs[i].clone();
+            let func = FuncGen::new(self, i + 1, &name, sig.ret_ty, sig.param_ty).function();
+            prog.functions.insert(func.id.clone(), func);
+        }
+        prog
+    }
+
+    fn param_type(&mut self) -> Type {
+        if !self.structs.is_empty() && self.rng.chance(15) {
+            let name = self.rng.pick(&self.structs).0.clone();
+            struct_ptr(&name)
+        } else if self.rng.chance(30) {
+            int_ptr()
+        } else {
+            Type::Int
+        }
+    }
+
+    fn alloc_id(&mut self, typ: &Type) -> Variable {
+        self.num_allocs += 1;
+        global(&format!("_alloc{}", self.num_allocs), typ.clone())
+    }
+}
+
+// the generator of a single function, lowering statements into the basic blocks of its body
+struct FuncGen<'g, 'a> {
+    gen: &'g mut Gen<'a>,
+    index: usize, // a function only calls the functions of greater index, `main` is 0
+    id: String,
+    ret_ty: Option<Type>,
+    params: Vec<Variable>,
+    locals: Vec<Variable>,
+    body: HashMap<String, Block>,
+    label: String, // the block under construction
+    insts: Vec<Instruction>,
+    num_blocks: usize,
+    num_temps: usize,
+    ints: Vec<Variable>,            // int variables the statements may assign to
+    counters: Vec<(Variable, i32)>, // counters of the enclosing loops, with their bounds
+    ptrs: Vec<Variable>,            // non-null `&int` variables
+    arrays: Vec<Variable>,          // `&int` variables pointing to ARRAY_LEN ints
+    struct_ptrs: Vec<Variable>,     // non-null pointers to structs
+}
+
+impl<'g, 'a> FuncGen<'g, 'a> {
+    fn new(
+        gen: &'g mut Gen<'a>,
+        index: usize,
+        id: &str,
+        ret_ty: Option<Type>,
+        param_ty: Vec<Type>,
+    ) -> FuncGen<'g, 'a> {
+        let params: Vec<Variable> = param_ty
+            .into_iter()
+            .enumerate()
+            .map(|(i, typ)| Variable {
+                name: format!("arg{}", i),
+                typ,
+                scope: Some(id.to_string()),
+            })
+            .collect();
+        let mut ints = gen.int_globals.clone();
+        let mut ptrs = gen.ptr_globals.clone();
+        let mut struct_ptrs = Vec::new();
+        for param in &params {
+            match &param.typ {
+                Type::Int => ints.push(param.clone()),
+                Type::Pointer(pointee) if **pointee == Type::Int => ptrs.push(param.clone()),
+                _ => struct_ptrs.push(param.clone()),
+            }
+        }
+        FuncGen {
+            gen,
+            index,
+            id: id.to_string(),
+            ret_ty,
+            params,
+            locals: Vec::new(),
+            body: HashMap::new(),
+            label: "entry".to_string(),
+            insts: Vec::new(),
+            num_blocks: 0,
+            num_temps: 0,
+            ints,
+            counters: Vec::new(),
+            ptrs,
+            arrays: Vec::new(),
+            struct_ptrs,
+        }
+    }
+
+    fn function(mut self) -> Function {
+        self.prologue();
+        let depth = self.gen.config.max_depth;
+        self.stmts(depth);
+        let term = match self.ret_ty {
+            Some(_) => Terminal::Ret(Some(self.int_operand())),
+            None => Terminal::Ret(None),
+        };
+        self.end_block(term, String::new());
+        self.locals.sort_by(|a, b| a.name.cmp(&b.name));
+        Function {
+            id: self.id,
+            ret_ty: self.ret_ty,
+            params: self.params,
+            locals: self.locals,
+            body: self.body,
+        }
+    }
+
+    fn local(&mut self, name: &str, typ: Type) -> Variable {
+        let var = Variable {
+            name: name.to_string(),
+            typ,
+            scope: Some(self.id.clone()),
+        };
+        self.locals.push(var.clone());
+        var
+    }
+
+    fn temp(&mut self, typ: Type) -> Variable {
+        self.num_temps += 1;
+        let name = format!("_t{}", self.num_temps);
+        self.local(&name, typ)
+    }
+
+    fn new_label(&mut self) -> String {
+        self.num_blocks += 1;
+        format!("bb{}", self.num_blocks)
+    }
+
+    // close the block under construction with `term` and continue in `next`
+    fn end_block(&mut self, term: Terminal, next: String) {
+        let label = std::mem::replace(&mut self.label, next);
+        let block = Block {
+            id: label.clone(),
+            insts: std::mem::take(&mut self.insts),
+            term,
+        };
+        self.body.insert(label, block);
+    }
+
+    fn alloc(&mut self, lhs: &Variable, num: i32) {
+        let pointee = match &lhs.typ {
+            Type::Pointer(pointee) => pointee.as_ref().clone(),
+            _ => unreachable!(),
+        };
+        let id = self.gen.alloc_id(&pointee);
+        self.insts.push(Instruction::Alloc {
+            lhs: lhs.clone(),
+            num: Operand::CInt(num),
+            id,
+        });
+    }
+
+    // initialize the pointers the statements rely on (and most int locals)
+    fn prologue(&mut self) {
+        if self.index == 0 {
+            for gp in self.gen.ptr_globals.clone() {
+                self.alloc(&gp, 1);
+            }
+        }
+        for i in 0..=self.gen.rng.below(3) {
+            let x = self.local(&format!("x{}", i), Type::Int);
+            // an uninitialized local is bottom for the analyses
+            if self.gen.rng.chance(90) {
+                let op = self.int_operand();
+                self.insts.push(Instruction::Copy { lhs: x.clone(), op });
+            }
+            self.ints.push(x);
+        }
+        let p = self.local("p0", int_ptr());
+        if self.gen.rng.chance(50) {
+            self.alloc(&p, 1);
+        } else {
+            let x = self.ints.last().unwrap().clone();
+            self.insts.push(Instruction::AddrOf {
+                lhs: p.clone(),
+                rhs: x,
+            });
+        }
+        self.ptrs.push(p);
+        let a = self.local("a0", int_ptr());
+        self.alloc(&a, ARRAY_LEN);
+        self.arrays.push(a);
+        for i in 0..self.gen.structs.len() {
+            let name = self.gen.structs[i].0.clone();
+            let s = self.local(&format!("s{}", i), struct_ptr(&name));
+            self.alloc(&s, 1);
+            self.struct_ptrs.push(s);
+        }
+    }
+
+    fn int_operand(&mut self) -> Operand {
+        let mut vars = self.ints.clone();
+        vars.extend(self.counters.iter().map(|(c, _)| c.clone()));
+        if vars.is_empty() || self.gen.rng.chance(30) {
+            Operand::CInt(self.gen.rng.range(-5, 5))
+        } else {
+            Operand::Var(self.gen.rng.pick(&vars).clone())
+        }
+    }
+
+    fn int_lhs(&mut self) -> Variable {
+        self.gen.rng.pick(&self.ints).clone()
+    }
+
+    fn stmts(&mut self, depth: usize) {
+        let num = 1 + self.gen.rng.below(self.gen.config.max_stmts);
+        for _ in 0..num {
+            self.stmt(depth);
+        }
+    }
+
+    // a statement nested in the current one, whose pointers are not in scope afterwards
+    fn nested(&mut self, depth: usize) {
+        let num_ptrs = self.ptrs.len();
+        self.stmts(depth - 1);
+        self.ptrs.truncate(num_ptrs);
+    }
+
+    fn stmt(&mut self, depth: usize) {
+        let kinds = if depth > 0 { 12 } else { 10 };
+        match self.gen.rng.below(kinds) {
+            0 | 1 => self.arith(),
+            2 => self.cmp(),
+            3 => {
+                let lhs = self.int_lhs();
+                let op = self.int_operand();
+                self.insts.push(Instruction::Copy { lhs, op });
+            }
+            4 => self.memory(),
+            5 => self.array(),
+            6 => self.field(),
+            7 => self.call_ext(),
+            8 => self.call(false),
+            9 => self.call(true),
+            10 => self.branch(depth),
+            _ => self.while_loop(depth),
+        }
+    }
+
+    fn arith(&mut self) {
+        let aop = self
+            .gen
+            .rng
+            .pick(&[
+                ArithOp::Add,
+                ArithOp::Subtract,
+                ArithOp::Multiply,
+                ArithOp::Divide,
+            ])
+            .clone();
+        let op1 = self.int_operand();
+        let op2 = if aop == ArithOp::Divide && self.gen.rng.chance(90) {
+            let n = self.gen.rng.range(1, 5);
+            Operand::CInt(if self.gen.rng.chance(50) { n } else { -n })
+        } else {
+            self.int_operand()
+        };
+        let lhs = self.int_lhs();
+        self.insts.push(Instruction::Arith { lhs, aop, op1, op2 });
+    }
+
+    fn rela_op(&mut self) -> RelaOp {
+        self.gen
+            .rng
+            .pick(&[
+                RelaOp::Neq,
+                RelaOp::Eq,
+                RelaOp::Less,
+                RelaOp::LessEq,
+                RelaOp::Greater,
+                RelaOp::GreaterEq,
+            ])
+            .clone()
+    }
+
+    fn cmp(&mut self) {
+        let rop = self.rela_op();
+        let lhs = self.int_lhs();
+        if self.gen.rng.chance(15) {
+            // a null check
+            let rop = if self.gen.rng.chance(50) {
+                RelaOp::Eq
+            } else {
+                RelaOp::Neq
+            };
+            let p = self.gen.rng.pick(&self.ptrs).clone();
+            self.insts.push(Instruction::Cmp {
+                lhs,
+                rop,
+                op1: Operand::Var(p),
+                op2: Operand::CInt(0),
+            });
+        } else {
+            let op1 = self.int_operand();
+            let op2 = self.int_operand();
+            self.insts.push(Instruction::Cmp { lhs, rop, op1, op2 });
+        }
+    }
+
+    // load from or store to an int through a pointer
+    fn access(&mut self, ptr: Variable) {
+        if self.gen.rng.chance(50) {
+            let lhs = self.int_lhs();
+            self.insts.push(Instruction::Load { lhs, src: ptr });
+        } else {
+            let op = self.int_operand();
+            self.insts.push(Instruction::Store { dst: ptr, op });
+        }
+    }
+
+    fn memory(&mut self) {
+        let locals: Vec<Variable> = self
+            .ints
+            .iter()
+            .filter(|x| x.scope.is_some())
+            .cloned()
+            .collect();
+        if !locals.is_empty() && self.gen.rng.chance(30) {
+            let x = self.gen.rng.pick(&locals).clone();
+            let p = self.temp(int_ptr());
+            self.insts.push(Instruction::AddrOf {
+                lhs: p.clone(),
+                rhs: x,
+            });
+            self.ptrs.push(p.clone());
+            self.access(p);
+        } else {
+            let p = self.gen.rng.pick(&self.ptrs).clone();
+            self.access(p);
+        }
+    }
+
+    fn array(&mut self) {
+        let a = self.gen.rng.pick(&self.arrays).clone();
+        // any enclosing loop counter is in bounds, see while_loop
+        let idx = if !self.counters.is_empty() && self.gen.rng.chance(60) {
+            Operand::Var(self.gen.rng.pick(&self.counters).0.clone())
+        } else {
+            Operand::CInt(self.gen.rng.range(0, ARRAY_LEN - 1))
+        };
+        let elem = self.temp(int_ptr());
+        self.insts.push(Instruction::Gep {
+            lhs: elem.clone(),
+            src: a,
+            idx,
+        });
+        self.access(elem);
+    }
+
+    fn field(&mut self) {
+        if self.struct_ptrs.is_empty() {
+            return self.memory();
+        }
+        let s = self.gen.rng.pick(&self.struct_ptrs).clone();
+        let struct_name = match &s.typ {
+            Type::Pointer(pointee) => match pointee.as_ref() {
+                Type::Struct(name) => name.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let fields = self
+            .gen
+            .structs
+            .iter()
+            .find(|(name, _)| *name == struct_name)
+            .unwrap()
+            .1
+            .clone();
+        let field = self.gen.rng.pick(&fields).clone();
+        let field_ptr = self.temp(Type::Pointer(Box::new(field.typ.clone())));
+        self.insts.push(Instruction::Gfp {
+            lhs: field_ptr.clone(),
+            src: s.clone(),
+            field: global(&field.name, field.typ.clone()),
+        });
+        if field.typ == Type::Int {
+            return self.access(field_ptr);
+        }
+        // link to a struct of the same type, or read the link (which may be null) back
+        if self.gen.rng.chance(50) {
+            let same: Vec<Variable> = self
+                .struct_ptrs
+                .iter()
+                .filter(|p| p.typ == s.typ)
+                .cloned()
+                .collect();
+            let op = Operand::Var(self.gen.rng.pick(&same).clone());
+            self.insts.push(Instruction::Store { dst: field_ptr, op });
+        } else {
+            let lhs = self.temp(field.typ);
+            self.insts.push(Instruction::Load {
+                lhs,
+                src: field_ptr,
+            });
+        }
+    }
+
+    fn call_ext(&mut self) {
+        match self.gen.rng.below(3) {
+            0 => {
+                let arg = self.int_operand();
+                self.insts.push(Instruction::CallExt {
+                    lhs: None,
+                    ext_callee: "print".to_string(),
+                    args: vec![arg],
+                });
+            }
+            1 => {
+                let lhs = self.int_lhs();
+                self.insts.push(Instruction::CallExt {
+                    lhs: Some(lhs),
+                    ext_callee: "input".to_string(),
+                    args: vec![],
+                });
+            }
+            _ => {
+                let lhs = self.int_lhs();
+                let p = self.gen.rng.pick(&self.ptrs).clone();
+                self.insts.push(Instruction::CallExt {
+                    lhs: Some(lhs),
+                    ext_callee: "peek".to_string(),
+                    args: vec![Operand::Var(p)],
+                });
+            }
+        }
+    }
+
+    // a direct or indirect call to one of the functions defined after this one
+    fn call(&mut self, indirect: bool) {
+        let callees = self.gen.sigs[self.index..].to_vec();
+        if callees.is_empty() {
+            return self.arith();
+        }
+        let (callee, sig) = self.gen.rng.pick(&callees).clone();
+        let mut args = Vec::new();
+        for typ in &sig.param_ty {
+            let arg = match typ {
+                Type::Int => self.int_operand(),
+                Type::Pointer(pointee) if **pointee == Type::Int => {
+                    Operand::Var(self.gen.rng.pick(&self.ptrs).clone())
+                }
+                _ => {
+                    let same: Vec<Variable> = self
+                        .struct_ptrs
+                        .iter()
+                        .filter(|p| p.typ == *typ)
+                        .cloned()
+                        .collect();
+                    Operand::Var(self.gen.rng.pick(&same).clone())
+                }
+            };
+            args.push(arg);
+        }
+        let lhs = match sig.ret_ty {
+            Some(_) if self.gen.rng.chance(80) => Some(self.int_lhs()),
+            _ => None,
+        };
+        let next_bb = self.new_label();
+        let term = if indirect {
+            let typ = Type::Pointer(Box::new(Type::Function(Box::new(sig.clone()))));
+            let callee_ptr = self.temp(typ.clone());
+            self.insts.push(Instruction::Copy {
+                lhs: callee_ptr.clone(),
+                op: Operand::Var(global(&callee, typ)),
+            });
+            Terminal::CallIndirect {
+                lhs,
+                callee: callee_ptr,
+                args,
+                next_bb: next_bb.clone(),
+            }
+        } else {
+            Terminal::CallDirect {
+                lhs,
+                callee,
+                args,
+                next_bb: next_bb.clone(),
+            }
+        };
+        self.end_block(term, next_bb);
+    }
+
+    fn branch(&mut self, depth: usize) {
+        let cond = if self.gen.rng.chance(70) {
+            let cond = self.temp(Type::Int);
+            let rop = self.rela_op();
+            let op1 = self.int_operand();
+            let op2 = self.int_operand();
+            self.insts.push(Instruction::Cmp {
+                lhs: cond.clone(),
+                rop,
+                op1,
+                op2,
+            });
+            Operand::Var(cond)
+        } else {
+            self.int_operand()
+        };
+        let tt = self.new_label();
+        let ff = self.new_label();
+        let join = self.new_label();
+        self.end_block(
+            Terminal::Branch {
+                cond,
+                tt: tt.clone(),
+                ff: ff.clone(),
+            },
+            tt,
+        );
+        self.nested(depth);
+        self.end_block(Terminal::Jump(join.clone()), ff);
+        if self.gen.rng.chance(70) {
+            self.nested(depth);
+        }
+        self.end_block(Terminal::Jump(join.clone()), join);
+    }
+
+    // `for (c = 0; c < bound; c++)`, where bound <= ARRAY_LEN so that `c` may index the arrays
+    fn while_loop(&mut self, depth: usize) {
+        let counter = self.temp(Type::Int);
+        let bound = self.gen.rng.range(1, ARRAY_LEN);
+        self.insts.push(Instruction::Copy {
+            lhs: counter.clone(),
+            op: Operand::CInt(0),
+        });
+        let header = self.new_label();
+        let body = self.new_label();
+        let exit = self.new_label();
+        self.end_block(Terminal::Jump(header.clone()), header.clone());
+        let cond = self.temp(Type::Int);
+        self.insts.push(Instruction::Cmp {
+            lhs: cond.clone(),
+            rop: RelaOp::Less,
+            op1: Operand::Var(counter.clone()),
+            op2: Operand::CInt(bound),
+        });
+        self.end_block(
+            Terminal::Branch {
+                cond: Operand::Var(cond),
+                tt: body.clone(),
+                ff: exit.clone(),
+            },
+            body,
+        );
+        self.counters.push((counter.clone(), bound));
+        self.nested(depth);
+        self.counters.pop();
+        self.insts.push(Instruction::Arith {
+            lhs: counter.clone(),
+            aop: ArithOp::Add,
+            op1: Operand::Var(counter),
+            op2: Operand::CInt(1),
+        });
+        self.end_block(Terminal::Jump(header), exit);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate_valid() {
+        for seed in 0..300 {
+            let prog = generate(&Config::new(seed));
+            let diags = prog.validate();
+            assert!(diags.is_empty(), "seed {}: {}", seed, diags[0]);
+            let errors = typeck::check(&prog);
+            assert!(errors.is_empty(), "seed {}: {}", seed, errors[0]);
+            assert_eq!(Program::parse_lir_str(&prog.to_string()).unwrap(), prog);
+        }
+    }
+
+    #[test]
+    fn test_generate_seeded() {
+        assert_eq!(generate(&Config::new(7)), generate(&Config::new(7)));
+        assert_ne!(generate(&Config::new(7)), generate(&Config::new(8)));
+
+        let mut config = Config::new(7);
+        config.max_functions = 0;
+        config.max_depth = 0;
+        let prog = generate(&config);
+        assert_eq!(prog.functions.len(), 1);
+        assert_eq!(prog.functions["main"].body.len(), 1);
+    }
+
+    #[test]
+    fn test_generate_runs() {
+        for seed in 0..300 {
+            let prog = generate(&Config::new(seed));
+            let mut interp = interp::Interpreter::new(&prog);
+            if let Err(trap) = interp.run() {
+                assert_eq!(trap.msg, "division by zero", "seed {}: {}", seed, trap);
+            }
+        }
+    }
+}